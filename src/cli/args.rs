@@ -0,0 +1,142 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: ignite [command] [options] <file> [-- script args...]
+
+Commands:
+    run <file.ign>        Compile and run a source file
+    compile <file.ign>    Compile a source file to bytecode (-o <out.igb>)
+    exec <file.igb>       Run a compiled bytecode file
+    disasm <file>         Print the instructions of a source or bytecode file
+    tokens <file.ign>     Print the lexed tokens of a source file
+    ast <file.ign>        Print the parsed nodes of a source file
+    repl                  Start an interactive session (the default with no command)
+    help                  Print this message
+
+Options:
+    -o, --output <path>   Output path for `compile` (defaults to <file>.igb)
+    -O, --opt             Run the AST and bytecode optimizers
+        --pre-run         Run the VM pre-run pass (inlines constants) before executing
+        --no-debug        Strip comments/nops from the bytecode
+        --no-expose       Don't expose interned names in runtime errors
+        --no-type-checks  Don't check annotated parameter and return types at runtime
+        --registers       Compile for the register VM (falls back to the stack VM for what it can't compile)
+        --gc-stress       Collect cycles on every allocation (slow, for testing the collector)
+        --inst            Print the compiled instructions before running
+        --trace           Print the last executed instruction after running
+        --bench           Run the program repeatedly and print the average time
+
+Anything after the file (or after `--`) is passed to the script as `Std::Env::args()`.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Run,
+    Compile,
+    Exec,
+    Disasm,
+    Tokens,
+    Ast,
+    Repl,
+    Help,
+}
+
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub command: Command,
+    pub file: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub optimize: bool,
+    pub pre_run: bool,
+    pub no_debug: bool,
+    pub no_expose: bool,
+    pub no_type_checks: bool,
+    pub registers: bool,
+    pub gc_stress: bool,
+    pub print_inst: bool,
+    pub trace: bool,
+    pub bench: bool,
+    pub script_args: Vec<String>,
+}
+
+impl Options {
+    pub fn new(command: Command) -> Self {
+        Self {
+            command,
+            file: None,
+            output: None,
+            optimize: false,
+            pre_run: false,
+            no_debug: false,
+            no_expose: false,
+            no_type_checks: false,
+            registers: false,
+            gc_stress: false,
+            print_inst: false,
+            trace: false,
+            bench: false,
+            script_args: vec![],
+        }
+    }
+
+    /// Parses `std::env::args()` (without the program name).
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.into_iter();
+
+        let command = match args.next().as_deref() {
+            Some("run") => Command::Run,
+            Some("compile") => Command::Compile,
+            Some("exec") => Command::Exec,
+            Some("disasm") => Command::Disasm,
+            Some("tokens") => Command::Tokens,
+            Some("ast") => Command::Ast,
+            Some("repl") | None => Command::Repl,
+            Some("help" | "-h" | "--help") => Command::Help,
+            Some(other) => return Err(format!("Unknown command `{other}`.")),
+        };
+
+        let mut options = Self::new(command);
+
+        while let Some(arg) = args.next() {
+            // Everything after the file belongs to the script
+            if options.file.is_some() && matches!(command, Command::Run | Command::Exec) {
+                if arg != "--" || !options.script_args.is_empty() {
+                    options.script_args.push(arg);
+                }
+                continue;
+            }
+
+            match arg.as_str() {
+                "-o" | "--output" => {
+                    let path = args
+                        .next()
+                        .ok_or_else(|| format!("`{arg}` expects a path."))?;
+                    options.output = Some(PathBuf::from(path));
+                }
+                "-O" | "--opt" => options.optimize = true,
+                "--pre-run" => options.pre_run = true,
+                "--no-debug" => options.no_debug = true,
+                "--no-expose" => options.no_expose = true,
+                "--no-type-checks" => options.no_type_checks = true,
+                "--registers" => options.registers = true,
+                "--gc-stress" => options.gc_stress = true,
+                "--inst" => options.print_inst = true,
+                "--trace" => options.trace = true,
+                "--bench" => options.bench = true,
+                "-h" | "--help" => options.command = Command::Help,
+                "--" => {
+                    options.script_args.extend(args.by_ref());
+                }
+
+                flag if flag.starts_with('-') => return Err(format!("Unknown option `{flag}`.")),
+                file if options.file.is_none() => options.file = Some(PathBuf::from(file)),
+                other => return Err(format!("Unexpected argument `{other}`.")),
+            }
+        }
+
+        if !matches!(options.command, Command::Help | Command::Repl) && options.file.is_none() {
+            return Err("Expected a file.".to_string());
+        }
+
+        Ok(options)
+    }
+}
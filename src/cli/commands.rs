@@ -0,0 +1,311 @@
+use crate::{
+    cli::{
+        args::{Command, Options, USAGE},
+        repl::Repl,
+    },
+    compiler::{compiler::Compiler, error::CompileError, register_compiler::RegisterCompiler},
+    language::{ast::AST, lexer::Lexer, nodes::Node, parser::Parser},
+    misc::source::{Location, excerpt, line_starts},
+    rc,
+    virtual_machine::{error::RuntimeResult, gc, vm::VM},
+};
+use std::{error::Error, fmt::Display, fs, path::Path};
+
+pub fn execute(options: Options) -> Result<(), Box<dyn Error>> {
+    match options.command {
+        Command::Help => println!("{USAGE}"),
+        Command::Repl => Repl::new(&options).start()?,
+        Command::Tokens => tokens(&options)?,
+        Command::Ast => ast(&options)?,
+        Command::Disasm => disasm(&options)?,
+        Command::Compile => compile(&options)?,
+        Command::Run => {
+            let mut vm = compile_file(&options)?;
+            run(&mut vm, &options);
+        }
+        Command::Exec => {
+            let mut vm = load_bytecode(&options)?;
+            run(&mut vm, &options);
+        }
+    }
+
+    Ok(())
+}
+
+fn read_source(path: &Path) -> Result<String, Box<dyn Error>> {
+    fs::read_to_string(path).map_err(|e| format!("Couldn't read `{}`: {e}", path.display()).into())
+}
+
+fn input_path(options: &Options) -> &Path {
+    options.file.as_deref().expect("Expected a file.")
+}
+
+fn is_bytecode(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "igb")
+}
+
+/////////////////////
+// PIPELINE
+/////////////////////
+
+/// Parses `text`, reporting every syntax error and warning found in it.
+pub fn parse_source(text: &str, file: &str, optimize: bool) -> Result<Vec<Node>, Box<dyn Error>> {
+    let tokens = Lexer::new(text).get_tokens();
+
+    let mut parser = Parser::new(text.to_string(), tokens);
+    let nodes = parser.parse_program();
+
+    let line_starts = line_starts(text);
+    for diagnostic in parser.diagnostics.iter() {
+        let location = diagnostic.location(file, &line_starts);
+
+        report(
+            &format!("{diagnostic}\n    at {location}"),
+            Some(&location),
+            Some(text),
+        );
+    }
+
+    let errors = parser.diagnostics.iter().filter(|d| d.is_error()).count();
+    if errors > 0 {
+        return Err(format!("Couldn't parse `{file}` due to {errors} error(s)").into());
+    }
+
+    let mut ast = AST::new(nodes);
+    if optimize {
+        ast.optimize();
+    }
+
+    Ok(ast.nodes)
+}
+
+fn compile_file(options: &Options) -> Result<VM, Box<dyn Error>> {
+    let path = input_path(options);
+    let file = path.to_string_lossy();
+    let text = read_source(path)?;
+
+    let nodes = parse_source(&text, &file, options.optimize)?;
+
+    let mut compiler = Compiler::new();
+    compiler.type_checks = !options.no_type_checks;
+    compiler.set_source(&file, &text);
+    for node in nodes.iter() {
+        compiler.compile_node(node);
+    }
+    compiler.resolve_type_hints();
+
+    // Errors can also point into the files it imports
+    let source_of = |location: Option<&Location>| match location {
+        Some(location) if *location.file != *file => fs::read_to_string(&*location.file).ok(),
+        _ => Some(text.clone()),
+    };
+
+    for w in compiler.warnings.iter() {
+        report(
+            w,
+            w.location.as_ref(),
+            source_of(w.location.as_ref()).as_deref(),
+        );
+    }
+
+    if !compiler.errors.is_empty() {
+        for e in compiler.errors.iter() {
+            report(
+                e,
+                e.location.as_ref(),
+                source_of(e.location.as_ref()).as_deref(),
+            );
+        }
+        return Err(format!(
+            "Couldn't compile `{file}` due to {} error(s)",
+            compiler.errors.len()
+        )
+        .into());
+    }
+
+    if options.optimize {
+        compiler.optimize();
+    }
+    if options.no_debug {
+        compiler.finalize_bytecode();
+    }
+
+    let mut vm = new_vm(options);
+    vm.constants = compiler.constants;
+    vm.instructions = rc!(compiler.instructions);
+    vm.intern_table = compiler.intern_table;
+    vm.files = compiler.files;
+    vm.lines = compiler.lines;
+
+    if options.registers {
+        compile_registers(&mut vm, &nodes, &text);
+    }
+
+    if options.pre_run {
+        vm.pre_run_pass();
+    }
+
+    Ok(vm)
+}
+
+/// Swaps the stack code in `vm` for register code, unless the program uses something the
+/// register compiler can't handle yet.
+fn compile_registers(vm: &mut VM, nodes: &[Node], text: &str) {
+    // The main file comes first in the stack compiler's `files`
+    let mut compiler = RegisterCompiler::new(0, text);
+
+    match compiler.compile(nodes) {
+        Ok(()) => {
+            vm.constants = compiler.constants;
+            vm.instructions = rc!(vec![]);
+            vm.register_code = rc!(compiler.instructions);
+            vm.lines = compiler.lines;
+        }
+        Err(what) => report(
+            &CompileError::warning(
+                format!(
+                    "The register compiler doesn't support {what} yet, running on the stack VM instead"
+                ),
+                None,
+            ),
+            None,
+            None,
+        ),
+    }
+}
+
+fn load_bytecode(options: &Options) -> Result<VM, Box<dyn Error>> {
+    let mut vm = new_vm(options);
+    vm.read_bytecode_file(&input_path(options).to_string_lossy())?;
+
+    if options.pre_run {
+        vm.pre_run_pass();
+    }
+
+    Ok(vm)
+}
+
+pub fn new_vm(options: &Options) -> VM {
+    let mut vm = VM::new();
+    vm.expose_interns = !options.no_expose;
+    vm.script_args = options.script_args.clone();
+    gc::set_stress(options.gc_stress);
+    vm
+}
+
+/////////////////////
+// COMMANDS
+/////////////////////
+
+fn tokens(options: &Options) -> Result<(), Box<dyn Error>> {
+    let text = read_source(input_path(options))?;
+
+    println!("{:#?}", Lexer::new(&text).get_tokens());
+    Ok(())
+}
+
+fn ast(options: &Options) -> Result<(), Box<dyn Error>> {
+    let path = input_path(options);
+    let text = read_source(path)?;
+
+    println!(
+        "{:#?}",
+        parse_source(&text, &path.to_string_lossy(), options.optimize)?
+    );
+    Ok(())
+}
+
+fn disasm(options: &Options) -> Result<(), Box<dyn Error>> {
+    let vm = if is_bytecode(input_path(options)) {
+        load_bytecode(options)?
+    } else {
+        compile_file(options)?
+    };
+
+    vm.print_instructions();
+    Ok(())
+}
+
+fn compile(options: &Options) -> Result<(), Box<dyn Error>> {
+    let mut vm = compile_file(options)?;
+
+    let output = options
+        .output
+        .clone()
+        .unwrap_or_else(|| input_path(options).with_extension("igb"));
+
+    vm.write_bytecode_file(&output.to_string_lossy())
+}
+
+fn run(vm: &mut VM, options: &Options) {
+    if options.print_inst {
+        println!("Compiled instructions:");
+        println!("---------------------------");
+        vm.print_instructions();
+
+        println!("\nRunning:");
+        println!("---------------------------");
+    }
+
+    let instructions_clone = vm.instructions.clone();
+    let register_code = vm.register_code.clone();
+
+    let result = if options.bench { bench(vm) } else { start(vm) };
+
+    if options.trace {
+        if vm.pos < instructions_clone.len() {
+            println!(
+                "Last Instruction ({}): {:?}",
+                vm.pos, instructions_clone[vm.pos]
+            );
+        } else if vm.pos < register_code.len() {
+            println!("Last Instruction ({}): {:?}", vm.pos, register_code[vm.pos]);
+        } else {
+            println!("Completed all instructions")
+        }
+    }
+
+    if let Err(e) = result {
+        // Bytecode doesn't carry the source, so read it back from where it was compiled
+        let source = e
+            .location
+            .as_ref()
+            .and_then(|location| fs::read_to_string(&*location.file).ok());
+
+        report(&e, e.location.as_ref(), source.as_deref());
+        std::process::exit(1);
+    }
+}
+
+/// Prints `error`, followed by the source line it points at when that is available.
+pub fn report(error: &dyn Display, location: Option<&Location>, source: Option<&str>) {
+    eprintln!("{error}");
+
+    if let Some(location) = location
+        && let Some(excerpt) = source.and_then(|source| excerpt(source, location))
+    {
+        eprintln!("{excerpt}");
+    }
+}
+
+/// Runs the chunk on whichever VM it was compiled for.
+fn start(vm: &mut VM) -> RuntimeResult<()> {
+    if vm.register_code.is_empty() {
+        vm.run(false, false)
+    } else {
+        vm.run_registers()
+    }
+}
+
+fn bench(vm: &mut VM) -> RuntimeResult<()> {
+    let runs = 1000;
+    let start_time = std::time::Instant::now();
+    for _ in 0..runs {
+        vm.pos = 0;
+        start(vm)?;
+    }
+    let avg = start_time.elapsed() / runs;
+    println!("avg: {:?}", avg);
+
+    Ok(())
+}
@@ -0,0 +1,273 @@
+use crate::{
+    cli::{
+        args::Options,
+        commands::{new_vm, parse_source, report},
+    },
+    compiler::compiler::Compiler,
+    language::{lexer::Lexer, nodes::Node, token::TokenKind},
+    misc::source::Location,
+    virtual_machine::{value::Value, vm::VM},
+};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+const HELP: &str = "\
+:help          Print this message
+:history       List the previous inputs
+!<n>           Run input <n> from the history again
+:quit, :exit   Leave the REPL";
+
+pub struct Repl {
+    compiler: Compiler,
+    vm: VM,
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+    /// Every evaluated input with the file name its errors are reported under
+    sources: Vec<(String, String)>,
+}
+
+impl Repl {
+    pub fn new(options: &Options) -> Self {
+        let history_file = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".ignite_history"));
+
+        // Inputs from previous sessions are kept so `!n` can reach them
+        let history = history_file
+            .as_ref()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|text| text.lines().map(unescape_entry).collect())
+            .unwrap_or_default();
+
+        Self {
+            compiler: Compiler::new(),
+            vm: new_vm(options),
+            history,
+            history_file,
+            sources: vec![],
+        }
+    }
+
+    pub fn start(&mut self) -> Result<(), Box<dyn Error>> {
+        println!("Ignite REPL. Type `:help` for commands, `:quit` to leave.");
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+        let mut buffer = String::new();
+
+        loop {
+            print!("{}", if buffer.is_empty() { ">> " } else { ".. " });
+            io::stdout().flush()?;
+
+            let Some(line) = lines.next() else {
+                println!();
+                break;
+            };
+            let line = line?;
+
+            if buffer.is_empty() {
+                match line.trim() {
+                    "" => continue,
+                    ":quit" | ":exit" => break,
+                    ":help" => {
+                        println!("{HELP}");
+                        continue;
+                    }
+                    ":history" => {
+                        for (i, entry) in self.history.iter().enumerate() {
+                            println!("{:>4}  {}", i + 1, entry.replace('\n', "\n      "));
+                        }
+                        continue;
+                    }
+                    cmd if cmd.starts_with('!') => {
+                        match cmd[1..]
+                            .parse::<usize>()
+                            .ok()
+                            .and_then(|n| n.checked_sub(1))
+                        {
+                            Some(n) if n < self.history.len() => {
+                                let input = self.history[n].clone();
+                                println!("{input}");
+                                self.submit(input);
+                            }
+                            _ => println!("No history entry `{}`", &cmd[1..]),
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            if !buffer.is_empty() {
+                buffer.push('\n');
+            }
+            buffer.push_str(&line);
+
+            // Keep reading while a block is still open
+            if !is_complete(&buffer) {
+                continue;
+            }
+
+            self.submit(std::mem::take(&mut buffer));
+        }
+
+        Ok(())
+    }
+
+    fn submit(&mut self, input: String) {
+        self.save_history(&input);
+        self.eval(&input);
+    }
+
+    fn save_history(&mut self, input: &str) {
+        self.history.push(input.to_string());
+
+        if let Some(path) = &self.history_file
+            && let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path)
+        {
+            let _ = writeln!(file, "{}", escape_entry(input));
+        }
+    }
+
+    /// Text of the input or imported file `location` points into
+    fn source_of(&self, location: Option<&Location>) -> Option<String> {
+        let location = location?;
+
+        self.sources
+            .iter()
+            .find(|(file, _)| *file == *location.file)
+            .map(|(_, text)| text.clone())
+            .or_else(|| fs::read_to_string(&*location.file).ok())
+    }
+
+    /// Compiles `input` onto the end of the program and runs only the new instructions.
+    pub fn eval(&mut self, input: &str) {
+        let file = format!("<input {}>", self.sources.len() + 1);
+        self.sources.push((file.clone(), input.to_string()));
+
+        // Syntax errors have already been reported
+        let Ok(nodes) = parse_source(input, &file, false) else {
+            return;
+        };
+
+        self.compiler.set_source(&file, input);
+
+        let start = self.compiler.instructions.len();
+        for (i, node) in nodes.iter().enumerate() {
+            match node {
+                // Leave the value of the last expression on the stack so it can be echoed
+                Node::ExprStmt(expr, span) if i == nodes.len() - 1 && is_echoable(expr) => self
+                    .compiler
+                    .with_span(span, |compiler| compiler.compile_node(expr)),
+                _ => self.compiler.compile_node(node),
+            }
+        }
+        self.compiler.resolve_type_hints();
+
+        for w in std::mem::take(&mut self.compiler.warnings) {
+            report(
+                &w,
+                w.location.as_ref(),
+                self.source_of(w.location.as_ref()).as_deref(),
+            );
+        }
+
+        if !self.compiler.errors.is_empty() {
+            for e in std::mem::take(&mut self.compiler.errors) {
+                report(
+                    &e,
+                    e.location.as_ref(),
+                    self.source_of(e.location.as_ref()).as_deref(),
+                );
+            }
+            self.compiler.truncate(start);
+            return;
+        }
+
+        self.vm.constants = self.compiler.constants.clone();
+        self.vm.intern_table = self.compiler.intern_table.clone();
+        self.vm.files = self.compiler.files.clone();
+        self.vm.lines = self.compiler.lines.clone();
+        self.vm
+            .extend_instructions(&self.compiler.instructions[start..]);
+
+        self.vm.stack.clear();
+        self.vm.pos = start;
+
+        if let Err(e) = self.vm.run(false, false) {
+            report(
+                &e,
+                e.location.as_ref(),
+                self.source_of(e.location.as_ref()).as_deref(),
+            );
+            self.vm.reset_to_top_level();
+            return;
+        }
+
+        if let Some(value) = self.vm.stack.pop()
+            && value != Value::NIL
+        {
+            println!("{}", value.to_string(true));
+        }
+    }
+}
+
+fn is_echoable(expr: &Node) -> bool {
+    !matches!(
+        expr,
+        Node::LetStatement { .. }
+            | Node::LetPattern { .. }
+            | Node::SetVariable { .. }
+            | Node::ShorthandAssignment { .. }
+            | Node::FunctionDefinition { .. }
+            | Node::WhileLoop { .. }
+            | Node::ForLoop { .. }
+            | Node::Loop { .. }
+    )
+}
+
+/// Whether every `(`, `[` and `{` in `text` has been closed.
+fn is_complete(text: &str) -> bool {
+    let mut depth = 0i32;
+
+    for token in Lexer::new(text).get_tokens() {
+        match token.kind {
+            TokenKind::LPAREN | TokenKind::LBRACK | TokenKind::LBRACE => depth += 1,
+            TokenKind::RPAREN | TokenKind::RBRACK | TokenKind::RBRACE => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth <= 0
+}
+
+/// Keeps a multi-line input on one line of the history file
+fn escape_entry(input: &str) -> String {
+    input.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Restores an input written by `escape_entry`
+fn unescape_entry(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            _ => entry.push(c),
+        }
+    }
+
+    entry
+}
@@ -1,27 +1,161 @@
 use crate::{
+    compiler::error::CompileError,
     hash_u64,
-    language::{nodes::Node, token::TokenKind},
+    language::{
+        nodes::{EnumItem, Node, Pattern},
+        token::{TokenKind, TokenRange},
+    },
+    language::{lexer::Lexer, parser::Parser},
+    misc::source::{Location, line_col, line_starts},
     patch, patch_execute, rc,
     virtual_machine::{
-        inst::Inst,
-        types::{list::TList, structdef::TStructDef},
+        chunk::LineInfo,
+        inst::{Capture, Inst},
+        types::{
+            interface::TInterface, list::TList, structdef::TStructDef,
+            variant::{TVariant, TVariantDef},
+        },
+        namespaces::standard_namespace::load_standard_namespace,
         value::Value,
+        vm::VM,
     },
 };
 use std::{
     cell::RefCell,
     collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
     rc::Rc,
 };
 
+/// A function parameter: (name, type, default value)
+type Param = (Rc<String>, Option<Rc<String>>, Option<Node>);
+
+/// Name of the hidden local holding the value being matched. It can't clash with a
+/// variable since it isn't a valid identifier.
+const MATCH_SUBJECT: &str = "<match>";
+
+/// One step from the value being matched to the part a pattern looks at
+#[derive(Clone)]
+enum PatternStep {
+    Index(usize),
+    /// Counted from the end, for items after a `..`
+    Back(usize),
+    Field(String),
+    /// Items left after skipping some from the start and some from the end
+    Slice(usize, usize),
+}
+
+/// A compiled source file, imported with `using`
+struct Module {
+    /// Global holding its namespace
+    id: u64,
+    /// Where its code starts
+    start: usize,
+    /// Its top-level definitions
+    members: Vec<String>,
+}
+
+/// A variable declared in a function, or in a block of the top level
+#[derive(Clone, Copy, Default)]
+struct Local {
+    /// Index into the slots of the frame
+    slot: usize,
+    is_const: bool,
+    /// A closure captured it, so leaving its scope has to close the upvalue
+    captured: bool,
+}
+
+/// Names declared in a block. The first scope is the top level, whose names are globals and
+/// don't take slots.
+#[derive(Default)]
+struct Scope {
+    vars: HashMap<String, Local>,
+    /// Slot of its first variable
+    first_slot: usize,
+    /// Slots holding the namespaces `using path::*` imported into it, whose members are only
+    /// known at runtime
+    wildcards: Vec<usize>,
+}
+
+impl Scope {
+    fn contains(&self, name: &str) -> bool {
+        self.vars.contains_key(name)
+    }
+}
+
+/// A function being compiled, or the top-level code
+struct FunctionScope {
+    /// Index into `scopes` of its outermost scope
+    scope_base: usize,
+    /// Slots taken by the variables of its open scopes
+    slots: usize,
+    /// Variables of the functions around it that it captures, one per upvalue
+    captures: Vec<Capture>,
+}
+
+/// What a name refers to in the function being compiled, apart from globals
+enum Variable {
+    /// A variable of the function, declared in `scopes[scope]`
+    Local { scope: usize, local: Local },
+    Upvalue { index: usize, is_const: bool },
+}
+
+/// How many scopes, `try` blocks and `for` iterators are open where a loop or block starts,
+/// so `break`, `continue` and `out` know what to close when jumping to it
+#[derive(Clone, Copy)]
+struct Boundary {
+    scopes: usize,
+    try_depth: usize,
+    iterators: usize,
+}
+
 pub struct Compiler {
     pub constants: Vec<Value>,
     pub offset: usize,
     pub instructions: Vec<Inst>,
     pub intern_table: HashMap<u64, Rc<str>>,
-    pub scopes: Vec<HashSet<String>>,
-    pub scope_base: usize,
-    pub current_captures: Vec<usize>,
+    scopes: Vec<Scope>,
+    /// The functions being compiled, innermost last, after the top-level code
+    functions: Vec<FunctionScope>,
+    /// Number of `try` blocks around the code being compiled
+    pub try_depth: usize,
+    /// Number of `for` iterators open around the code being compiled
+    pub iterators: usize,
+    /// Where each enclosing loop starts, innermost last
+    loops: Vec<Boundary>,
+    /// Where each block that `out` can leave starts, innermost last
+    out_targets: Vec<Boundary>,
+    /// Compiling a class constructor, where `return` hands back `self`
+    pub in_constructor: bool,
+    /// Whether the function being compiled has a `yield`, `None` outside of functions
+    pub function_yields: Option<bool>,
+    /// Emit `CHECK_TYPE` for annotated parameters and return types (off for release bytecode)
+    pub type_checks: bool,
+    /// The return type and name of the function being compiled, if its returns are checked
+    return_type: Option<(String, String)>,
+    /// Where the return checks of the function being compiled are, dropped if it's a generator
+    return_checks: Vec<usize>,
+    /// Type checks and struct definitions whose hints are resolved once the program is compiled
+    type_hints: Vec<(usize, Option<Location>)>,
+    /// Names of the structs, classes, interfaces and enums declared so far
+    type_names: HashSet<String>,
+    pub files: Vec<Rc<str>>,
+    pub lines: Vec<LineInfo>,
+    pub errors: Vec<CompileError>,
+    pub warnings: Vec<CompileError>,
+    /// Item names of every enum declared so far, to check that a `match` covers them all
+    enums: HashMap<String, Vec<String>>,
+    line_starts: Vec<usize>,
+    current_span: Option<LineInfo>,
+    /// Index into `files` of the source being compiled
+    current_file: usize,
+    /// Prefix of the top-level names of the module being compiled, `None` in the main file
+    module_prefix: Option<String>,
+    /// Modules compiled so far
+    modules: HashMap<PathBuf, Module>,
+    /// Files being compiled, the importing ones first, to catch import cycles
+    module_stack: Vec<PathBuf>,
 }
 
 impl Compiler {
@@ -30,11 +164,176 @@ impl Compiler {
             offset: 0,
             constants: vec![],
             instructions: vec![],
-            scopes: vec![HashSet::new()],
+            scopes: vec![Scope::default()],
+            functions: vec![FunctionScope {
+                scope_base: 1,
+                slots: 0,
+                captures: vec![],
+            }],
             intern_table: HashMap::new(),
-            scope_base: 0,
-            current_captures: vec![],
+            try_depth: 0,
+            iterators: 0,
+            loops: vec![],
+            out_targets: vec![],
+            in_constructor: false,
+            function_yields: None,
+            type_checks: true,
+            return_type: None,
+            return_checks: vec![],
+            type_hints: vec![],
+            type_names: HashSet::new(),
+            files: vec![],
+            lines: vec![],
+            errors: vec![],
+            warnings: vec![],
+            enums: HashMap::new(),
+            line_starts: vec![],
+            current_span: None,
+            current_file: 0,
+            module_prefix: None,
+            modules: HashMap::new(),
+            module_stack: vec![],
+        }
+    }
+
+    /// Spans of the nodes compiled next refer to `text`, which was read from `file`.
+    pub fn set_source(&mut self, file: &str, text: &str) {
+        self.files.push(Rc::from(file));
+        self.current_file = self.files.len() - 1;
+        self.line_starts = line_starts(text);
+    }
+
+    fn resolve_span(&self, span: &TokenRange) -> Option<LineInfo> {
+        if self.files.is_empty() {
+            return None;
+        }
+
+        let (line, col) = line_col(&self.line_starts, span.start);
+
+        Some(LineInfo {
+            start: 0, // filled in by `mark_span`
+            file: self.current_file,
+            line,
+            col,
+            len: span.end.saturating_sub(span.start).max(1),
+        })
+    }
+
+    fn mark_span(&mut self) {
+        if let Some(span) = self.current_span {
+            LineInfo::push(
+                &mut self.lines,
+                LineInfo {
+                    start: self.offset + self.instructions.len(),
+                    ..span
+                },
+            );
+        }
+    }
+
+    /// Compiles with `span` as the source position, handing it back to the parent node afterwards.
+    pub fn with_span(&mut self, span: &TokenRange, f: impl FnOnce(&mut Self)) {
+        let parent = self.current_span;
+
+        self.current_span = self.resolve_span(span).or(parent);
+        self.mark_span();
+        f(self);
+
+        self.current_span = parent;
+        self.mark_span();
+    }
+
+    pub fn current_location(&self) -> Option<Location> {
+        self.current_span?.location(&self.files)
+    }
+
+    pub fn error(&mut self, message: impl Into<String>) {
+        let location = self.current_location();
+        self.errors.push(CompileError::new(message, location));
+    }
+
+    pub fn warn(&mut self, message: impl Into<String>) {
+        let location = self.current_location();
+        self.warnings.push(CompileError::warning(message, location));
+    }
+
+    /// Leaves out the checks of type hints naming a type the program doesn't declare, with a
+    /// warning, like hints were treated before they were checked. Runs once everything is
+    /// compiled, since a hint can name a type declared further down.
+    pub fn resolve_type_hints(&mut self) {
+        let mut unknown = vec![];
+
+        for (i, location) in std::mem::take(&mut self.type_hints) {
+            match &self.instructions[i] {
+                Inst::CHECK_TYPE {
+                    hint,
+                    function,
+                    param,
+                } => {
+                    let what = match param {
+                        Some(param) => format!("`{param}` of `{function}`"),
+                        None => format!("the return value of `{function}`"),
+                    };
+
+                    let names = self.unknown_types(hint);
+                    if !names.is_empty() {
+                        self.instructions[i] = Inst::NOP;
+                    }
+                    for name in names {
+                        unknown.push((name, what.clone(), location.clone()));
+                    }
+                }
+
+                Inst::PUSH(Value::StructDef(def)) => {
+                    let mut fields = (*def.fields).clone();
+                    let mut changed = false;
+
+                    for (field, hint) in fields.iter_mut() {
+                        let names = self.unknown_types(hint);
+                        if !names.is_empty() {
+                            *hint = "any".to_string();
+                            changed = true;
+                        }
+
+                        let what = format!("field `{field}` of `{}`", def.name);
+                        for name in names {
+                            unknown.push((name, what.clone(), location.clone()));
+                        }
+                    }
+
+                    if changed {
+                        let def = TStructDef::new(def.name.clone(), rc!(fields));
+                        self.instructions[i] = Inst::PUSH(Value::StructDef(rc!(def)));
+                    }
+                }
+
+                // Already left out, like the return checks of generators
+                _ => {}
+            }
         }
+
+        for (name, what, location) in unknown {
+            self.warnings.push(CompileError::warning(
+                format!("Unknown type `{name}` in the hint of {what}, it isn't checked"),
+                location,
+            ));
+        }
+    }
+
+    /// The names in `hint` that are neither builtin nor declared
+    fn unknown_types(&self, hint: &str) -> Vec<String> {
+        Value::type_hint_names(hint)
+            .into_iter()
+            .filter(|name| !Value::BUILTIN_TYPES.contains(name) && !self.type_names.contains(*name))
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Drops the instructions from `start` on, along with the modules compiled into them.
+    pub fn truncate(&mut self, start: usize) {
+        self.instructions.truncate(start);
+        self.lines.retain(|line| line.start < start);
+        self.modules.retain(|_, module| module.start < start);
     }
 
     pub fn intern(&mut self, s: &str) -> u64 {
@@ -48,72 +347,204 @@ impl Compiler {
     }
 
     pub fn push_scope(&mut self) {
-        self.instructions.push(Inst::PUSH_SCOPE);
-        self.scopes.push(HashSet::new());
+        let first_slot = self.function().slots;
+        self.scopes.push(Scope {
+            first_slot,
+            ..Scope::default()
+        });
     }
 
-    pub fn pop_scope(&mut self) {
-        self.instructions.push(Inst::POP_SCOPE);
-        self.scopes.pop();
+    /// Ends the innermost scope, closing the variables of it that closures captured. Returns
+    /// the instruction doing that, for the other ways out of the scope.
+    pub fn pop_scope(&mut self) -> Option<Inst> {
+        let scope = self.scopes.pop()?;
+        self.function_mut().slots = scope.first_slot;
+
+        let close = scope
+            .vars
+            .values()
+            .any(|local| local.captured)
+            .then_some(Inst::CLOSE_UPVALUES(scope.first_slot));
+        self.instructions.extend(close.clone());
+        close
     }
 
-    pub fn emit_store_local(&mut self, name: &str, is_const: bool) {
-        let id = self.intern(name);
-        let depth = self.scopes.len() - 1 - self.scope_base;
+    fn function(&self) -> &FunctionScope {
+        self.functions.last().expect("the top level is always compiling")
+    }
+
+    fn function_mut(&mut self) -> &mut FunctionScope {
+        self.functions
+            .last_mut()
+            .expect("the top level is always compiling")
+    }
 
+    /// Id of a top-level variable. A module's are prefixed with its path, so they don't clash
+    /// with the ones of the files importing it.
+    fn global_id(&mut self, name: &str) -> u64 {
+        match &self.module_prefix {
+            Some(prefix) => {
+                let name = format!("{prefix}{name}");
+                self.intern(&name)
+            }
+            None => self.intern(name),
+        }
+    }
+
+    /// A slot for a variable of the innermost scope
+    fn new_slot(&mut self) -> usize {
+        let function = self.function_mut();
+        function.slots += 1;
+        function.slots - 1
+    }
+
+    /// Declares `name` in the innermost scope, or makes it const or not if it is declared
+    /// there already, and returns its slot.
+    fn declare_local(&mut self, name: &str, is_const: bool) -> usize {
+        if let Some(local) = self.scopes.last_mut().and_then(|scope| scope.vars.get_mut(name)) {
+            local.is_const = is_const;
+            return local.slot;
+        }
+
+        let slot = self.new_slot();
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.vars.insert(
+                name.to_string(),
+                Local {
+                    slot,
+                    is_const,
+                    captured: false,
+                },
+            );
+        }
+        slot
+    }
+
+    /// Finds `name` among the variables of the function at `level` of `functions`, or of the
+    /// ones around it, which it then captures.
+    fn resolve(&mut self, name: &str, level: usize) -> Option<Variable> {
+        let start = self.functions[level].scope_base;
+        let end = self
+            .functions
+            .get(level + 1)
+            .map_or(self.scopes.len(), |function| function.scope_base);
+
+        for scope in (start..end).rev() {
+            if let Some(&local) = self.scopes[scope].vars.get(name) {
+                return Some(Variable::Local { scope, local });
+            }
+        }
+
+        if level == 0 {
+            return None;
+        }
+
+        let (capture, is_const) = match self.resolve(name, level - 1)? {
+            Variable::Local { scope, local } => {
+                if let Some(local) = self.scopes[scope].vars.get_mut(name) {
+                    local.captured = true;
+                }
+                (Capture::Local(local.slot), local.is_const)
+            }
+            Variable::Upvalue { index, is_const } => (Capture::Upvalue(index), is_const),
+        };
+
+        let captures = &mut self.functions[level].captures;
+        let index = match captures.iter().position(|c| *c == capture) {
+            Some(index) => index,
+            None => {
+                captures.push(capture);
+                captures.len() - 1
+            }
+        };
+        Some(Variable::Upvalue { index, is_const })
+    }
+
+    /// Slots of the namespaces `using path::*` imported into the scopes of the current
+    /// function, innermost first
+    fn wildcards(&self) -> Vec<usize> {
+        self.scopes[self.function().scope_base..]
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.wildcards.iter().rev().copied())
+            .collect()
+    }
+
+    /// Declares `name` in the innermost scope, and stores the value on the stack in it.
+    pub fn emit_store_local(&mut self, name: &str, is_const: bool) {
         if self.scopes.len() == 1 {
+            let id = self.global_id(name);
 			if is_const {
 				self.instructions.push(Inst::STORE_GLOBAL_CONST(id));
 			} else {
 				self.instructions.push(Inst::STORE_GLOBAL(id));
 			}
-            self.scopes[0].insert(name.to_string());
+            self.scopes[0].vars.insert(
+                name.to_string(),
+                Local {
+                    is_const,
+                    ..Local::default()
+                },
+            );
             return;
         }
 
-        self.instructions.push(if is_const {
-            Inst::STORE_LOCAL_CONST { id, depth }
-        } else {
-            Inst::STORE_LOCAL { id, depth }
-        });
-
-        if let Some(current_scope) = self.scopes.last_mut() {
-            current_scope.insert(name.to_string());
-        }
+        let slot = self.declare_local(name, is_const);
+        self.instructions.push(Inst::STORE_LOCAL(slot));
     }
 
     pub fn emit_load_local(&mut self, name: &str) {
-        for (depth, scope) in self.scopes.iter().enumerate().rev() {
-            if scope.contains(name) {
-                let id = self.intern(name);
+        let level = self.functions.len() - 1;
 
-                if depth == 0 {
+        match self.resolve(name, level) {
+            Some(Variable::Local { local, .. }) => {
+                self.instructions.push(Inst::LOAD_LOCAL(local.slot))
+            }
+            Some(Variable::Upvalue { index, .. }) => {
+                self.instructions.push(Inst::LOAD_UPVALUE(index))
+            }
+            None if self.scopes[0].contains(name) => {
+                let id = self.global_id(name);
+                self.instructions.push(Inst::LOAD_GLOBAL(id));
+            }
+            None => {
+                let namespaces = self.wildcards();
+                if namespaces.is_empty() {
+                    let id = self.intern(name);
                     self.instructions.push(Inst::LOAD_GLOBAL(id));
-                } else if depth < self.scope_base {
-                    let absolute = depth; // absolute index into locals at runtime
-                    if !self.current_captures.contains(&absolute) {
-                        self.current_captures.push(absolute);
-                    }
-
-                    let scope_idx = self
-                        .current_captures
-                        .iter()
-                        .position(|&d| d == absolute)
-                        .unwrap();
-                    self.instructions.push(Inst::LOAD_UPVALUE { id, scope_idx });
                 } else {
-                    let relative = depth - self.scope_base;
-                    self.instructions.push(Inst::LOAD_LOCAL {
-                        id,
-                        depth: relative,
+                    self.instructions.push(Inst::LOAD {
+                        name: name.to_string(),
+                        namespaces,
                     });
                 }
-                return;
             }
         }
+    }
 
-        let id = self.intern(name);
-        self.instructions.push(Inst::LOAD_GLOBAL(id));
+    /// Stores the value on the stack in the variable `name`, which closures that captured it
+    /// see too.
+    fn emit_assign(&mut self, name: &str) {
+        let level = self.functions.len() - 1;
+
+        match self.resolve(name, level) {
+            Some(Variable::Local { local, .. }) if !local.is_const => {
+                self.instructions.push(Inst::STORE_LOCAL(local.slot))
+            }
+            Some(Variable::Upvalue {
+                index,
+                is_const: false,
+            }) => self.instructions.push(Inst::STORE_UPVALUE(index)),
+            Some(_) => self.error(format!("Cannot assign to the constant `{name}`")),
+            None => {
+                let id = if self.scopes[0].contains(name) {
+                    self.global_id(name)
+                } else {
+                    self.intern(name)
+                };
+                self.instructions.push(Inst::SET_VAR(id));
+            }
+        }
     }
 
     pub fn compile_node(&mut self, node: &Node) {
@@ -149,17 +580,25 @@ impl Compiler {
                 inclusive,
             } => self.compile_range(start, end, step, *inclusive),
 
-            Node::ExprStmt(x) => {
-                self.compile_node(&*x);
-                self.instructions.push(Inst::TRY_POP);
-            }
+            Node::ExprStmt(x, span) => self.with_span(span, |this| {
+                this.compile_statement(x);
+                this.instructions.push(Inst::TRY_POP);
+            }),
 
             Node::UnaryOp {
                 op,
                 right,
                 is_prefix,
-            } => self.compile_unary_op(op, right, *is_prefix),
-            Node::BinOp { left, right, op } => self.compile_bin_op(left, right, op),
+                span,
+            } => self.with_span(span, |this| {
+                this.compile_unary_op(op, right, *is_prefix)
+            }),
+            Node::BinOp {
+                left,
+                right,
+                op,
+                span,
+            } => self.with_span(span, |this| this.compile_bin_op(left, right, op)),
             Node::ComparisonChain {
                 expressions,
                 operators,
@@ -178,16 +617,31 @@ impl Compiler {
                 values,
                 is_const,
             } => self.compile_let(names, &mut values.clone(), *is_const),
+            Node::LetPattern {
+                pattern,
+                value,
+                is_const,
+            } => {
+                self.compile_node(value);
+                self.compile_destructure(pattern, *is_const);
+            }
 
             Node::UsingStatement {
+                file,
                 sequence,
                 imports,
                 wildcard,
-            } => self.compile_using(sequence, imports, *wildcard),
+                alias,
+                span,
+            } => self.with_span(span, |this| {
+                this.compile_using(file, sequence, imports, *wildcard, alias)
+            }),
 
             Node::SetVariable { target, value } => self.compile_set_variable(target, value),
 
-            Node::MemberAccess { expr, member } => self.compile_member_access(expr, member),
+            Node::MemberAccess { expr, member, span } => self.with_span(span, |this| {
+                this.compile_member_access(expr, member)
+            }),
 
             Node::ShorthandAssignment {
                 token,
@@ -195,7 +649,9 @@ impl Compiler {
                 value,
             } => self.compile_shorthand_assignment(target, value, token),
 
-            Node::FunctionCall { target, args } => self.compile_function_call(target, args),
+            Node::FunctionCall { target, args, span } => self.with_span(span, |this| {
+                this.compile_function_call(target, args)
+            }),
 
             Node::FunctionDefinition {
                 name,
@@ -212,7 +668,7 @@ impl Compiler {
                 block,
                 elifs,
                 else_block,
-            } => self.compile_if_statement(condition, block, elifs, else_block),
+            } => self.compile_if_statement(condition, block, elifs, else_block, false),
 
             Node::Block { body } => self.compile_block(body),
 
@@ -227,21 +683,47 @@ impl Compiler {
             Node::WhileLoop { condition, block } => self.compile_while_loop(condition, block),
 
             Node::BreakStatement(value) => self.compile_break(value),
+            Node::ContinueStatement => self.compile_continue(),
+            Node::OutStatement(value) => self.compile_out(value),
+            Node::YieldStatement(value) => self.compile_yield(value),
+
+            Node::TryCatch {
+                block,
+                error_name,
+                catch_block,
+            } => self.compile_try_catch(block, error_name, catch_block, false),
+            Node::ThrowStatement(value) => {
+                self.compile_node(value);
+                self.instructions.push(Inst::THROW);
+            }
 
             Node::ForLoop {
-                var_name,
+                pattern,
                 expr,
                 block,
-            } => self.compile_for(var_name, expr, block),
+            } => self.compile_for(pattern, expr, block),
 
-            Node::MatchStatement { expr, branches } => self.compile_match(expr, branches),
+            Node::MatchStatement { expr, branches } => {
+                self.compile_match(expr, branches, false)
+            }
 
             Node::EnumDef { name, items } => self.compile_enum_def(name, items),
 
             Node::StructDef { name, fields } => self.compile_struct_def(name, fields),
+            Node::ClassDef {
+                name,
+                interfaces,
+                let_statements,
+                functions,
+                span,
+            } => self.compile_class_def(name, interfaces, let_statements, functions, span),
+            Node::InterfaceDef {
+                name,
+                let_statements,
+                functions,
+            } => self.compile_interface_def(name, let_statements, functions),
             Node::StructInit { target, fields } => self.compile_struct_init(target, fields),
-
-            _ => panic!("Unknown node: `{node:?}`"),
+            Node::ImplBlock { name, functions } => self.compile_impl_block(name, functions),
         }
     }
 }
@@ -291,10 +773,13 @@ impl Compiler {
         }
 
         if all_literals && !values.is_empty() {
-            self.instructions
-                .push(Inst::PUSH(Value::List(TList::new(rc!(RefCell::new(
-                    folded_values
-                ))))));
+            let values = rc!(RefCell::new(folded_values));
+
+            self.instructions.push(Inst::PUSH(if is_tuple {
+                Value::Tuple(TList::new_tuple(values))
+            } else {
+                Value::List(TList::new(values))
+            }));
             return;
         }
 
@@ -334,9 +819,8 @@ impl Compiler {
                 if is_prefix {
                     self.instructions.push(Inst::DUP);
                 }
-                let id = self.intern(x.as_str());
-                self.instructions.push(Inst::SET_VAR(id))
-            } else if let Node::MemberAccess { expr, member } = &**target {
+                self.emit_assign(x)
+            } else if let Node::MemberAccess { expr, member, .. } = &**target {
                 self.compile_node(&**expr);
                 if !is_prefix {
                     self.instructions.push(Inst::DUP);
@@ -350,7 +834,7 @@ impl Compiler {
                 self.compile_node(&**member);
                 self.instructions.push(Inst::SET_PROP);
             } else {
-                panic!("Cannot set equal a value to `{:?}`", **target);
+                self.error(format!("Cannot assign to `{:?}`", **target));
             }
 
             return;
@@ -372,7 +856,7 @@ impl Compiler {
             TokenKind::PLUS => self.instructions.push(Inst::POS),
             TokenKind::BANG => self.instructions.push(Inst::NOT),
 
-            _ => panic!("Tried compiling unknown unary op: {op:?}"),
+            _ => self.error(format!("Unknown unary operator `{op:?}`")),
         }
     }
 
@@ -380,7 +864,7 @@ impl Compiler {
         self.compile_node(&**left);
         self.compile_node(&**right);
 
-        self.instructions.push(match op {
+        let inst = match op {
             TokenKind::PLUS => Inst::ADD,
             TokenKind::MINUS => Inst::SUB,
             TokenKind::STAR => Inst::MUL,
@@ -399,8 +883,10 @@ impl Compiler {
             TokenKind::AND => Inst::AND,
             TokenKind::OR => Inst::OR,
 
-            _ => panic!("Cannot compile unknown bin-op: `{op:?}`"),
-        });
+            _ => return self.error(format!("Unknown binary operator `{op:?}`")),
+        };
+
+        self.instructions.push(inst);
     }
 
     pub fn compile_comparison_chain(
@@ -531,45 +1017,109 @@ impl Compiler {
     }
 
     pub fn compile_block(&mut self, body: &Vec<Node>) {
-        let mut outs = vec![];
+        self.compile_block_body(body, true);
+    }
+
+    /// Compiles an expression whose value is dropped. `out` passes through the blocks of an
+    /// `if` or `try` used this way, to reach the block around it.
+    pub fn compile_statement(&mut self, node: &Node) {
+        match node {
+            Node::IfStatement {
+                condition,
+                block,
+                elifs,
+                else_block,
+            } => self.compile_if_statement(condition, block, elifs, else_block, true),
+            Node::TryCatch {
+                block,
+                error_name,
+                catch_block,
+            } => self.compile_try_catch(block, error_name, catch_block, true),
+            Node::MatchStatement { expr, branches } => self.compile_match(expr, branches, true),
+
+            _ => self.compile_node(node),
+        }
+    }
+
+    /// Compiles the block of an `if`, loop, `try` or `catch`, which `out` leaves unless it
+    /// `is_out_target`.
+    pub fn compile_body(&mut self, node: &Node, is_out_target: bool) {
+        if let Node::Block { body } = node {
+            self.compile_block_body(body, is_out_target);
+        } else {
+            self.compile_node(node);
+        }
+    }
+
+    fn compile_block_body(&mut self, body: &Vec<Node>, is_out_target: bool) {
+        let block_start = self.instructions.len();
 
         self.push_scope();
-        for i in body {
-            if let Node::ExprStmt(expr) = i {
-                if let Node::OutStatement(val) = expr.as_ref() {
-                    if let Some(v) = val {
-                        self.compile_node(&*v);
-                    } else {
-                        self.instructions.push(Inst::PUSH(Value::NIL));
-                    }
-                    outs.push(patch!(self.instructions)); // Jump to scope cleanup
-                } else {
+        if is_out_target {
+            self.out_targets.push(self.boundary());
+        }
+
+        // The last expression is the block's value, which is only used if `out` can target it
+        match body.split_last() {
+            Some((Node::ExprStmt(expr, span), statements)) if Self::is_block_value(expr) => {
+                for i in statements {
                     self.compile_node(i);
                 }
-            } else {
-                if let Node::OutStatement(val) = i {
-                    if let Some(v) = val {
-                        self.compile_node(&*v);
+                self.with_span(span, |this| {
+                    if is_out_target {
+                        this.compile_node(expr);
                     } else {
-                        self.instructions.push(Inst::PUSH(Value::NIL));
+                        this.compile_statement(expr);
                     }
-                    outs.push(patch!(self.instructions)); // Jump to scope cleanup
-                } else {
+                });
+            }
+            _ => {
+                for i in body {
                     self.compile_node(i);
                 }
+                self.instructions.push(Inst::PUSH(Value::NIL));
             }
         }
 
-        self.instructions.push(Inst::PUSH(Value::NIL));
+        if is_out_target {
+            self.out_targets.pop();
 
-        let block_end = self.instructions.len();
-        for i in outs {
-            patch_execute!(self.instructions, i, Inst::JUMP(block_end));
+            // Jump to scope cleanup
+            patch_execute!(
+                self.instructions,
+                "out",
+                Inst::JUMP(self.instructions.len()),
+                block_start
+            );
         }
 
         self.pop_scope();
     }
 
+    /// Statements like `let` or loops at the end of a block leave it with a value of nil.
+    pub fn is_block_value(node: &Node) -> bool {
+        !matches!(
+            node,
+            Node::LetStatement { .. }
+                | Node::LetPattern { .. }
+                | Node::SetVariable { .. }
+                | Node::ShorthandAssignment { .. }
+                | Node::WhileLoop { .. }
+                | Node::ForLoop { .. }
+                | Node::Loop { .. }
+                | Node::OutStatement(..)
+                | Node::ReturnStatement(..)
+                | Node::BreakStatement(..)
+                | Node::ContinueStatement
+                | Node::StructDef { .. }
+                | Node::ClassDef { .. }
+                | Node::InterfaceDef { .. }
+                | Node::EnumDef { .. }
+                | Node::ImplBlock { .. }
+                | Node::UsingStatement { .. }
+        )
+    }
+
     pub fn compile_member_access(&mut self, expr: &Box<Node>, member: &Box<Node>) {
         self.compile_node(&**expr);
         self.compile_node(&**member);
@@ -591,6 +1141,7 @@ impl Compiler {
         block: &Box<Node>,
         elifs: &Vec<(Node, Node)>,
         else_block: &Option<Box<Node>>,
+        is_statement: bool,
     ) {
         let mut if_end_jumps = vec![];
 
@@ -604,19 +1155,21 @@ impl Compiler {
             self.compile_node(&condition);
             let jump_if_false = patch!(self.instructions);
 
-            self.compile_node(&block);
+            self.compile_body(&block, !is_statement);
 
-            self.pop_scope();
+            let close = self.pop_scope();
 
             if_end_jumps.push(patch!(self.instructions));
 
             self.comment("If branch end");
 
+            // The branch's scope was pushed before checking the condition
             patch_execute!(
                 self.instructions,
                 jump_if_false,
                 Inst::JUMP_IF_FALSE(self.instructions.len())
             );
+            self.instructions.extend(close);
         };
 
         handler(&**condition, &**block);
@@ -628,7 +1181,7 @@ impl Compiler {
         }
 
         if let Some(body) = else_block {
-            self.compile_node(&**body);
+            self.compile_body(&**body, !is_statement);
         } else {
             self.instructions.push(Inst::PUSH(Value::NIL));
         }
@@ -645,15 +1198,15 @@ impl Compiler {
         if let Node::Variable(x) = &**target {
             self.compile_node(&**value);
             self.instructions.push(Inst::DUP);
-            self.instructions.push(Inst::SET_VAR(hash_u64!(x.as_str())));
-        } else if let Node::MemberAccess { expr, member } = &**target {
+            self.emit_assign(x);
+        } else if let Node::MemberAccess { expr, member, .. } = &**target {
             self.compile_node(&**value);
             self.instructions.push(Inst::DUP);
             self.compile_node(&**expr);
             self.compile_node(&**member);
             self.instructions.push(Inst::SET_PROP);
         } else {
-            panic!("Cannot set equal a value to `{:?}`", **target);
+            self.error(format!("Cannot assign to `{:?}`", **target));
         }
     }
 
@@ -671,51 +1224,88 @@ impl Compiler {
             TokenKind::POW_SH => Inst::POW,
             TokenKind::MOD_SH => Inst::MOD,
 
-            _ => panic!("Unknown shorthand assignment token: {token:?}"),
+            _ => return self.error(format!("Unknown shorthand assignment `{token:?}`")),
         };
 
         if let Node::Variable(x) = &**target {
             self.compile_node(&**target);
             self.compile_node(&**value);
             self.instructions.push(operator_inst);
-            self.instructions.push(Inst::SET_VAR(hash_u64!(x.as_str())));
-        } else if let Node::MemberAccess { expr, member } = &**target {
+            self.instructions.push(Inst::DUP);
+            self.emit_assign(x);
+        } else if let Node::MemberAccess { expr, member, .. } = &**target {
             self.compile_node(&**expr);
             self.compile_node(&**member);
             self.instructions.push(Inst::GET_PROP);
             self.compile_node(&**value);
             self.instructions.push(operator_inst);
+            self.instructions.push(Inst::DUP);
             self.compile_node(&**expr);
             self.compile_node(&**member);
             self.instructions.push(Inst::SET_PROP);
         } else {
-            panic!("Cannot set equal a value to `{:?}`", **target);
+            self.error(format!("Cannot assign to `{:?}`", **target));
         }
     }
 
     pub fn compile_return(&mut self, value: &Option<Box<Node>>) {
         if let Some(val) = value {
             self.compile_node(val);
+
+            if let Some(Inst::CALL(args)) = self.instructions.last()
+                && self.can_tail_call()
+            {
+                *self.instructions.last_mut().unwrap() = Inst::TAIL_CALL {
+                    args: *args,
+                    iterators: self.iterators,
+                };
+            }
         } else {
             self.instructions.push(Inst::PUSH(Value::NIL));
         }
+        self.emit_return_check();
+        // Frames don't track iterators, so drop the ones this function's loops opened
+        for _ in 0..self.iterators {
+            self.instructions.push(Inst::END_ITER);
+        }
+        if self.in_constructor {
+            self.instructions.push(Inst::POP);
+            self.compile_node(&Node::Variable(rc!(String::from("self"))));
+        }
         self.instructions.push(Inst::RETURN);
     }
 
     pub fn compile_function_def(
         &mut self,
         name: &Option<Rc<String>>,
-        _return_type: &Option<Rc<String>>,
+        return_type: &Option<Rc<String>>,
         args: &Vec<(Rc<String>, Option<Rc<String>>, Option<Node>)>,
 		is_const: bool,
         block: &Box<Node>,
     ) {
-        let saved_captures = std::mem::take(&mut self.current_captures);
-
         self.comment(&format!("New function (const: {is_const}):"));
+
+        let store = name.as_ref().map(|name| (name.as_str(), is_const));
+        let function = name.as_ref().map_or("<lambda>", |name| name.as_str());
+        self.compile_closure(function, args, return_type.as_ref(), store, |this| {
+            this.compile_node(block)
+        });
+    }
+
+    /// Emits a `MAKE_CLOSURE` for a function taking `args` whose body is emitted by `body`.
+    /// With `store`, the closure is bound to that name before the body is compiled so it can recurse.
+    /// `name` is only used by the errors of the type checks.
+    pub fn compile_closure(
+        &mut self,
+        name: &str,
+        args: &[Param],
+        return_type: Option<&Rc<String>>,
+        store: Option<(&str, bool)>,
+        body: impl FnOnce(&mut Self),
+    ) {
         let func_value = patch!(self.instructions);
-        if let Some(name) = name {
-            self.emit_store_local(name.as_str(), is_const);
+        if let Some((name, is_const)) = store {
+            self.emit_store_local(name, is_const);
         }
         let func_jump_to_end = patch!(self.instructions);
 
@@ -723,40 +1313,91 @@ impl Compiler {
 
         self.comment("Function def start:");
 
-        let saved_base = self.scope_base; // <-- save
-        self.scope_base = self.scopes.len(); // <-- reset: depths start fresh here
+        // Its variables take slots of its own frame
+        self.functions.push(FunctionScope {
+            scope_base: self.scopes.len(),
+            slots: 0,
+            captures: vec![],
+        });
+
+        // Handlers live on the call frame, so a function body starts outside any `try` or loop
+        let saved_try_depth = std::mem::take(&mut self.try_depth);
+        let saved_iterators = std::mem::take(&mut self.iterators);
+        let saved_loops = std::mem::take(&mut self.loops);
+        let saved_out_targets = std::mem::take(&mut self.out_targets);
+        let saved_in_constructor = std::mem::replace(&mut self.in_constructor, false);
+        let saved_function_yields = self.function_yields.replace(false);
+        let saved_return_type = std::mem::replace(
+            &mut self.return_type,
+            return_type
+                .filter(|_| self.type_checks)
+                .map(|hint| (hint.to_string(), name.to_string())),
+        );
+        let saved_return_checks = std::mem::take(&mut self.return_checks);
 
         self.push_scope();
+        let body_start = self.instructions.len();
 
-        for (arg_name, _, default_value) in args.iter() {
+        // The caller pads the arguments to the arity, with the last one on top of the stack
+        for (arg_name, arg_type, default_value) in args.iter().rev() {
             if let Some(def) = default_value {
                 self.compile_node(def);
                 self.instructions.push(Inst::DEFAULT);
-            } else {
-                self.instructions.push(Inst::DEFAULT_NIL);
+            }
+
+            if let Some(hint) = arg_type
+                && self.type_checks
+            {
+                self.type_hints.push((self.instructions.len(), self.current_location()));
+                self.instructions.push(Inst::CHECK_TYPE {
+                    hint: hint.to_string(),
+                    function: name.to_string(),
+                    param: Some(arg_name.to_string()),
+                });
             }
 
             self.emit_store_local(arg_name.as_str(), false);
         }
 
-        self.compile_node(block);
+        body(self);
 
+        if self.can_tail_call() {
+            self.emit_tail_calls(body_start);
+        }
+        self.emit_return_check();
         self.instructions.push(Inst::RETURN);
-        self.pop_scope();
 
-        self.scope_base = saved_base; // <-- restore
+        // `RETURN` closes every upvalue of the frame
+        self.scopes.pop();
+        let captures = self.functions.pop().map_or(vec![], |function| function.captures);
+
+        self.try_depth = saved_try_depth;
+        self.iterators = saved_iterators;
+        self.loops = saved_loops;
+        self.out_targets = saved_out_targets;
+        self.in_constructor = saved_in_constructor;
+        let generator = std::mem::replace(&mut self.function_yields, saved_function_yields)
+            .unwrap_or_default();
+
+        // What a generator returns is the generator itself, not what its body ends with
+        let return_checks = std::mem::replace(&mut self.return_checks, saved_return_checks);
+        if generator {
+            for i in return_checks {
+                self.instructions[i] = Inst::NOP;
+            }
+        }
+        self.return_type = saved_return_type;
 
         self.comment("Function def end");
 
-        let captures = std::mem::take(&mut self.current_captures);
-        self.current_captures = saved_captures;
-
         patch_execute!(
             self.instructions,
             func_value,
             Inst::MAKE_CLOSURE {
                 entry: func_start,
-                captures
+                arity: args.len(),
+                captures,
+                generator,
             }
         );
 
@@ -774,8 +1415,7 @@ impl Compiler {
 
         let end_loop_jump = patch!(self.instructions);
 
-        self.compile_node(&*block);
-
+        self.compile_loop_body(block, None);
         self.instructions.push(Inst::JUMP(loop_start_index));
 
         patch_execute!(
@@ -783,6 +1423,7 @@ impl Compiler {
             end_loop_jump,
             Inst::JUMP_IF_FALSE(self.instructions.len())
         );
+        self.instructions.push(Inst::PUSH(Value::NIL));
 
         patch_execute!(
             self.instructions,
@@ -790,50 +1431,65 @@ impl Compiler {
             Inst::JUMP(self.instructions.len()),
             loop_start_index
         );
-
-        self.instructions.push(Inst::DEFAULT_NIL);
+        patch_execute!(
+            self.instructions,
+            "continue",
+            Inst::JUMP(loop_start_index),
+            loop_start_index
+        );
     }
 
-    pub fn compile_for(&mut self, var_name: &Rc<String>, expr: &Box<Node>, block: &Box<Node>) {
+    pub fn compile_for(&mut self, pattern: &Pattern, expr: &Box<Node>, block: &Box<Node>) {
         self.comment("For loop start:");
 
-        self.push_scope();
-
         self.compile_node(&*expr);
         self.instructions.push(Inst::GET_ITER);
+        self.iterators += 1;
 
         let loop_start_index = self.instructions.len();
 
         let for_iter = patch!(self.instructions);
-        self.emit_store_local(var_name.as_str(), false);
-
-        self.compile_node(&*block);
-
+        self.compile_loop_body(block, Some(pattern));
         self.instructions.push(Inst::JUMP(loop_start_index));
 
+        // `FOR_ITER` drops the iterator once it runs out, `break` has to do it here
+        let break_index = self.instructions.len();
+        self.instructions.push(Inst::END_ITER);
+        let jump_to_end = patch!(self.instructions);
+        self.iterators -= 1;
+
         patch_execute!(
             self.instructions,
             for_iter,
             Inst::FOR_ITER(self.instructions.len())
         );
+        self.instructions.push(Inst::PUSH(Value::NIL));
 
+        patch_execute!(
+            self.instructions,
+            jump_to_end,
+            Inst::JUMP(self.instructions.len())
+        );
         patch_execute!(
             self.instructions,
             "break",
-            Inst::JUMP(self.instructions.len()),
+            Inst::JUMP(break_index),
+            loop_start_index
+        );
+        patch_execute!(
+            self.instructions,
+            "continue",
+            Inst::JUMP(loop_start_index),
             loop_start_index
         );
 
-        self.pop_scope();
-        self.instructions.push(Inst::DEFAULT_NIL);
         self.comment("For loop end");
     }
 
     pub fn compile_loop(&mut self, block: &Box<Node>) {
         let loop_start_index = self.instructions.len();
 
-        self.compile_node(&*block);
-
+        self.compile_loop_body(block, None);
         self.instructions.push(Inst::JUMP(loop_start_index));
 
         patch_execute!(
@@ -842,61 +1498,613 @@ impl Compiler {
             Inst::JUMP(self.instructions.len()),
             loop_start_index
         );
+        patch_execute!(
+            self.instructions,
+            "continue",
+            Inst::JUMP(loop_start_index),
+            loop_start_index
+        );
+    }
+
+    /// Compiles one iteration of a loop, dropping the value of its block. A `for` loop binds
+    /// the item to `pattern` in a scope of the iteration, so closures made in different
+    /// iterations capture different variables.
+    fn compile_loop_body(&mut self, block: &Node, pattern: Option<&Pattern>) {
+        self.loops.push(self.boundary());
+        if let Some(pattern) = pattern {
+            self.push_scope();
+            self.compile_destructure(pattern, false);
+        }
+
+        self.compile_body(block, false);
+        self.instructions.push(Inst::POP);
+
+        if pattern.is_some() {
+            self.pop_scope();
+        }
+        self.loops.pop();
+    }
 
-        self.instructions.push(Inst::DEFAULT_NIL);
+    fn boundary(&self) -> Boundary {
+        Boundary {
+            scopes: self.scopes.len(),
+            try_depth: self.try_depth,
+            iterators: self.iterators,
+        }
+    }
+
+    /// Emits what's needed to leave everything opened since `boundary`.
+    fn emit_unwind(&mut self, boundary: Boundary) {
+        // Closures made later in the scopes may run before this, so any variable can be captured
+        let scopes = &self.scopes[boundary.scopes..];
+        if scopes.iter().any(|scope| !scope.vars.is_empty()) {
+            self.instructions
+                .push(Inst::CLOSE_UPVALUES(scopes[0].first_slot));
+        }
+        for _ in boundary.try_depth..self.try_depth {
+            self.instructions.push(Inst::END_TRY);
+        }
+        for _ in boundary.iterators..self.iterators {
+            self.instructions.push(Inst::END_ITER);
+        }
     }
 
     pub fn compile_break(&mut self, value: &Option<Box<Node>>) {
+        let Some(&boundary) = self.loops.last() else {
+            return self.error("`break` outside of a loop");
+        };
+
         if let Some(val) = value {
             self.compile_node(&*val);
         } else {
             self.instructions.push(Inst::PUSH(Value::NIL));
         }
+        self.emit_unwind(boundary);
         let _ = patch!(self.instructions, "break");
     }
 
-    pub fn compile_match(&mut self, expr: &Box<Node>, branches: &Vec<(Node, Node)>) {
-        self.compile_node(expr);
+    pub fn compile_continue(&mut self) {
+        let Some(&boundary) = self.loops.last() else {
+            return self.error("`continue` outside of a loop");
+        };
 
-        for (idx, (condition, value)) in branches.iter().enumerate() {
-            if idx <= branches.len() {
-                self.instructions.push(Inst::DUP);
-            }
-            self.compile_node(condition);
-            self.instructions.push(Inst::MATCH);
+        self.emit_unwind(boundary);
+        let _ = patch!(self.instructions, "continue");
+    }
 
-            let jump_if_false = patch!(self.instructions);
+    /// Checks the value on top of the stack against the return type of the current function.
+    fn emit_return_check(&mut self) {
+        if let Some((hint, function)) = &self.return_type {
+            self.return_checks.push(self.instructions.len());
+            self.type_hints.push((self.instructions.len(), self.current_location()));
+            self.instructions.push(Inst::CHECK_TYPE {
+                hint: hint.clone(),
+                function: function.clone(),
+                param: None,
+            });
+        }
+    }
 
-            self.compile_node(value);
+    /// Whether a call returned from here can replace the current frame, which needs nothing
+    /// else to run once it returns.
+    fn can_tail_call(&self) -> bool {
+        self.functions.len() > 1
+            && self.return_type.is_none()
+            && !self.in_constructor
+            && self.try_depth == 0
+    }
 
-            patch_execute!(
-                self.instructions,
-                jump_if_false,
-                Inst::JUMP_IF_FALSE(self.instructions.len())
-            );
-        }
+    /// Turns the calls the body of a function ends with into `TAIL_CALL`s, the ones whose
+    /// result is what the `RETURN` about to be emitted returns.
+    fn emit_tail_calls(&mut self, body_start: usize) {
+        let end = self.instructions.len();
+
+        // Where running on from `i` ends up, past comments and jumps
+        let land = |mut i: usize| {
+            while i < end {
+                match self.instructions[i] {
+                    Inst::COMMENT(_) | Inst::NOP => i += 1,
+                    Inst::JUMP(target) if target > i => i = target,
+                    _ => break,
+                }
+            }
+            i
+        };
+
+        let tail_calls: Vec<_> = (body_start..end)
+            .filter(|&i| matches!(self.instructions[i], Inst::CALL(_)) && land(i + 1) == end)
+            .collect();
 
-        self.instructions.push(Inst::DEFAULT_NIL);
+        for i in tail_calls {
+            if let Inst::CALL(args) = self.instructions[i] {
+                self.instructions[i] = Inst::TAIL_CALL { args, iterators: 0 };
+            }
+        }
+    }
+
+    /// Suspends the generator this function becomes, handing `value` to whoever resumes it.
+    /// `yield` itself evaluates to nil once the generator continues.
+    pub fn compile_yield(&mut self, value: &Option<Box<Node>>) {
+        if self.function_yields.is_none() {
+            return self.error("`yield` outside of a function");
+        }
+        if self.in_constructor {
+            return self.error("A constructor cannot `yield`");
+        }
+        self.function_yields = Some(true);
+
+        if let Some(val) = value {
+            self.compile_node(val);
+        } else {
+            self.instructions.push(Inst::PUSH(Value::NIL));
+        }
+        self.instructions.push(Inst::YIELD);
+    }
+
+    pub fn compile_out(&mut self, value: &Option<Box<Node>>) {
+        let Some(&boundary) = self.out_targets.last() else {
+            return self.error("`out` outside of a block");
+        };
+
+        if let Some(val) = value {
+            self.compile_node(&*val);
+        } else {
+            self.instructions.push(Inst::PUSH(Value::NIL));
+        }
+        self.emit_unwind(boundary);
+        let _ = patch!(self.instructions, "out");
+    }
+
+    pub fn compile_try_catch(
+        &mut self,
+        block: &Node,
+        error_name: &Option<Rc<String>>,
+        catch_block: &Node,
+        is_statement: bool,
+    ) {
+        self.comment("Try start:");
+
+        let try_handler = patch!(self.instructions);
+
+        self.try_depth += 1;
+        self.compile_body(block, !is_statement);
+        self.try_depth -= 1;
+
+        self.instructions.push(Inst::END_TRY);
+        let jump_end = patch!(self.instructions);
+
+        patch_execute!(
+            self.instructions,
+            try_handler,
+            Inst::TRY(self.instructions.len())
+        );
+
+        // The VM pushes the caught error before jumping here, having dropped the slots the
+        // `try` block added but not the ones it reused
+        self.comment("Catch start:");
+        let slots = self.function().slots;
+        self.instructions.push(Inst::CLOSE_UPVALUES(slots));
+        self.push_scope();
+        if let Some(name) = error_name {
+            self.emit_store_local(name.as_str(), false);
+        } else {
+            self.instructions.push(Inst::POP);
+        }
+        self.compile_body(catch_block, !is_statement);
+        self.pop_scope();
+
+        patch_execute!(
+            self.instructions,
+            jump_end,
+            Inst::JUMP(self.instructions.len())
+        );
+
+        self.comment("Try end");
+    }
+
+    /// Arms are tried in order and only the first one whose pattern and guard match runs.
+    /// Each arm gets its own scope for the names its pattern binds.
+    pub fn compile_match(
+        &mut self,
+        expr: &Box<Node>,
+        branches: &Vec<(Pattern, Option<Node>, Node)>,
+        is_statement: bool,
+    ) {
+        self.check_exhaustive(branches);
+
+        self.push_scope();
+        self.compile_node(expr);
+        self.emit_store_local(MATCH_SUBJECT, true);
+
+        let mut jump_ends = vec![];
+
+        for (pattern, guard, value) in branches.iter() {
+            self.push_scope();
+
+            let mut fails = vec![];
+            self.compile_pattern(pattern, &mut vec![], &mut fails);
+
+            if let Some(guard) = guard {
+                self.compile_node(guard);
+                fails.push(patch!(self.instructions));
+            }
+
+            self.compile_body(value, !is_statement);
+            let close = self.pop_scope();
+            jump_ends.push(patch!(self.instructions));
+
+            // A failed arm still has to leave the scope it opened
+            for fail in fails {
+                patch_execute!(
+                    self.instructions,
+                    fail,
+                    Inst::JUMP_IF_FALSE(self.instructions.len())
+                );
+            }
+            self.instructions.extend(close);
+        }
+
+        // No arm matched
+        self.instructions.push(Inst::PUSH(Value::NIL));
+
+        for jump in jump_ends {
+            patch_execute!(self.instructions, jump, Inst::JUMP(self.instructions.len()));
+        }
+
+        self.pop_scope();
+    }
+
+    /// Binds the value on the stack to `pattern` in the current scope, erroring at runtime
+    /// if it doesn't have the pattern's shape.
+    fn compile_destructure(&mut self, pattern: &Pattern, is_const: bool) {
+        if let Pattern::Binding(name) = pattern {
+            return self.emit_store_local(name, is_const);
+        }
+
+        self.emit_store_local(MATCH_SUBJECT, false);
+        self.compile_destructure_part(pattern, &mut vec![], is_const);
+    }
+
+    fn compile_destructure_part(
+        &mut self,
+        pattern: &Pattern,
+        path: &mut Vec<PatternStep>,
+        is_const: bool,
+    ) {
+        match pattern {
+            Pattern::Wildcard => {}
+
+            Pattern::Binding(name) => {
+                self.emit_pattern_path(path);
+                self.emit_store_local(name, is_const);
+            }
+
+            Pattern::Sequence { items, rest, .. } => {
+                self.emit_pattern_path(path);
+                self.instructions.push(Inst::UNPACK {
+                    len: items.len(),
+                    rest: rest.is_some(),
+                });
+
+                let rest_start = rest.as_ref().map_or(items.len(), |(start, _)| *start);
+
+                for (i, item) in items.iter().enumerate() {
+                    path.push(if i < rest_start {
+                        PatternStep::Index(i)
+                    } else {
+                        PatternStep::Back(items.len() - i)
+                    });
+                    self.compile_destructure_part(item, path, is_const);
+                    path.pop();
+                }
+
+                if let Some((start, Some(name))) = rest {
+                    path.push(PatternStep::Slice(*start, items.len() - start));
+                    self.emit_pattern_path(path);
+                    path.pop();
+                    self.emit_store_local(name, is_const);
+                }
+            }
+
+            Pattern::Struct { name: None, fields } => {
+                for (field, pattern) in fields {
+                    path.push(PatternStep::Field(field.clone()));
+                    self.compile_destructure_part(pattern, path, is_const);
+                    path.pop();
+                }
+            }
+
+            _ => self.error(
+                "Only names, `_`, lists, tuples and `{ fields }` can be destructured, use `match` for patterns that can fail",
+            ),
+        }
+    }
+
+    fn emit_pattern_path(&mut self, path: &[PatternStep]) {
+        self.emit_load_local(MATCH_SUBJECT);
+
+        for step in path {
+            match step {
+                PatternStep::Index(i) => {
+                    self.instructions.push(Inst::PUSH(Value::Number(*i as f64)));
+                    self.instructions.push(Inst::GET_PROP);
+                }
+                PatternStep::Back(i) => {
+                    self.instructions
+                        .push(Inst::PUSH(Value::Number(-(*i as f64))));
+                    self.instructions.push(Inst::GET_PROP);
+                }
+                PatternStep::Field(name) => {
+                    self.instructions.push(Inst::PUSH(Value::string(name)));
+                    self.instructions.push(Inst::GET_PROP);
+                }
+                PatternStep::Slice(start, end) => {
+                    self.instructions.push(Inst::SLICE(*start, *end));
+                }
+            }
+        }
+    }
+
+    /// Checks the part of the match subject at `path` against `pattern`, binding names as it
+    /// goes. Every check leaves a bool and a patch index in `fails` to jump away if it's false.
+    fn compile_pattern(
+        &mut self,
+        pattern: &Pattern,
+        path: &mut Vec<PatternStep>,
+        fails: &mut Vec<usize>,
+    ) {
+        match pattern {
+            Pattern::Wildcard => {}
+
+            Pattern::Binding(name) => {
+                self.emit_pattern_path(path);
+                self.emit_store_local(name, false);
+            }
+
+            Pattern::Value(value) => {
+                self.emit_pattern_path(path);
+                self.compile_node(value);
+                self.instructions.push(Inst::MATCH);
+                fails.push(patch!(self.instructions));
+            }
+
+            Pattern::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.emit_pattern_path(path);
+                self.compile_node(start);
+                self.compile_node(end);
+                self.instructions.push(Inst::MATCH_RANGE(*inclusive));
+                fails.push(patch!(self.instructions));
+            }
+
+            Pattern::Or(alternatives) => {
+                let mut jump_oks = vec![];
+
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i == alternatives.len() - 1 {
+                        self.compile_pattern(alternative, path, fails);
+                        break;
+                    }
+
+                    let mut alternative_fails = vec![];
+                    self.compile_pattern(alternative, path, &mut alternative_fails);
+                    jump_oks.push(patch!(self.instructions));
+
+                    for fail in alternative_fails {
+                        patch_execute!(
+                            self.instructions,
+                            fail,
+                            Inst::JUMP_IF_FALSE(self.instructions.len())
+                        );
+                    }
+                }
+
+                for jump in jump_oks {
+                    patch_execute!(self.instructions, jump, Inst::JUMP(self.instructions.len()));
+                }
+            }
+
+            Pattern::Sequence {
+                is_tuple,
+                items,
+                rest,
+            } => {
+                self.emit_pattern_path(path);
+                self.instructions.push(Inst::MATCH_SEQ {
+                    tuple: *is_tuple,
+                    len: items.len(),
+                    rest: rest.is_some(),
+                });
+                fails.push(patch!(self.instructions));
+
+                let rest_start = rest.as_ref().map_or(items.len(), |(start, _)| *start);
+
+                for (i, item) in items.iter().enumerate() {
+                    path.push(if i < rest_start {
+                        PatternStep::Index(i)
+                    } else {
+                        PatternStep::Back(items.len() - i)
+                    });
+                    self.compile_pattern(item, path, fails);
+                    path.pop();
+                }
+
+                if let Some((start, Some(name))) = rest {
+                    path.push(PatternStep::Slice(*start, items.len() - start));
+                    self.emit_pattern_path(path);
+                    path.pop();
+                    self.emit_store_local(name, false);
+                }
+            }
+
+            Pattern::Variant {
+                target,
+                items,
+                fields,
+            } => {
+                self.emit_pattern_path(path);
+                self.compile_node(target);
+                self.instructions.push(Inst::MATCH_VARIANT(items.len()));
+                fails.push(patch!(self.instructions));
+
+                for (i, item) in items.iter().enumerate() {
+                    path.push(PatternStep::Index(i));
+                    self.compile_pattern(item, path, fails);
+                    path.pop();
+                }
+
+                for (field, pattern) in fields {
+                    path.push(PatternStep::Field(field.clone()));
+                    self.compile_pattern(pattern, path, fails);
+                    path.pop();
+                }
+            }
+
+            Pattern::Struct { name, fields } => {
+                if let Some(name) = name {
+                    self.emit_pattern_path(path);
+                    self.instructions.push(Inst::MATCH_TYPE(name.to_string()));
+                    fails.push(patch!(self.instructions));
+                }
+
+                for (field, pattern) in fields {
+                    path.push(PatternStep::Field(field.clone()));
+                    self.compile_pattern(pattern, path, fails);
+                    path.pop();
+                }
+            }
+        }
+    }
+
+    /// Warns when every arm compares against items of one enum but some items are left out.
+    fn check_exhaustive(&mut self, branches: &Vec<(Pattern, Option<Node>, Node)>) {
+        fn enum_item(target: &Node) -> Option<(&str, &str)> {
+            if let Node::MemberAccess { expr, member, .. } = target
+                && let (Node::Variable(name), Node::StringLiteral(item)) = (&**expr, &**member)
+            {
+                return Some((name.as_str(), item.as_str()));
+            }
+            None
+        }
+
+        fn enum_items<'a>(pattern: &'a Pattern, items: &mut Vec<(&'a str, &'a str)>) -> bool {
+            let item = match pattern {
+                Pattern::Value(target) => enum_item(target),
+
+                // Only a variant whose fields can't fail to match covers it
+                Pattern::Variant {
+                    target,
+                    items: fields,
+                    fields: named,
+                } if fields
+                    .iter()
+                    .chain(named.iter().map(|(_, p)| p))
+                    .all(|p| matches!(p, Pattern::Wildcard | Pattern::Binding(_))) =>
+                {
+                    enum_item(target)
+                }
+
+                Pattern::Or(alternatives) => {
+                    return alternatives.iter().all(|p| enum_items(p, items));
+                }
+                _ => None,
+            };
+
+            item.map(|item| items.push(item)).is_some()
+        }
+
+        let mut covered = vec![];
+
+        for (pattern, guard, _) in branches {
+            let is_catch_all = matches!(pattern, Pattern::Wildcard | Pattern::Binding(_));
+            if is_catch_all && guard.is_none() {
+                return;
+            }
+
+            let mut items = vec![];
+            if enum_items(pattern, &mut items) && guard.is_none() {
+                covered.extend(items);
+            }
+        }
+
+        let Some((name, _)) = covered.first().copied() else {
+            return;
+        };
+        let Some(all) = self.enums.get(name) else {
+            return;
+        };
+
+        let missing = all
+            .iter()
+            .filter(|item| !covered.contains(&(name, item.as_str())))
+            .map(|item| format!("`{item}`"))
+            .collect::<Vec<_>>();
+
+        if !missing.is_empty() {
+            self.warn(format!(
+                "Non-exhaustive match on enum `{name}`: missing {}",
+                missing.join(", ")
+            ));
+        }
     }
 
     pub fn compile_using(
         &mut self,
+        file: &Option<String>,
         sequence: &Vec<String>,
         imports: &Vec<(String, Option<String>)>,
-        _wildcard: bool,
+        wildcard: bool,
+        alias: &Option<String>,
     ) {
-        for (index, item) in sequence.iter().enumerate() {
-            if index == 0 {
-                let id = self.intern(item);
-                self.instructions.push(Inst::LOAD(id));
-            } else {
-                self.instructions
-                    .push(Inst::PUSH(Value::string(item.clone())));
-                self.instructions.push(Inst::GET_PROP);
+        // `using "a/b.ign"` and `using a::b` (when `a/b.ign` exists) import another file
+        let (module, walked) = match file {
+            Some(file) => (Some(self.source_dir().join(file)), 0),
+            None => match self.find_module(sequence) {
+                Some((path, walked)) => (Some(path), walked),
+                None => (None, 0),
+            },
+        };
+
+        let rest = match &module {
+            Some(path) => {
+                if self.scopes.len() > 1 {
+                    return self.error("Files can only be imported at the top level");
+                }
+                if !self.compile_module(path) {
+                    return;
+                }
+                &sequence[walked..]
             }
+            None => {
+                self.emit_load_local(&sequence[0]);
+                &sequence[1..]
+            }
+        };
+
+        for item in rest {
+            self.instructions
+                .push(Inst::PUSH(Value::string(item.clone())));
+            self.instructions.push(Inst::GET_PROP);
         }
 
-        if imports.len() > 0 {
+        if wildcard {
+            let members = match &module {
+                Some(path) if rest.is_empty() => fs::canonicalize(path)
+                    .ok()
+                    .and_then(|path| self.modules.get(&path))
+                    .map(|module| module.members.clone()),
+                Some(_) => None,
+                None => Self::std_members(sequence),
+            };
+            let path = match file {
+                Some(file) => format!("\"{file}\""),
+                None => sequence.join("::"),
+            };
+            self.compile_import_all(&path, members);
+        } else if imports.len() > 0 {
             for (idx, (item, alias)) in imports.iter().enumerate() {
                 if idx < imports.len() - 1 {
                     self.instructions.push(Inst::DUP)
@@ -910,17 +2118,278 @@ impl Compiler {
 					self.emit_store_local(item, false);
 				}
             }
-        } else {
-            self.emit_store_local(&sequence[sequence.len() - 1], false);
+        } else if let Some(alias) = alias {
+            self.emit_store_local(alias, false);
+        } else if let Some(last) = sequence.last() {
+            self.emit_store_local(last, false);
+        } else if let Some(path) = &module {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            self.emit_store_local(&stem, false);
+        }
+    }
+
+    /// Binds every member of the namespace on the stack in the current scope. `members` are
+    /// its member names when they are known while compiling, to warn about the ones hiding
+    /// a variable or builtin.
+    fn compile_import_all(&mut self, path: &str, members: Option<Vec<String>>) {
+        let global = self.scopes.len() == 1;
+
+        if let Some(mut members) = members {
+            members.sort();
+
+            let builtins = VM::initialize_globals();
+            for name in members.iter() {
+                if self.scopes.iter().any(|scope| scope.contains(name)) {
+                    self.warn(format!("`using {path}::*` shadows the variable `{name}`"));
+                } else if builtins.contains_key(&hash_u64!(name.as_str())) {
+                    self.warn(format!("`using {path}::*` shadows the builtin `{name}`"));
+                }
+            }
+
+            // Globals that don't resolve are looked up by name anyway
+            if !global {
+                for name in members {
+                    self.instructions.push(Inst::DUP);
+                    self.instructions.push(Inst::PUSH(Value::string(name.clone())));
+                    self.instructions.push(Inst::GET_PROP);
+                    self.emit_store_local(&name, false);
+                }
+                self.instructions.push(Inst::POP);
+                return;
+            }
+        } else if !global {
+            let slot = self.new_slot();
+            self.scopes.last_mut().unwrap().wildcards.push(slot);
+            self.instructions.push(Inst::STORE_LOCAL(slot));
+            return;
+        }
+
+        self.instructions.push(Inst::IMPORT_ALL);
+    }
+
+    /// Member names of the `Std` namespace `using` walks to, if it is one
+    fn std_members(sequence: &[String]) -> Option<Vec<String>> {
+        let (first, rest) = sequence.split_first()?;
+        if first != "Std" {
+            return None;
         }
+
+        let mut value = load_standard_namespace();
+        for item in rest {
+            let Value::Namespace(namespace) = value else {
+                return None;
+            };
+            value = namespace.borrow().env.get(item.as_str())?.0.clone();
+        }
+
+        let Value::Namespace(namespace) = value else {
+            return None;
+        };
+        let members = namespace.borrow().env.keys().map(|k| k.to_string()).collect();
+        Some(members)
+    }
+
+    /// Directory of the file being compiled, which imports are relative to
+    fn source_dir(&self) -> PathBuf {
+        self.files
+            .get(self.current_file)
+            .and_then(|file| Path::new(&**file).parent())
+            .map(Path::to_path_buf)
+            .unwrap_or_default()
+    }
+
+    /// The file `using a::b::c` refers to, and how many parts of the path it took: `a/b/c.ign`,
+    /// then `a/b.ign` and `a.ign`. Variables (like `Std`) shadow files.
+    fn find_module(&self, sequence: &[String]) -> Option<(PathBuf, usize)> {
+        let first = sequence.first()?;
+        if self.scopes.iter().any(|scope| scope.contains(first)) {
+            return None;
+        }
+
+        let dir = self.source_dir();
+        (1..=sequence.len()).rev().find_map(|len| {
+            let mut path = dir.join(sequence[..len].join("/"));
+            path.set_extension("ign");
+            path.is_file().then_some((path, len))
+        })
+    }
+
+    /// Pushes the namespace of the module at `path`, compiling the file the first time it is
+    /// imported. Its code runs once, in its own globals, and its top-level definitions become
+    /// the members of the namespace.
+    fn compile_module(&mut self, path: &Path) -> bool {
+        let Ok(canonical) = fs::canonicalize(path) else {
+            self.error(format!("Couldn't find module `{}`", path.display()));
+            return false;
+        };
+
+        if let Some(module) = self.modules.get(&canonical) {
+            self.instructions.push(Inst::LOAD_GLOBAL(module.id));
+            return true;
+        }
+
+        if self.module_stack.is_empty()
+            && let Some(file) = self.files.get(self.current_file)
+            && let Ok(main) = fs::canonicalize(&**file)
+        {
+            self.module_stack.push(main);
+        }
+
+        if let Some(start) = self.module_stack.iter().position(|p| *p == canonical) {
+            let cycle = self.module_stack[start..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.file_name().unwrap_or_default().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" -> ");
+
+            self.error(format!("Import cycle: {cycle}"));
+            return false;
+        }
+
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.error(format!("Couldn't read module `{}`: {e}", path.display()));
+                return false;
+            }
+        };
+
+        let file = path.to_string_lossy().to_string();
+        let tokens = Lexer::new(&text).get_tokens();
+        let mut parser = Parser::new(text.clone(), tokens);
+        let nodes = parser.parse_program();
+
+        let starts = line_starts(&text);
+        for diagnostic in parser.diagnostics.iter() {
+            let location = Some(diagnostic.location(&file, &starts));
+
+            if diagnostic.is_error() {
+                self.errors
+                    .push(CompileError::new(diagnostic.message.clone(), location));
+            } else {
+                self.warnings
+                    .push(CompileError::warning(diagnostic.message.clone(), location));
+            }
+        }
+        if parser.diagnostics.iter().any(|d| d.is_error()) {
+            return false;
+        }
+
+        let exports = Self::top_level_names(&nodes);
+
+        // The module gets its own top level, so its names don't mix with the importer's
+        let top_level = Scope {
+            vars: exports
+                .iter()
+                .map(|name| (name.clone(), Local::default()))
+                .collect(),
+            ..Scope::default()
+        };
+        let scopes = std::mem::replace(&mut self.scopes, vec![top_level]);
+        let prefix = self.module_prefix.replace(format!("{file}::"));
+        let current_file = self.current_file;
+        let starts = std::mem::take(&mut self.line_starts);
+        let span = self.current_span.take();
+
+        let start = self.instructions.len();
+        self.module_stack.push(canonical.clone());
+        self.set_source(&file, &text);
+        self.comment(&format!("Module {file}:"));
+
+        for node in nodes.iter() {
+            self.compile_node(node);
+        }
+        for name in exports.iter() {
+            self.emit_load_local(name);
+        }
+
+        self.module_stack.pop();
+        self.scopes = scopes;
+        self.module_prefix = prefix;
+        self.current_file = current_file;
+        self.line_starts = starts;
+        self.current_span = span;
+        self.mark_span();
+
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let id = self.intern(&format!("<module {file}>"));
+        self.instructions.push(Inst::MODULE {
+            name: name.to_string(),
+            members: exports.clone(),
+        });
+        self.instructions.push(Inst::DUP);
+        self.instructions.push(Inst::STORE_GLOBAL_CONST(id));
+        self.modules.insert(
+            canonical,
+            Module {
+                id,
+                start,
+                members: exports,
+            },
+        );
+
+        true
     }
 
-    pub fn compile_enum_def(&mut self, name: &String, items: &Vec<(String, Node)>) {
+    /// Names a file defines at its top level
+    fn top_level_names(nodes: &[Node]) -> Vec<String> {
+        let mut names = vec![];
+
+        for node in nodes {
+            let node = match node {
+                Node::ExprStmt(node, _) => &**node,
+                node => node,
+            };
+
+            match node {
+                Node::LetStatement { names: lets, .. } => {
+                    names.extend(lets.iter().map(|name| name.to_string()))
+                }
+                Node::FunctionDefinition {
+                    name: Some(name), ..
+                } => names.push(name.to_string()),
+                Node::ClassDef { name, .. }
+                | Node::StructDef { name, .. }
+                | Node::EnumDef { name, .. } => names.push(name.clone()),
+                Node::InterfaceDef { name, .. } => names.push(name.to_string()),
+                _ => {}
+            }
+        }
+
+        names
+    }
+
+    pub fn compile_enum_def(&mut self, name: &String, items: &Vec<(String, EnumItem)>) {
         let mut name_vec = vec![];
+        self.type_names.insert(name.clone());
 
-        for (name, value) in items {
-            name_vec.push(Value::string(name));
-            self.compile_node(value);
+        self.enums.insert(
+            name.clone(),
+            items.iter().map(|(item, _)| item.clone()).collect(),
+        );
+
+        for (item_name, item) in items {
+            name_vec.push(Value::string(item_name));
+
+            match item {
+                EnumItem::Value(value) => self.compile_node(value),
+                EnumItem::Variant { fields, is_struct } => {
+                    let def = rc!(TVariantDef::new(
+                        name.clone(),
+                        item_name.clone(),
+                        fields.clone(),
+                        *is_struct,
+                    ));
+
+                    // Variants without fields are values already, the rest are constructors
+                    self.instructions.push(Inst::PUSH(if fields.is_empty() {
+                        Value::Variant(rc!(TVariant::new(def, rc!(vec![]))))
+                    } else {
+                        Value::VariantDef(def)
+                    }));
+                }
+            }
         }
         self.instructions.push(Inst::ENUM(name.clone(), name_vec));
         self.emit_store_local(name, false);
@@ -932,6 +2401,8 @@ impl Compiler {
             field_map.insert(k.clone(), v.clone());
         }
 
+        self.type_names.insert(name.clone());
+        self.type_hints.push((self.instructions.len(), self.current_location()));
         self.instructions
             .push(Inst::PUSH(Value::StructDef(rc!(TStructDef::new(
                 name.clone(),
@@ -941,6 +2412,175 @@ impl Compiler {
         self.emit_store_local(name, false);
     }
 
+    pub fn compile_class_def(
+        &mut self,
+        name: &String,
+        interfaces: &Vec<Rc<String>>,
+        let_statements: &Vec<Node>,
+        functions: &Vec<Node>,
+        span: &TokenRange,
+    ) {
+        self.comment(&format!("Class {name}:"));
+        self.type_names.insert(name.clone());
+
+        // Conformance is checked by `CLASS` once the methods exist
+        for interface in interfaces {
+            self.compile_node(&Node::Variable(interface.clone()));
+        }
+
+        let mut fields = vec![];
+        for statement in let_statements {
+            if let Node::LetStatement { names, values, .. } = statement {
+                for (i, field) in names.iter().enumerate() {
+                    fields.push((field.to_string(), values.get(i).and_then(Option::as_ref)));
+                }
+            }
+        }
+
+        let mut methods = vec![];
+        let mut init = None;
+        for function in functions {
+            let Node::FunctionDefinition {
+                name: Some(method),
+                args,
+                return_type,
+                block,
+                ..
+            } = function
+            else {
+                self.error("Class methods must be named");
+                continue;
+            };
+
+            if method.as_str() == "init" {
+                init = Some((args, block));
+                continue;
+            }
+
+            // Every method takes the object it was called on as `self`
+            let mut args = args.clone();
+            args.insert(0, (rc!(String::from("self")), None, None));
+
+            let function = format!("{name}.{method}");
+            self.compile_closure(&function, &args, return_type.as_ref(), None, |this| {
+                this.compile_node(block)
+            });
+            methods.push(method.to_string());
+        }
+
+        // The constructor sets each field to its initial value, runs `init` and returns `self`
+        let mut args = init.map(|(args, _)| args.clone()).unwrap_or_default();
+        args.insert(0, (rc!(String::from("self")), None, None));
+
+        self.compile_closure(name, &args, None, None, |this| {
+            let load_self = Node::Variable(rc!(String::from("self")));
+
+            for (field, value) in fields.iter() {
+                if let Some(value) = value {
+                    this.compile_node(value);
+                } else {
+                    this.instructions.push(Inst::PUSH(Value::NIL));
+                }
+                this.compile_node(&load_self);
+                this.instructions
+                    .push(Inst::PUSH(Value::string(field)));
+                this.instructions.push(Inst::SET_PROP);
+            }
+
+            if let Some((_, block)) = init {
+                this.in_constructor = true;
+                this.compile_node(block);
+                this.instructions.push(Inst::POP);
+            }
+
+            this.compile_node(&load_self);
+        });
+        methods.push(String::from("init"));
+
+        self.with_span(span, |this| {
+            this.instructions.push(Inst::CLASS {
+                name: name.clone(),
+                fields: fields.into_iter().map(|(field, _)| field).collect(),
+                methods,
+                interfaces: interfaces.len(),
+            })
+        });
+
+        self.emit_store_local(name, false);
+    }
+
+    pub fn compile_impl_block(&mut self, name: &Rc<String>, functions: &Vec<Node>) {
+        self.comment(&format!("Impl {name}:"));
+
+        let mut methods = vec![];
+        let mut statics = vec![];
+
+        for function in functions {
+            let Node::FunctionDefinition {
+                name: Some(function_name),
+                args,
+                return_type,
+                block,
+                ..
+            } = function
+            else {
+                self.error("Functions in an impl block must be named");
+                continue;
+            };
+
+            if args.first().is_some_and(|(arg, _, _)| arg.as_str() == "self") {
+                methods.push((function_name.to_string(), args, return_type, block));
+            } else {
+                statics.push((function_name.to_string(), args, return_type, block));
+            }
+        }
+
+        // `IMPL` pops the statics first, then the methods below them
+        for (function, args, return_type, block) in methods.iter().chain(statics.iter()) {
+            let function = format!("{name}::{function}");
+            self.compile_closure(&function, args, return_type.as_ref(), None, |this| {
+                this.compile_node(block)
+            });
+        }
+
+        self.emit_load_local(name);
+        self.instructions.push(Inst::IMPL {
+            methods: methods.into_iter().map(|(name, ..)| name).collect(),
+            statics: statics.into_iter().map(|(name, ..)| name).collect(),
+        });
+    }
+
+    pub fn compile_interface_def(&mut self, name: &Rc<String>, let_statements: &Vec<Node>, functions: &Vec<Node>) {
+        self.type_names.insert(name.to_string());
+        let mut fields = vec![];
+        for statement in let_statements {
+            if let Node::LetStatement { names, .. } = statement {
+                fields.extend(names.iter().map(|field| field.to_string()));
+            }
+        }
+
+        let mut methods = vec![];
+        for function in functions {
+            if let Node::FunctionDefinition {
+                name: Some(method),
+                args,
+                ..
+            } = function
+            {
+                methods.push((method.to_string(), args.len()));
+            }
+        }
+
+        self.instructions
+            .push(Inst::PUSH(Value::Interface(rc!(TInterface::new(
+                name.to_string(),
+                fields,
+                methods,
+            )))));
+
+        self.emit_store_local(name, false);
+    }
+
     pub fn compile_struct_init(&mut self, target: &Box<Node>, fields: &Vec<(String, Node)>) {
         let mut field_names = vec![];
         for (name, value) in fields {
@@ -0,0 +1,44 @@
+use crate::misc::source::Location;
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone)]
+pub struct CompileError {
+    pub message: String,
+    pub location: Option<Location>,
+    pub is_warning: bool,
+}
+
+impl CompileError {
+    pub fn new(message: impl Into<String>, location: Option<Location>) -> Self {
+        Self {
+            message: message.into(),
+            location,
+            is_warning: false,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>, location: Option<Location>) -> Self {
+        Self {
+            is_warning: true,
+            ..Self::new(message, location)
+        }
+    }
+}
+
+impl Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_warning {
+            write!(f, "Warning: {}", self.message)?;
+        } else {
+            write!(f, "CompileError: {}", self.message)?;
+        }
+
+        if let Some(location) = &self.location {
+            write!(f, "\n    at {location}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileError {}
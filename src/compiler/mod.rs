@@ -1,3 +1,5 @@
 pub mod compiler;
-// pub mod register_allocator;
+pub mod error;
+pub mod register_allocator;
+pub mod register_compiler;
 pub mod optimization;
@@ -1,8 +1,9 @@
 use crate::{
     compiler::compiler::Compiler,
     hash_u64,
-    virtual_machine::{inst::Inst, value::Value},
+    virtual_machine::{chunk::LineInfo, inst::Inst, value::Value},
 };
+use std::rc::Rc;
 
 impl Compiler {
     pub fn optimize(&mut self) {
@@ -42,6 +43,11 @@ impl Compiler {
             Inst::CALL(1),
             Inst::TO_STRING,
         );
+        self.replace_pattern_2(
+            Inst::LOAD_GLOBAL(hash_u64!("string")),
+            Inst::TAIL_CALL { args: 1, iterators: 0 },
+            Inst::TO_STRING,
+        );
 
         self.replace(Inst::CONCAT_STR(1), Inst::TO_STRING);
 
@@ -83,9 +89,10 @@ impl Compiler {
             .map(|(i, _)| i)
             .collect();
 
+        // NOPs are removed by `remove_nops`, which keeps jump targets and the line table in sync
         for i in indices {
             self.instructions[i] = replacement.clone();
-            self.instructions.remove(i + 1);
+            self.instructions[i + 1] = Inst::NOP;
         }
     }
 
@@ -99,7 +106,7 @@ impl Compiler {
 
         for (i, replacement) in indices {
             self.instructions[i] = replacement;
-            self.instructions.remove(i + 1);
+            self.instructions[i + 1] = Inst::NOP;
         }
     }
 
@@ -121,18 +128,30 @@ impl Compiler {
                 | Inst::JUMP_IF_FALSE(target)
                 | Inst::JUMP_IF_TRUE(target)
                 | Inst::JUMP_IF_NOT_NIL(target)
-                | Inst::FOR_ITER(target) => {
+                | Inst::FOR_ITER(target)
+                | Inst::TRY(target) => {
                     *target = old_to_new[*target];
                 }
 
                 // Functions
-                Inst::PUSH(Value::Function(f)) => f.entry = old_to_new[f.entry],
+                Inst::PUSH(Value::Function(f)) => Rc::make_mut(f).entry = old_to_new[f.entry],
                 Inst::MAKE_CLOSURE { entry, .. } => *entry = old_to_new[*entry],
 
                 _ => {}
             }
         }
 
+        let lines = std::mem::take(&mut self.lines);
+        for line in lines {
+            LineInfo::push(
+                &mut self.lines,
+                LineInfo {
+                    start: old_to_new[line.start],
+                    ..line
+                },
+            );
+        }
+
         self.instructions.retain(|inst| !matches!(inst, Inst::NOP));
     }
 
@@ -141,7 +160,7 @@ impl Compiler {
         let mut i = 0;
         while i < self.instructions.len().saturating_sub(1) {
             match (&self.instructions[i], &self.instructions[i + 1]) {
-                (Inst::LOAD(_), Inst::POP | Inst::TRY_POP) => {
+                (Inst::LOAD { .. }, Inst::POP | Inst::TRY_POP) => {
                     self.instructions[i] = Inst::COMMENT("optimized away LOAD".to_string());
                     self.instructions[i + 1] = Inst::COMMENT("optimized away POP".to_string());
                     i += 2;
@@ -151,7 +170,7 @@ impl Compiler {
                     self.instructions[i + 1] = Inst::COMMENT("optimized away POP".to_string());
                     i += 2;
                 }
-                (Inst::LOAD_LOCAL { .. }, Inst::POP | Inst::TRY_POP) => {
+                (Inst::LOAD_LOCAL(_), Inst::POP | Inst::TRY_POP) => {
                     self.instructions[i] = Inst::COMMENT("optimized away LOAD_LOCAL".to_string());
                     self.instructions[i + 1] = Inst::COMMENT("optimized away POP".to_string());
                     i += 2;
@@ -177,16 +196,7 @@ impl Compiler {
         let mut i = 0;
         while i < self.instructions.len().saturating_sub(1) {
             let is_redundant = match (&self.instructions[i], &self.instructions[i + 1]) {
-                (
-                    Inst::STORE_LOCAL {
-                        id: id_a,
-                        depth: depth_a,
-                    },
-                    Inst::LOAD_LOCAL {
-                        id: id_b,
-                        depth: depth_b,
-                    },
-                ) => *id_a == *id_b && *depth_a == *depth_b,
+                (Inst::STORE_LOCAL(a), Inst::LOAD_LOCAL(b)) => a == b,
                 _ => false,
             };
 
@@ -1,6 +1,8 @@
 pub struct RegisterAllocator {
     next: u8,
     free: Vec<u8>,
+    /// Most registers in use at once, which is what a frame needs
+    size: u8,
 }
 
 impl RegisterAllocator {
@@ -8,19 +10,49 @@ impl RegisterAllocator {
         Self {
             next: 0,
             free: vec![],
+            size: 0,
         }
     }
 
-    pub fn alloc(&mut self) -> u8 {
-        self.free.pop().unwrap_or_else(|| {
-			assert!(self.next < 255, "register overflow");
-            let reg = self.next;
-            self.next += 1;
-            reg
-        })
+    /// A free register, or `None` once they are all taken.
+    pub fn alloc(&mut self) -> Option<u8> {
+        if let Some(reg) = self.free.pop() {
+            return Some(reg);
+        }
+
+        self.alloc_range(1)
+    }
+
+    /// `n` consecutive registers above every one in use, for the arguments of a call or the
+    /// items of a list. Returns the first.
+    pub fn alloc_range(&mut self, n: u8) -> Option<u8> {
+        let base = self.next;
+        self.next = self.next.checked_add(n)?;
+        self.size = self.size.max(self.next);
+        Some(base)
     }
 
     pub fn free(&mut self, reg: u8) {
-        self.free.push(reg);
+        if reg + 1 != self.next {
+            self.free.push(reg);
+            return;
+        }
+        self.next -= 1;
+
+        // Registers freed earlier may now be on top too
+        while let Some(pos) = self.free.iter().position(|&r| r + 1 == self.next) {
+            self.free.swap_remove(pos);
+            self.next -= 1;
+        }
+    }
+
+    pub fn free_range(&mut self, base: u8, n: u8) {
+        for i in (0..n).rev() {
+            self.free(base + i);
+        }
+    }
+
+    pub fn size(&self) -> u8 {
+        self.size
     }
 }
@@ -0,0 +1,885 @@
+use crate::{
+    compiler::{compiler::Compiler, register_allocator::RegisterAllocator},
+    hash_u64,
+    language::{
+        nodes::{Node, Pattern},
+        token::{TokenKind, TokenRange},
+    },
+    misc::source::{line_col, line_starts},
+    rc,
+    virtual_machine::{
+        chunk::LineInfo, reg_inst::RegInst, types::function::TFunction, value::Value,
+    },
+};
+use std::{
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+/// `Err` names what the register compiler ran into that it can't compile yet.
+pub type RegisterResult<T = ()> = Result<T, String>;
+
+type Param = (Rc<String>, Option<Rc<String>>, Option<Node>);
+
+struct Local {
+    name: Rc<String>,
+    reg: u8,
+    is_const: bool,
+}
+
+struct LoopLabels {
+    start: usize,
+    breaks: Vec<usize>,
+}
+
+/// Compiles a program to the instructions of the register VM. It covers the core of the
+/// language (locals, arithmetic, control flow, lists, calls and top-level functions), and
+/// `compile` fails on anything else so the program can run on the stack VM instead.
+pub struct RegisterCompiler {
+    pub instructions: Vec<RegInst>,
+    pub constants: Vec<Value>,
+    pub lines: Vec<LineInfo>,
+    /// The file the compiled code comes from, in the chunk's `files`
+    file: usize,
+    line_starts: Vec<usize>,
+    current_span: Option<LineInfo>,
+
+    registers: RegisterAllocator,
+    scopes: Vec<Vec<Local>>,
+    loops: Vec<LoopLabels>,
+    in_function: bool,
+    /// The constant holding each top-level function
+    functions: HashMap<Rc<String>, usize>,
+    /// Variables of the top-level code, which functions can't see in registers
+    top_level: HashSet<Rc<String>>,
+}
+
+impl RegisterCompiler {
+    pub fn new(file: usize, text: &str) -> Self {
+        Self {
+            instructions: vec![],
+            constants: vec![],
+            lines: vec![],
+            file,
+            line_starts: line_starts(text),
+            current_span: None,
+            registers: RegisterAllocator::new(),
+            scopes: vec![],
+            loops: vec![],
+            in_function: false,
+            functions: HashMap::new(),
+            top_level: HashSet::new(),
+        }
+    }
+
+    pub fn compile(&mut self, nodes: &[Node]) -> RegisterResult {
+        let enter = self.instructions.len();
+        self.emit(RegInst::ENTER(0));
+
+        // Functions become constants, which the top-level code can use from their definition on
+        let mut functions = vec![];
+        self.scopes.push(vec![]);
+        for node in nodes {
+            if let Node::FunctionDefinition {
+                name: Some(name),
+                return_type,
+                args,
+                block,
+                ..
+            } = node
+            {
+                if return_type.is_some() {
+                    return Err(format!("the return type of `{name}`"));
+                }
+
+                // Filled in once the function is compiled
+                self.constants.push(Value::NIL);
+                let k = self.constants.len() - 1;
+                if self.functions.insert(name.clone(), k).is_some() {
+                    return Err(format!("redefining `{name}`"));
+                }
+                functions.push((k, args, block));
+            } else {
+                self.statement(node)?;
+            }
+        }
+        self.pop_scope();
+        self.emit(RegInst::EXIT);
+        self.instructions[enter] = RegInst::ENTER(self.registers.size());
+
+        for (k, args, block) in functions {
+            self.constants[k] = Value::Function(rc!(self.function(args, block)?));
+        }
+
+        Ok(())
+    }
+
+    fn function(&mut self, args: &[Param], block: &Node) -> RegisterResult<TFunction> {
+        if let Some((name, ..)) = args
+            .iter()
+            .find(|(_, hint, default)| hint.is_some() || default.is_some())
+        {
+            return Err(format!("the type hint or default value of `{name}`"));
+        }
+
+        self.registers = RegisterAllocator::new();
+        self.in_function = true;
+        self.current_span = None;
+
+        let entry = self.instructions.len();
+        self.emit(RegInst::ENTER(0));
+
+        // The caller leaves the arguments in the first registers
+        self.scopes.push(vec![]);
+        for (name, ..) in args {
+            self.declare(name, false)?;
+        }
+
+        let result = self.alloc()?;
+        self.block(block, Some(result))?;
+        self.emit(RegInst::RETURN(result));
+        self.pop_scope();
+
+        self.instructions[entry] = RegInst::ENTER(self.registers.size());
+        Ok(TFunction::new(entry, args.len()))
+    }
+
+    fn statement(&mut self, node: &Node) -> RegisterResult {
+        match node {
+            Node::ExprStmt(expr, span) => self.with_span(span, |this| this.statement(expr)),
+
+            Node::LetStatement {
+                names,
+                values,
+                is_const,
+            } => {
+                for (i, name) in names.iter().enumerate() {
+                    // The value can still use a variable the new one shadows
+                    let reg = self.alloc()?;
+                    match values.get(i) {
+                        Some(Some(value)) => self.expr(value, reg)?,
+                        _ => self.emit(RegInst::LOAD_NIL(reg)),
+                    }
+                    self.declare_in(name, reg, *is_const);
+                }
+                Ok(())
+            }
+
+            Node::IfStatement {
+                condition,
+                block,
+                elifs,
+                else_block,
+            } => self.if_statement(condition, block, elifs, else_block, None),
+            Node::WhileLoop { condition, block } => self.while_loop(Some(condition), block),
+            Node::Loop { block } => self.while_loop(None, block),
+            Node::ForLoop {
+                pattern,
+                expr,
+                block,
+            } => self.for_loop(pattern, expr, block),
+
+            Node::BreakStatement(None) => {
+                let jump = self.emit_jump(RegInst::JUMP(0));
+                let labels = self.loops.last_mut().ok_or("`break` outside of a loop")?;
+                labels.breaks.push(jump);
+                Ok(())
+            }
+            Node::ContinueStatement => match self.loops.last() {
+                Some(labels) => {
+                    self.emit(RegInst::JUMP(labels.start));
+                    Ok(())
+                }
+                None => Err("`continue` outside of a loop".to_string()),
+            },
+            Node::ReturnStatement(value) if self.in_function => {
+                let reg = self.alloc()?;
+                match value {
+                    Some(value) => self.expr(value, reg)?,
+                    None => self.emit(RegInst::LOAD_NIL(reg)),
+                }
+                self.emit(RegInst::RETURN(reg));
+                self.registers.free(reg);
+                Ok(())
+            }
+
+            Node::Block { .. } | Node::SingleLineBlock { .. } => self.block(node, None),
+
+            _ => {
+                let reg = self.alloc()?;
+                self.expr(node, reg)?;
+                self.registers.free(reg);
+                Ok(())
+            }
+        }
+    }
+
+    /// Compiles `node`, leaving its value in `dst`. Nothing writes `dst` before it's done reading
+    /// its operands, except what `writes_last` rules out.
+    fn expr(&mut self, node: &Node, dst: u8) -> RegisterResult {
+        match node {
+            Node::NIL => self.emit(RegInst::LOAD_NIL(dst)),
+            Node::BooleanLiteral(value) => self.emit(RegInst::LOAD_BOOL { dst, value: *value }),
+            Node::NumberLiteral(x) => self.load_constant(dst, Value::Number(*x)),
+            Node::StringLiteral(x) => self.load_constant(dst, Value::string(x)),
+
+            Node::Variable(name) => {
+                if let Some(local) = self.resolve(name) {
+                    if local.reg != dst {
+                        let src = local.reg;
+                        self.emit(RegInst::MOVE { dst, src });
+                    }
+                } else if self.functions.contains_key(name) {
+                    return Err(format!("`{name}` used as a value"));
+                } else {
+                    self.load_global(name, dst)?;
+                }
+            }
+
+            Node::ListNode(items) | Node::TupleNode(items) => {
+                let len = u8::try_from(items.len()).map_err(|_| "a list of over 255 items")?;
+                let base = self.alloc_range(len)?;
+                for (i, item) in items.iter().enumerate() {
+                    self.expr(item, base + i as u8)?;
+                }
+                self.emit(if let Node::TupleNode(_) = node {
+                    RegInst::TUPLE { dst, base, len }
+                } else {
+                    RegInst::LIST { dst, base, len }
+                });
+                self.registers.free_range(base, len);
+            }
+
+            Node::RangeNode {
+                start,
+                end,
+                step,
+                inclusive,
+            } => {
+                let base = self.alloc_range(3)?;
+                self.expr(start, base)?;
+                self.expr(end, base + 1)?;
+                match step {
+                    Some(step) => self.expr(step, base + 2)?,
+                    None => self.load_constant(base + 2, Value::Number(1.0)),
+                }
+                self.emit(RegInst::RANGE {
+                    dst,
+                    base,
+                    inclusive: *inclusive,
+                });
+                self.registers.free_range(base, 3);
+            }
+
+            Node::BinOp {
+                left,
+                right,
+                op,
+                span,
+            } => self.with_span(span, |this| {
+                let (a, a_temp) = this.operand(left)?;
+                let (b, b_temp) = this.operand(right)?;
+                this.emit(Self::binary_inst(op, dst, a, b)?);
+                this.free_temp(b, b_temp);
+                this.free_temp(a, a_temp);
+                Ok(())
+            })?,
+            Node::ComparisonChain {
+                expressions,
+                operators,
+            } => self.comparison_chain(expressions, operators, dst)?,
+
+            Node::UnaryOp {
+                op,
+                right,
+                is_prefix,
+                span,
+            } => self.with_span(span, |this| this.unary_op(op, right, *is_prefix, dst))?,
+
+            Node::NullCoalesce { left, right } | Node::ElvisCoalesce { left, right } => {
+                self.expr(left, dst)?;
+                let jump = if let Node::NullCoalesce { .. } = node {
+                    self.emit_jump(RegInst::JUMP_IF_NOT_NIL {
+                        src: dst,
+                        target: 0,
+                    })
+                } else {
+                    let skip = self.emit_jump(RegInst::JUMP_IF_FALSE {
+                        cond: dst,
+                        target: 0,
+                    });
+                    let jump = self.emit_jump(RegInst::JUMP(0));
+                    self.patch(skip);
+                    jump
+                };
+                self.expr(right, dst)?;
+                self.patch(jump);
+            }
+            Node::TernaryOp {
+                condition,
+                true_expr,
+                false_expr,
+            } => {
+                let jump_if_false = self.condition(condition)?;
+                self.expr(true_expr, dst)?;
+                let jump_end = self.emit_jump(RegInst::JUMP(0));
+                self.patch(jump_if_false);
+                self.expr(false_expr, dst)?;
+                self.patch(jump_end);
+            }
+            Node::IfStatement {
+                condition,
+                block,
+                elifs,
+                else_block,
+            } => self.if_statement(condition, block, elifs, else_block, Some(dst))?,
+            Node::Block { .. } | Node::SingleLineBlock { .. } => self.block(node, Some(dst))?,
+
+            Node::SetVariable { target, value } => match &**target {
+                Node::Variable(name) => {
+                    let reg = self.assignable(name)?;
+                    if Self::writes_last(value) {
+                        self.expr(value, reg)?;
+                    } else {
+                        let temp = self.alloc()?;
+                        self.expr(value, temp)?;
+                        self.emit(RegInst::MOVE {
+                            dst: reg,
+                            src: temp,
+                        });
+                        self.registers.free(temp);
+                    }
+                    if reg != dst {
+                        self.emit(RegInst::MOVE { dst, src: reg });
+                    }
+                }
+                Node::MemberAccess { expr, member, .. } => {
+                    let (target, target_temp) = self.operand(expr)?;
+                    let (key, key_temp) = self.operand(member)?;
+                    self.expr(value, dst)?;
+                    self.emit(RegInst::SET_PROP {
+                        target,
+                        key,
+                        src: dst,
+                    });
+                    self.free_temp(key, key_temp);
+                    self.free_temp(target, target_temp);
+                }
+                _ => return Err("assigning to that target".to_string()),
+            },
+            Node::ShorthandAssignment {
+                token,
+                target,
+                value,
+            } => {
+                let Node::Variable(name) = &**target else {
+                    return Err("shorthand assignment to a member".to_string());
+                };
+                let op = match token {
+                    TokenKind::ADD_SH => TokenKind::PLUS,
+                    TokenKind::SUB_SH => TokenKind::MINUS,
+                    TokenKind::MUL_SH => TokenKind::STAR,
+                    TokenKind::DIV_SH => TokenKind::SLASH,
+                    TokenKind::POW_SH => TokenKind::POW,
+                    TokenKind::MOD_SH => TokenKind::MOD,
+                    _ => return Err(format!("the `{token:?}` assignment")),
+                };
+
+                let reg = self.assignable(name)?;
+                let (b, b_temp) = self.operand(value)?;
+                self.emit(Self::binary_inst(&op, reg, reg, b)?);
+                self.free_temp(b, b_temp);
+                if reg != dst {
+                    self.emit(RegInst::MOVE { dst, src: reg });
+                }
+            }
+
+            Node::MemberAccess { expr, member, span } => self.with_span(span, |this| {
+                let (target, target_temp) = this.operand(expr)?;
+                let (key, key_temp) = this.operand(member)?;
+                this.emit(RegInst::GET_PROP { dst, target, key });
+                this.free_temp(key, key_temp);
+                this.free_temp(target, target_temp);
+                Ok(())
+            })?,
+
+            Node::FunctionCall { target, args, span } => {
+                self.with_span(span, |this| this.call(target, args, dst))?
+            }
+
+            // Statements evaluate to nil
+            Node::LetStatement { .. }
+            | Node::WhileLoop { .. }
+            | Node::Loop { .. }
+            | Node::ForLoop { .. }
+            | Node::BreakStatement(None)
+            | Node::ContinueStatement
+            | Node::ReturnStatement(_)
+                if self.in_function || !matches!(node, Node::ReturnStatement(_)) =>
+            {
+                self.statement(node)?;
+                self.emit(RegInst::LOAD_NIL(dst));
+            }
+
+            _ => return Err(Self::describe(node)),
+        }
+
+        Ok(())
+    }
+
+    /// Whether `node` only writes its destination after reading everything else, so an
+    /// assignment can compile it straight into the variable's register.
+    fn writes_last(node: &Node) -> bool {
+        matches!(
+            node,
+            Node::NIL
+                | Node::BooleanLiteral(_)
+                | Node::NumberLiteral(_)
+                | Node::StringLiteral(_)
+                | Node::Variable(_)
+                | Node::BinOp { .. }
+                | Node::MemberAccess { .. }
+                | Node::FunctionCall { .. }
+                | Node::ListNode(_)
+                | Node::TupleNode(_)
+        )
+    }
+
+    /// A register holding the value of `node`: the variable's own register for a local,
+    /// otherwise a temporary one (the `bool`) that the caller frees.
+    fn operand(&mut self, node: &Node) -> RegisterResult<(u8, bool)> {
+        if let Node::Variable(name) = node
+            && let Some(local) = self.resolve(name)
+        {
+            return Ok((local.reg, false));
+        }
+
+        let reg = self.alloc()?;
+        self.expr(node, reg)?;
+        Ok((reg, true))
+    }
+
+    fn free_temp(&mut self, reg: u8, is_temp: bool) {
+        if is_temp {
+            self.registers.free(reg);
+        }
+    }
+
+    /// Evaluates `condition` and emits the jump to patch to wherever it's false.
+    fn condition(&mut self, condition: &Node) -> RegisterResult<usize> {
+        let (cond, temp) = self.operand(condition)?;
+        let jump = self.emit_jump(RegInst::JUMP_IF_FALSE { cond, target: 0 });
+        self.free_temp(cond, temp);
+        Ok(jump)
+    }
+
+    fn binary_inst(op: &TokenKind, dst: u8, a: u8, b: u8) -> RegisterResult<RegInst> {
+        Ok(match op {
+            TokenKind::PLUS => RegInst::ADD { dst, a, b },
+            TokenKind::MINUS => RegInst::SUB { dst, a, b },
+            TokenKind::STAR => RegInst::MUL { dst, a, b },
+            TokenKind::SLASH => RegInst::DIV { dst, a, b },
+            TokenKind::POW => RegInst::POW { dst, a, b },
+            TokenKind::MOD => RegInst::MOD { dst, a, b },
+
+            TokenKind::GT => RegInst::GT { dst, a, b },
+            TokenKind::LT => RegInst::LT { dst, a, b },
+            TokenKind::GE => RegInst::GE { dst, a, b },
+            TokenKind::LE => RegInst::LE { dst, a, b },
+
+            TokenKind::EQ => RegInst::EQ { dst, a, b },
+            TokenKind::NEQ => RegInst::NEQ { dst, a, b },
+
+            TokenKind::AND => RegInst::AND { dst, a, b },
+            TokenKind::OR => RegInst::OR { dst, a, b },
+
+            _ => return Err(format!("the `{op:?}` operator")),
+        })
+    }
+
+    fn comparison_chain(
+        &mut self,
+        expressions: &[Node],
+        operators: &[TokenKind],
+        dst: u8,
+    ) -> RegisterResult {
+        // Every operand is evaluated once, and the result only written at the end
+        let result = self.alloc()?;
+        let (mut a, mut a_temp) = self.operand(&expressions[0])?;
+
+        for (i, op) in operators.iter().enumerate() {
+            let (b, b_temp) = self.operand(&expressions[i + 1])?;
+            if i == 0 {
+                self.emit(Self::binary_inst(op, result, a, b)?);
+            } else {
+                let temp = self.alloc()?;
+                self.emit(Self::binary_inst(op, temp, a, b)?);
+                self.emit(RegInst::AND {
+                    dst: result,
+                    a: result,
+                    b: temp,
+                });
+                self.registers.free(temp);
+            }
+            self.free_temp(a, a_temp);
+            (a, a_temp) = (b, b_temp);
+        }
+
+        self.free_temp(a, a_temp);
+        self.emit(RegInst::MOVE { dst, src: result });
+        self.registers.free(result);
+        Ok(())
+    }
+
+    fn unary_op(
+        &mut self,
+        op: &TokenKind,
+        target: &Node,
+        is_prefix: bool,
+        dst: u8,
+    ) -> RegisterResult {
+        if matches!(op, TokenKind::INCREMENT | TokenKind::DECREMENT) {
+            let Node::Variable(name) = target else {
+                return Err("incrementing a member".to_string());
+            };
+            let reg = self.assignable(name)?;
+
+            if !is_prefix && reg != dst {
+                self.emit(RegInst::MOVE { dst, src: reg });
+            }
+            let one = self.alloc()?;
+            self.load_constant(one, Value::Number(1.0));
+            self.emit(if let TokenKind::INCREMENT = op {
+                RegInst::ADD {
+                    dst: reg,
+                    a: reg,
+                    b: one,
+                }
+            } else {
+                RegInst::SUB {
+                    dst: reg,
+                    a: reg,
+                    b: one,
+                }
+            });
+            self.registers.free(one);
+            if is_prefix && reg != dst {
+                self.emit(RegInst::MOVE { dst, src: reg });
+            }
+            return Ok(());
+        }
+
+        let (src, temp) = self.operand(target)?;
+        match op {
+            TokenKind::MINUS => self.emit(RegInst::NEG { dst, src }),
+            TokenKind::BANG => self.emit(RegInst::NOT { dst, src }),
+            _ => return Err(format!("the unary `{op:?}` operator")),
+        }
+        self.free_temp(src, temp);
+        Ok(())
+    }
+
+    fn call(&mut self, target: &Node, args: &[Node], dst: u8) -> RegisterResult {
+        let n = u8::try_from(args.len()).map_err(|_| "a call with over 255 arguments")?;
+        let base = self.alloc_range(n + 1)?;
+
+        match target {
+            Node::Variable(name)
+                if self.resolve(name).is_none() && self.functions.contains_key(name) =>
+            {
+                let k = self.functions[name];
+                self.emit(RegInst::LOAD_K { dst: base, k });
+            }
+            _ => self.expr(target, base)?,
+        }
+        for (i, arg) in args.iter().enumerate() {
+            self.expr(arg, base + 1 + i as u8)?;
+        }
+
+        self.emit(RegInst::CALL { base, args: n });
+        if base != dst {
+            self.emit(RegInst::MOVE { dst, src: base });
+        }
+        self.registers.free_range(base, n + 1);
+        Ok(())
+    }
+
+    /// Compiles the body of a function, branch or loop. With `dst`, the block's last
+    /// expression is its value, like in the stack compiler.
+    fn block(&mut self, node: &Node, dst: Option<u8>) -> RegisterResult {
+        let body = match node {
+            Node::Block { body } => body,
+            Node::SingleLineBlock { body } => {
+                return match dst {
+                    Some(dst) => self.expr(body, dst),
+                    None => self.statement(body),
+                };
+            }
+            _ => {
+                return match dst {
+                    Some(dst) => self.expr(node, dst),
+                    None => self.statement(node),
+                };
+            }
+        };
+
+        self.scopes.push(vec![]);
+        match (body.split_last(), dst) {
+            (Some((Node::ExprStmt(expr, span), statements)), Some(dst))
+                if Compiler::is_block_value(expr) =>
+            {
+                for i in statements {
+                    self.statement(i)?;
+                }
+                self.with_span(span, |this| this.expr(expr, dst))?;
+            }
+            _ => {
+                for i in body {
+                    self.statement(i)?;
+                }
+                if let Some(dst) = dst {
+                    self.emit(RegInst::LOAD_NIL(dst));
+                }
+            }
+        }
+        self.pop_scope();
+
+        Ok(())
+    }
+
+    fn if_statement(
+        &mut self,
+        condition: &Node,
+        block: &Node,
+        elifs: &[(Node, Node)],
+        else_block: &Option<Box<Node>>,
+        dst: Option<u8>,
+    ) -> RegisterResult {
+        let mut end_jumps = vec![];
+
+        for (condition, block) in
+            std::iter::once((condition, block)).chain(elifs.iter().map(|(c, b)| (c, b)))
+        {
+            let jump_if_false = self.condition(condition)?;
+            self.block(block, dst)?;
+            end_jumps.push(self.emit_jump(RegInst::JUMP(0)));
+            self.patch(jump_if_false);
+        }
+
+        match (else_block, dst) {
+            (Some(block), _) => self.block(block, dst)?,
+            (None, Some(dst)) => self.emit(RegInst::LOAD_NIL(dst)),
+            (None, None) => {}
+        }
+
+        for jump in end_jumps {
+            self.patch(jump);
+        }
+        Ok(())
+    }
+
+    fn while_loop(&mut self, condition: Option<&Node>, block: &Node) -> RegisterResult {
+        let start = self.instructions.len();
+        let exit = condition
+            .map(|condition| self.condition(condition))
+            .transpose()?;
+
+        self.loop_body(start, block, exit)
+    }
+
+    fn for_loop(&mut self, pattern: &Pattern, expr: &Node, block: &Node) -> RegisterResult {
+        let name = match pattern {
+            Pattern::Binding(name) => Some(name),
+            Pattern::Wildcard => None,
+            _ => return Err("destructuring in `for`".to_string()),
+        };
+
+        let iter = self.alloc()?;
+        let (src, temp) = self.operand(expr)?;
+        self.emit(RegInst::GET_ITER { dst: iter, src });
+        self.free_temp(src, temp);
+
+        self.scopes.push(vec![]);
+        let item = match name {
+            Some(name) => self.declare(name, false)?,
+            None => self.alloc()?,
+        };
+
+        let start = self.emit_jump(RegInst::FOR_ITER {
+            iter,
+            dst: item,
+            exit: 0,
+        });
+        self.loop_body(start, block, Some(start))?;
+
+        if name.is_none() {
+            self.registers.free(item);
+        }
+        self.pop_scope();
+        self.registers.free(iter);
+        Ok(())
+    }
+
+    /// Compiles a loop's block, which jumps back to `start`. `exit` is the jump that leaves
+    /// the loop once its condition fails.
+    fn loop_body(&mut self, start: usize, block: &Node, exit: Option<usize>) -> RegisterResult {
+        self.loops.push(LoopLabels {
+            start,
+            breaks: vec![],
+        });
+        let result = self.block(block, None);
+        let labels = self.loops.pop().unwrap();
+        result?;
+
+        self.emit(RegInst::JUMP(start));
+        for jump in exit.into_iter().chain(labels.breaks) {
+            self.patch(jump);
+        }
+        Ok(())
+    }
+
+    // Variables
+
+    fn resolve(&self, name: &str) -> Option<&Local> {
+        self.scopes
+            .iter()
+            .rev()
+            .flat_map(|scope| scope.iter().rev())
+            .find(|local| *local.name == name)
+    }
+
+    fn assignable(&self, name: &Rc<String>) -> RegisterResult<u8> {
+        match self.resolve(name) {
+            Some(local) if !local.is_const => Ok(local.reg),
+            Some(_) => Err(format!("assigning to the constant `{name}`")),
+            None => Err(format!("assigning to the global `{name}`")),
+        }
+    }
+
+    fn declare(&mut self, name: &Rc<String>, is_const: bool) -> RegisterResult<u8> {
+        let reg = self.alloc()?;
+        self.declare_in(name, reg, is_const);
+        Ok(reg)
+    }
+
+    fn declare_in(&mut self, name: &Rc<String>, reg: u8, is_const: bool) {
+        if !self.in_function {
+            self.top_level.insert(name.clone());
+        }
+        self.scopes.last_mut().unwrap().push(Local {
+            name: name.clone(),
+            reg,
+            is_const,
+        });
+    }
+
+    fn pop_scope(&mut self) {
+        for local in self.scopes.pop().unwrap().into_iter().rev() {
+            self.registers.free(local.reg);
+        }
+    }
+
+    fn load_global(&mut self, name: &Rc<String>, dst: u8) -> RegisterResult {
+        // The stack VM looks these up at runtime, registers are gone by the time a function runs
+        if self.top_level.contains(name) {
+            return Err(format!("`{name}` used inside a function"));
+        }
+
+        self.emit(RegInst::LOAD_GLOBAL {
+            dst,
+            id: hash_u64!(name.as_str()),
+        });
+        Ok(())
+    }
+
+    // Emitting
+
+    fn alloc(&mut self) -> RegisterResult<u8> {
+        self.registers.alloc().ok_or_else(Self::out_of_registers)
+    }
+
+    fn alloc_range(&mut self, n: u8) -> RegisterResult<u8> {
+        self.registers
+            .alloc_range(n)
+            .ok_or_else(Self::out_of_registers)
+    }
+
+    fn out_of_registers() -> String {
+        "a function needing over 255 registers".to_string()
+    }
+
+    fn constant(&mut self, value: Value) -> usize {
+        if let Some(k) = self.constants.iter().position(|x| *x == value) {
+            return k;
+        }
+        self.constants.push(value);
+        self.constants.len() - 1
+    }
+
+    fn load_constant(&mut self, dst: u8, value: Value) {
+        let k = self.constant(value);
+        self.emit(RegInst::LOAD_K { dst, k });
+    }
+
+    fn emit(&mut self, inst: RegInst) {
+        if let Some(span) = self.current_span {
+            LineInfo::push(
+                &mut self.lines,
+                LineInfo {
+                    start: self.instructions.len(),
+                    ..span
+                },
+            );
+        }
+        self.instructions.push(inst);
+    }
+
+    /// Emits a jump whose target `patch` fills in later, returning where it is.
+    fn emit_jump(&mut self, inst: RegInst) -> usize {
+        self.emit(inst);
+        self.instructions.len() - 1
+    }
+
+    /// Points the jump at `idx` to the next instruction.
+    fn patch(&mut self, idx: usize) {
+        let next = self.instructions.len();
+        match &mut self.instructions[idx] {
+            RegInst::JUMP(target)
+            | RegInst::JUMP_IF_FALSE { target, .. }
+            | RegInst::JUMP_IF_NOT_NIL { target, .. }
+            | RegInst::FOR_ITER { exit: target, .. } => *target = next,
+            inst => unreachable!("Can't patch {inst:?}"),
+        }
+    }
+
+    /// Compiles with `span` as the source position of what `f` emits.
+    fn with_span(
+        &mut self,
+        span: &TokenRange,
+        f: impl FnOnce(&mut Self) -> RegisterResult,
+    ) -> RegisterResult {
+        let parent = self.current_span;
+        let (line, col) = line_col(&self.line_starts, span.start);
+        self.current_span = Some(LineInfo {
+            start: 0, // filled in by `emit`
+            file: self.file,
+            line,
+            col,
+            len: span.end.saturating_sub(span.start).max(1),
+        });
+
+        let result = f(self);
+        self.current_span = parent;
+        result
+    }
+
+    /// Names an unsupported node by its variant, like `ClassDef`.
+    fn describe(node: &Node) -> String {
+        let debug = format!("{node:?}");
+        let name = debug
+            .split(|c: char| !c.is_alphanumeric())
+            .next()
+            .unwrap_or_default();
+        format!("`{name}`")
+    }
+}
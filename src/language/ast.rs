@@ -1,4 +1,7 @@
-use crate::language::{nodes::Node, token::TokenKind};
+use crate::language::{
+    nodes::{EnumItem, Node},
+    token::TokenKind,
+};
 
 pub struct AST {
     pub nodes: Vec<Node>,
@@ -18,6 +21,8 @@ impl AST {
             true
         } else if let Node::ContinueStatement = node {
             true
+        } else if let Node::ThrowStatement(_) = node {
+            true
         } else {
             false
         }
@@ -32,7 +37,7 @@ impl AST {
 
     pub fn prune_node(node: &mut Node) {
         match node {
-            Node::ExprStmt(node) => {
+            Node::ExprStmt(node, _) => {
                 Self::prune_node(node);
             }
             Node::Block { body } => Self::prune_block(body),
@@ -67,11 +72,17 @@ impl AST {
                     }
                 }
             }
+            Node::LetPattern { value, .. } => Self::prune_node(value),
             Node::ReturnStatement(value) => {
                 if let Some(v) = value {
                     Self::prune_node(v);
                 }
             }
+            Node::YieldStatement(value) => {
+                if let Some(v) = value {
+                    Self::prune_node(v);
+                }
+            }
             Node::OutStatement(value) => {
                 if let Some(v) = value {
                     Self::prune_node(v);
@@ -85,6 +96,13 @@ impl AST {
             Node::FunctionDefinition { block, .. } => {
                 Self::prune_node(block);
             }
+            Node::TryCatch {
+                block, catch_block, ..
+            } => {
+                Self::prune_node(block);
+                Self::prune_node(catch_block);
+            }
+            Node::ThrowStatement(value) => Self::prune_node(value),
             Node::Loop { block, .. } => {
                 Self::prune_node(block);
             }
@@ -130,14 +148,14 @@ impl AST {
                     Self::prune_node(v);
                 }
             }
-            Node::FunctionCall { target, args } => {
+            Node::FunctionCall { target, args, .. } => {
                 Self::prune_node(target);
 
                 for val in args {
                     Self::prune_node(val);
                 }
             }
-            Node::MemberAccess { expr, member } => {
+            Node::MemberAccess { expr, member, .. } => {
                 Self::prune_node(expr);
                 Self::prune_node(member);
             }
@@ -150,8 +168,10 @@ impl AST {
                 Self::prune_node(value);
             }
             Node::EnumDef { items, .. } => {
-                for (_, value) in items.iter_mut() {
-                    Self::prune_node(value);
+                for (_, item) in items.iter_mut() {
+                    if let EnumItem::Value(value) = item {
+                        Self::prune_node(value);
+                    }
                 }
             }
 
@@ -162,7 +182,7 @@ impl AST {
     pub fn prune_block(body: &mut Vec<Node>) {
         for (idx, i) in body.iter_mut().enumerate() {
             Self::prune_node(i);
-            if let Node::ExprStmt(i) = i {
+            if let Node::ExprStmt(i, _) = i {
                 if Self::is_terminator(&*i) {
                     body.truncate(idx + 1);
                     return;
@@ -176,9 +196,14 @@ impl AST {
 
     pub fn fold_constants(node: Node) -> Node {
         match node {
-            Node::ExprStmt(n) => Node::ExprStmt(Box::new(Self::fold_constants(*n))),
+            Node::ExprStmt(n, span) => Node::ExprStmt(Box::new(Self::fold_constants(*n)), span),
 
-            Node::BinOp { left, right, op } => {
+            Node::BinOp {
+                left,
+                right,
+                op,
+                span,
+            } => {
                 // Desugar chained comparison: (a < b) < c  →  (a < b) && (b < c)
                 if matches!(
                     op,
@@ -188,6 +213,7 @@ impl AST {
                         left: ll,
                         right: lr,
                         op: inner_op,
+                        span: inner_span,
                     } = &*left
                     {
                         if matches!(
@@ -199,13 +225,16 @@ impl AST {
                                     left: ll.clone(),
                                     right: lr.clone(),
                                     op: inner_op.clone(),
+                                    span: *inner_span,
                                 }),
                                 right: Box::new(Node::BinOp {
                                     left: lr.clone(), // middle value
                                     right,
                                     op,
+                                    span,
                                 }),
                                 op: TokenKind::AND,
+                                span,
                             };
                             return Self::fold_constants(and_node); // re-fold the AND
                         }
@@ -241,6 +270,7 @@ impl AST {
                             left: Box::new(folded_left),
                             right: Box::new(folded_right),
                             op,
+                            span,
                         },
                     },
 
@@ -255,6 +285,7 @@ impl AST {
                             left: Box::new(folded_left),
                             right: Box::new(folded_right),
                             op,
+                            span,
                         },
                     },
 
@@ -267,6 +298,7 @@ impl AST {
                             left: Box::new(folded_left),
                             right: Box::new(folded_right),
                             op,
+                            span,
                         },
                     },
 
@@ -278,6 +310,7 @@ impl AST {
                             left: Box::new(folded_left),
                             right: Box::new(folded_right),
                             op,
+                            span,
                         },
                     },
 
@@ -289,6 +322,7 @@ impl AST {
                             left: Box::new(folded_left),
                             right: Box::new(folded_right),
                             op,
+                            span,
                         },
                     },
 
@@ -300,6 +334,7 @@ impl AST {
                             left: Box::new(folded_left),
                             right: Box::new(folded_right),
                             op,
+                            span,
                         },
                     },
 
@@ -307,6 +342,7 @@ impl AST {
                         left: Box::new(folded_left),
                         right: Box::new(folded_right),
                         op,
+                        span,
                     },
                 }
             }
@@ -315,6 +351,7 @@ impl AST {
                 op,
                 right,
                 is_prefix,
+                span,
             } => {
                 let folded_right = Self::fold_constants(*right);
 
@@ -328,6 +365,7 @@ impl AST {
                             op,
                             right: Box::new(Node::NumberLiteral(x)),
                             is_prefix,
+                            span,
                         },
                     },
                     Node::BooleanLiteral(x) => match op {
@@ -337,6 +375,7 @@ impl AST {
                             op,
                             right: Box::new(Node::BooleanLiteral(x)),
                             is_prefix,
+                            span,
                         },
                     },
                     Node::NIL => match op {
@@ -346,6 +385,7 @@ impl AST {
                             op,
                             right: Box::new(Node::NIL),
                             is_prefix,
+                            span,
                         },
                     },
 
@@ -353,6 +393,7 @@ impl AST {
                         op,
                         right: Box::new(folded_right),
                         is_prefix,
+                        span,
                     },
                 }
             }
@@ -389,9 +430,10 @@ impl AST {
                 inclusive,
             },
 
-            Node::MemberAccess { expr, member } => Node::MemberAccess {
+            Node::MemberAccess { expr, member, span } => Node::MemberAccess {
                 expr: Box::new(Self::fold_constants(*expr)),
                 member: Box::new(Self::fold_constants(*member)),
+                span,
             },
 
             Node::LetStatement {
@@ -406,6 +448,15 @@ impl AST {
                     .collect(),
                 is_const,
             },
+            Node::LetPattern {
+                pattern,
+                value,
+                is_const,
+            } => Node::LetPattern {
+                pattern,
+                value: Box::new(Self::fold_constants(*value)),
+                is_const,
+            },
             Node::SetVariable { target, value } => Node::SetVariable {
                 target: Box::new(Self::fold_constants(*target)),
                 value: Box::new(Self::fold_constants(*value)),
@@ -433,20 +484,37 @@ impl AST {
 				is_const,
                 block: Box::new(Self::fold_constants(*block)),
             },
-            Node::FunctionCall { target, args } => Node::FunctionCall {
+            Node::FunctionCall { target, args, span } => Node::FunctionCall {
                 target: Box::new(Self::fold_constants(*target)),
                 args: args.into_iter().map(Self::fold_constants).collect(),
+                span,
             },
 
             Node::ReturnStatement(val) => {
                 Node::ReturnStatement(val.map(|v| Box::new(Self::fold_constants(*v))))
             }
+            Node::YieldStatement(val) => {
+                Node::YieldStatement(val.map(|v| Box::new(Self::fold_constants(*v))))
+            }
             Node::OutStatement(val) => {
                 Node::OutStatement(val.map(|v| Box::new(Self::fold_constants(*v))))
             }
             Node::BreakStatement(val) => {
                 Node::BreakStatement(val.map(|v| Box::new(Self::fold_constants(*v))))
             }
+            Node::ThrowStatement(val) => {
+                Node::ThrowStatement(Box::new(Self::fold_constants(*val)))
+            }
+
+            Node::TryCatch {
+                block,
+                error_name,
+                catch_block,
+            } => Node::TryCatch {
+                block: Box::new(Self::fold_constants(*block)),
+                error_name,
+                catch_block: Box::new(Self::fold_constants(*catch_block)),
+            },
 
             Node::IfStatement {
                 condition,
@@ -471,11 +539,11 @@ impl AST {
                 block: Box::new(Self::fold_constants(*block)),
             },
             Node::ForLoop {
-                var_name,
+                pattern,
                 expr,
                 block,
             } => Node::ForLoop {
-                var_name,
+                pattern,
                 expr: Box::new(Self::fold_constants(*expr)),
                 block: Box::new(Self::fold_constants(*block)),
             },
@@ -484,7 +552,13 @@ impl AST {
                 expr: Box::new(Self::fold_constants(*expr)),
                 branches: branches
                     .into_iter()
-                    .map(|(p, b)| (Self::fold_constants(p), Self::fold_constants(b)))
+                    .map(|(p, guard, b)| {
+                        (
+                            p,
+                            guard.map(Self::fold_constants),
+                            Self::fold_constants(b),
+                        )
+                    })
                     .collect(),
             },
 
@@ -492,7 +566,10 @@ impl AST {
                 name,
                 items: items
                     .into_iter()
-                    .map(|(k, v)| (k, Self::fold_constants(v)))
+                    .map(|(k, item)| match item {
+                        EnumItem::Value(v) => (k, EnumItem::Value(Self::fold_constants(v))),
+                        variant => (k, variant),
+                    })
                     .collect(),
             },
 
@@ -0,0 +1,82 @@
+use crate::{
+    language::token::{TokenKind, TokenRange},
+    misc::source::{Location, line_col},
+};
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found while parsing, pointing at the tokens involved.
+#[allow(unused)]
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: TokenRange,
+    /// Token kinds that would have been accepted here, if the parser knew
+    pub expected: Vec<TokenKind>,
+    /// The token that was there instead (`None` at the end of input)
+    pub found: Option<TokenKind>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: TokenRange, found: Option<TokenKind>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            span,
+            expected: vec![],
+            found,
+        }
+    }
+
+    pub fn expected(expected: TokenKind, span: TokenRange, found: Option<TokenKind>) -> Self {
+        let message = match &found {
+            Some(found) => format!("Expected `{expected:?}`, found `{found:?}`"),
+            None => format!("Expected `{expected:?}`, found [EOF]"),
+        };
+
+        Self {
+            expected: vec![expected],
+            ..Self::error(message, span, found)
+        }
+    }
+
+    pub fn into_warning(self) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..self
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn location(&self, file: &str, line_starts: &[usize]) -> Location {
+        let (line, col) = line_col(line_starts, self.span.start);
+
+        Location {
+            file: Rc::from(file),
+            line,
+            col,
+            len: self.span.end.saturating_sub(self.span.start).max(1),
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "SyntaxError: {}", self.message),
+            Severity::Warning => write!(f, "Warning: {}", self.message),
+        }
+    }
+}
@@ -7,9 +7,9 @@ use crate::language::token::{
 };
 
 const PUNCTUATION: &str = "!@#$%^&*()-+[]{}|:;,./<>?=\n";
-const DOUBLE: [&str; 21] = [
+const DOUBLE: [&str; 22] = [
     "->", "||", "&&", "<=", ">=", "==", "!=", "=>", "::", "..", "++", "--", "+=", "-=", "*=", "/=",
-    "%=", "^=", "//", "??", "?:",
+    "%=", "^=", "//", "??", "?:", "|>",
 ];
 
 #[derive(Debug)]
@@ -22,7 +22,7 @@ pub struct Lexer {
 impl Lexer {
     pub fn new(text: &str) -> Self {
         let mut new_lexer = Self {
-            chars: text.replace("\r", "\n").trim().chars().collect::<Vec<_>>(),
+            chars: text.replace("\r", "\n").trim_end().chars().collect::<Vec<_>>(),
             pos: -1,
             cur_char: None,
         };
@@ -160,6 +160,7 @@ impl Lexer {
             "const" => CONST,
             "fn" => FN,
             "return" => RETURN,
+            "yield" => YIELD,
             "for" => FOR,
             "in" => IN,
             "break" => BREAK,
@@ -175,7 +176,11 @@ impl Lexer {
             "as" => AS,
             "enum" => ENUM,
             "struct" => STRUCT,
-            // "interface" => INTERFACE,
+            "try" => TRY,
+            "catch" => CATCH,
+            "throw" => THROW,
+            "interface" => INTERFACE,
+            "impl" => IMPL,
 
             // Punctuation
             "(" => LPAREN, // Parenthesis ()
@@ -214,6 +219,7 @@ impl Lexer {
             "~" => TILDA,
             "`" => BACKTICK,
             "|" => PIPE,
+            "|>" => PIPELINE,
             "." => DOT,
             ".." => DOUBLEDOT,
             "," => COMMA,
@@ -1,4 +1,5 @@
 pub mod ast;
+pub mod diagnostic;
 pub mod lexer;
 pub mod nodes;
 pub mod parser;
@@ -1,12 +1,12 @@
 use std::rc::Rc;
 
-use crate::language::token::TokenKind;
+use crate::language::token::{TokenKind, TokenRange};
 
 #[allow(unused)]
 #[derive(Debug, Clone)]
 pub enum Node {
     // Expressions vs Statements
-    ExprStmt(Box<Node>),
+    ExprStmt(Box<Node>, TokenRange),
 
     // LITERALS
     NIL,
@@ -35,11 +35,13 @@ pub enum Node {
         left: Box<Node>,
         right: Box<Node>,
         op: TokenKind,
+        span: TokenRange,
     },
     UnaryOp {
         op: TokenKind,
         right: Box<Node>,
         is_prefix: bool,
+        span: TokenRange,
     },
     ComparisonChain {
         expressions: Vec<Node>,
@@ -65,6 +67,7 @@ pub enum Node {
     MemberAccess {
         expr: Box<Node>,
         member: Box<Node>,
+        span: TokenRange,
     },
 
     // STATEMENTS
@@ -73,10 +76,20 @@ pub enum Node {
         values: Vec<Option<Box<Node>>>,
         is_const: bool,
     },
+    // `let (a, b) = value`, binding the parts of the value that `pattern` picks out
+    LetPattern {
+        pattern: Box<Pattern>,
+        value: Box<Node>,
+        is_const: bool,
+    },
+    // `file` is set for `using "path/to/file.ign"`, the sequence then walks into that module
     UsingStatement {
+        file: Option<String>,
         sequence: Vec<String>,
         imports: Vec<(String, Option<String>)>,
         wildcard: bool,
+        alias: Option<String>,
+        span: TokenRange,
     },
 
     SetVariable {
@@ -108,12 +121,22 @@ pub enum Node {
     FunctionCall {
         target: Box<Node>,
         args: Vec<Node>,
+        span: TokenRange,
     },
 
     ReturnStatement(Option<Box<Node>>),
+    YieldStatement(Option<Box<Node>>),
     BreakStatement(Option<Box<Node>>),
     OutStatement(Option<Box<Node>>),
     ContinueStatement,
+    ThrowStatement(Box<Node>),
+
+    // Error handling
+    TryCatch {
+        block: Box<Node>,
+        error_name: Option<Rc<String>>,
+        catch_block: Box<Node>,
+    },
 
     // Loops
     Loop {
@@ -124,7 +147,7 @@ pub enum Node {
         block: Box<Node>,
     },
     ForLoop {
-        var_name: Rc<String>,
+        pattern: Box<Pattern>,
         expr: Box<Node>,
         block: Box<Node>,
     },
@@ -143,6 +166,8 @@ pub enum Node {
         interfaces: Vec<Rc<String>>,
         let_statements: Vec<Node>,
         functions: Vec<Node>,
+        /// The `class Name : Interface` header
+        span: TokenRange,
     },
 
     StructDef {
@@ -155,6 +180,12 @@ pub enum Node {
         fields: Vec<(String, Node)>,
     },
 
+    // Functions taking `self` become methods of the struct, the others are static
+    ImplBlock {
+        name: Rc<String>,
+        functions: Vec<Node>,
+    },
+
     InterfaceDef {
         name: Rc<String>,
         let_statements: Vec<Node>,
@@ -163,11 +194,56 @@ pub enum Node {
 
     EnumDef {
         name: String,
-        items: Vec<(String, Node)>,
+        items: Vec<(String, EnumItem)>,
     },
 
+    // (pattern, guard, value)
     MatchStatement {
         expr: Box<Node>,
-        branches: Vec<(Node, Node)>,
+        branches: Vec<(Pattern, Option<Node>, Node)>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum EnumItem {
+    Value(Node),
+
+    // `Circle(r)`, or `Rect { w, h }` if `is_struct`. An item without fields in an enum
+    // that has these is a variant too, not a numbered value.
+    Variant {
+        fields: Vec<String>,
+        is_struct: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Wildcard,
+    Binding(Rc<String>),
+    Value(Node),
+    Range {
+        start: Node,
+        end: Node,
+        inclusive: bool,
+    },
+    Or(Vec<Pattern>),
+
+    // `rest` is the position of `..` among the items and the name it binds
+    Sequence {
+        is_tuple: bool,
+        items: Vec<Pattern>,
+        rest: Option<(usize, Option<Rc<String>>)>,
+    },
+    // `name` is `None` for `{ name, age }`, which only destructures in `let` and `for`
+    Struct {
+        name: Option<Rc<String>>,
+        fields: Vec<(String, Pattern)>,
+    },
+
+    // `Shape.Circle(r)` or `Shape.Rect { w, h }`, where `target` evaluates to the variant
+    Variant {
+        target: Node,
+        items: Vec<Pattern>,
+        fields: Vec<(String, Pattern)>,
     },
 }
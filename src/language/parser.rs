@@ -1,24 +1,36 @@
 #![allow(unused)]
 
-use std::{collections::HashMap, thread::current};
+use std::{collections::HashMap, rc::Rc, thread::current};
 
 use crate::{
     language::{
         lexer::Lexer,
-        nodes::Node,
-        token::{Token, TokenKind},
+        nodes::{EnumItem, Node, Pattern},
+        diagnostic::Diagnostic,
+        token::{Token, TokenKind, TokenRange},
     },
     rc,
 };
 
-type TokenResult = Result<Token, String>;
-type NodeResult = Result<Node, String>;
+type TokenResult = Result<Token, Diagnostic>;
+type NodeResult = Result<Node, Diagnostic>;
+type PatternResult = Result<Pattern, Diagnostic>;
+// (name, args, return type)
+type SignatureResult = Result<
+    (
+        Option<Rc<String>>,
+        Vec<(Rc<String>, Option<Rc<String>>, Option<Node>)>,
+        Option<Rc<String>>,
+    ),
+    Diagnostic,
+>;
 
 #[derive(Debug, Clone)]
 pub struct Parser {
     source: String,
     tokens: Vec<Token>,
     pos: i32,
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Parser {
@@ -27,9 +39,25 @@ impl Parser {
             source,
             tokens,
             pos: 0,
+            diagnostics: vec![],
         }
     }
 
+    /// An error pointing at the current token.
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(
+            message,
+            self.current_range().unwrap_or(TokenRange { start: 0, end: 0 }),
+            self.current_kind(),
+        )
+    }
+
+    fn current_kind(&self) -> Option<TokenKind> {
+        self.tokens
+            .get(self.pos.max(0) as usize)
+            .map(|token| token.kind.clone())
+    }
+
     fn advance(&mut self) -> TokenResult {
         let current = self.current();
         self.pos += 1;
@@ -42,7 +70,7 @@ impl Parser {
         if self.pos < self.tokens.len() as i32 {
             Ok(self.tokens[self.pos as usize].clone())
         } else {
-            Err("Expected more tokens. Got [EOF]. (Current)".to_string())
+            Err(self.error("Unexpected end of input [EOF]."))
         }
     }
 
@@ -54,20 +82,29 @@ impl Parser {
         }
     }
 
-    fn expect_and_consume(&mut self, kind: TokenKind) -> Result<Token, String> {
+    fn expect_and_consume(&mut self, kind: TokenKind) -> TokenResult {
         self.skip_new_lines();
 
-        if let Ok(next) = self.current() {
-            if next.kind != kind {
-                Err(format!("Expected `{kind:?}`, got `{:?}`", next.kind))
-            } else {
-                self.advance()?;
-                self.skip_new_lines();
+        if let Ok(next) = self.current()
+            && next.kind == kind
+        {
+            self.advance()?;
+            self.skip_new_lines();
 
-                Ok(next)
-            }
+            Ok(next)
         } else {
-            Err(format!("Expected `{kind:?}`, got [EOF]."))
+            Err(Diagnostic::expected(
+                kind,
+                self.current_range().unwrap_or(TokenRange { start: 0, end: 0 }),
+                self.current_kind(),
+            ))
+        }
+    }
+
+    /// Like `expect_and_consume`, but a missing token is only warned about and parsing carries on.
+    fn expect_or_warn(&mut self, kind: TokenKind) {
+        if let Err(e) = self.expect_and_consume(kind) {
+            self.diagnostics.push(e.into_warning());
         }
     }
 
@@ -76,8 +113,8 @@ impl Parser {
         left: TokenKind,
         right: TokenKind,
         separator: Option<TokenKind>,
-        mut f: impl FnMut(&mut Self) -> Result<(), String>,
-    ) -> Result<(), String> {
+        mut f: impl FnMut(&mut Self) -> Result<(), Diagnostic>,
+    ) -> Result<(), Diagnostic> {
         self.expect_and_consume(left)?;
 
         loop {
@@ -106,23 +143,107 @@ impl Parser {
         Ok(())
     }
 
+    /// Parses every statement, collecting syntax errors in `diagnostics` instead of stopping at the first.
+    pub fn parse_program(&mut self) -> Vec<Node> {
+        let mut nodes = vec![];
+
+        loop {
+            self.skip_new_lines();
+            if self.current().is_err() {
+                break;
+            }
+
+            match self.parse() {
+                Ok(node) => nodes.push(node),
+                Err(e) => {
+                    self.diagnostics.push(e);
+                    self.synchronize();
+
+                    // A stray `}` has nothing left to close at the top level
+                    if let Ok(next) = self.current()
+                        && next.kind == TokenKind::RBRACE
+                    {
+                        self.pos += 1;
+                    }
+                }
+            }
+        }
+
+        nodes
+    }
+
+    /// Skips to the end of the statement an error happened in: the next newline, or the `}`
+    /// closing the enclosing block (which is left for the block to consume).
+    fn synchronize(&mut self) {
+        let start = self.pos;
+        let mut depth = 0;
+
+        while let Some(token) = self.tokens.get(self.pos as usize) {
+            match token.kind {
+                TokenKind::LBRACE | TokenKind::LPAREN | TokenKind::LBRACK => depth += 1,
+                TokenKind::RPAREN | TokenKind::RBRACK => depth = (depth - 1).max(0),
+                TokenKind::RBRACE if depth == 0 => break,
+                TokenKind::RBRACE => depth -= 1,
+                TokenKind::NEWLINE if depth == 0 => break,
+                _ => {}
+            }
+
+            self.pos += 1;
+        }
+
+        // Always make progress, even if the error was reported on the stopping token
+        if self.pos == start
+            && let Some(token) = self.tokens.get(self.pos as usize)
+            && token.kind != TokenKind::RBRACE
+        {
+            self.pos += 1;
+        }
+    }
+
     pub fn parse(&mut self) -> NodeResult {
         self.skip_new_lines();
 
         match self.current()?.kind {
             TokenKind::CLASS => self.parse_class_def(),
+            TokenKind::INTERFACE => self.parse_interface_def(),
+            TokenKind::IMPL => self.parse_impl_block(),
             TokenKind::FN => self.parse_function_def(false, false),
             TokenKind::USING => self.parse_using(),
             TokenKind::ENUM => self.parse_enum(),
             TokenKind::STRUCT => self.parse_struct_def(),
 
             _ => {
+                let start = self.current()?.range.start;
                 let expr = self.parse_expression()?;
 
-                Ok(Node::ExprStmt(Box::new(expr)))
+                Ok(Node::ExprStmt(Box::new(expr), self.span_from(start)))
             }
         }
     }
+
+    /// Range from `start` to the end of the last token consumed, ignoring newlines.
+    fn span_from(&self, start: usize) -> TokenRange {
+        let end = self.tokens[..(self.pos as usize).min(self.tokens.len())]
+            .iter()
+            .rev()
+            .find(|token| token.kind != TokenKind::NEWLINE)
+            .map_or(start, |token| token.range.end);
+
+        TokenRange {
+            start,
+            end: end.max(start),
+        }
+    }
+
+    /// Range of the token the parser stopped at, or of the last token at [EOF].
+    pub fn current_range(&self) -> Option<TokenRange> {
+        let pos = (self.pos.max(0) as usize).min(self.tokens.len());
+
+        self.tokens
+            .get(pos)
+            .or_else(|| self.tokens.last())
+            .map(|token| token.range)
+    }
 }
 
 // EXPRESSIONS
@@ -160,13 +281,14 @@ impl Parser {
             }
 
             self.skip_new_lines();
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
             let right = self.parse_mul_div()?;
 
             left = Node::BinOp {
                 left: Box::new(left),
                 right: Box::new(right),
-                op,
+                op: op.kind,
+                span: op.range,
             };
         }
 
@@ -185,13 +307,14 @@ impl Parser {
             }
 
             self.skip_new_lines();
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
             let right = self.parse_unary()?;
 
             left = Node::BinOp {
                 left: Box::new(left),
                 right: Box::new(right),
-                op,
+                op: op.kind,
+                span: op.range,
             };
         }
 
@@ -211,12 +334,13 @@ impl Parser {
                     | TokenKind::DECREMENT
             )
         {
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
 
             return Ok(Node::UnaryOp {
-                op,
+                op: op.kind,
                 right: Box::new(self.parse_fstring()?),
                 is_prefix: true,
+                span: op.range,
             });
         }
 
@@ -225,12 +349,13 @@ impl Parser {
         if let Ok(next) = self.current()
             && matches!(next.kind, TokenKind::INCREMENT | TokenKind::DECREMENT)
         {
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
 
             return Ok(Node::UnaryOp {
-                op,
+                op: op.kind,
                 right: Box::new(expr),
                 is_prefix: false,
+                span: op.range,
             });
         }
 
@@ -302,7 +427,7 @@ impl Parser {
                 }
 
                 if depth != 0 {
-                    return Err("Unclosed `{` in f-string interpolation.".to_string());
+                    return Err(self.error("Unclosed `{` in f-string interpolation."));
                 }
 
                 // Flush any trailing string literal after the last `}`
@@ -312,7 +437,7 @@ impl Parser {
 
                 return Ok(Node::FString(values));
             } else {
-                panic!("`$` can only be applied to string literals")
+                Err(self.error("`$` can only be applied to string literals"))
             }
         } else {
             self.parse_exponent()
@@ -328,13 +453,14 @@ impl Parser {
             }
 
             self.skip_new_lines();
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
             let right = self.parse_primary()?;
 
             left = Node::BinOp {
                 left: Box::new(left),
                 right: Box::new(right),
-                op,
+                op: op.kind,
+                span: op.range,
             };
         }
 
@@ -370,22 +496,25 @@ impl Parser {
             TokenKind::WHILE => self.parse_while(),
             TokenKind::FOR => self.parse_for(),
             TokenKind::RETURN => self.parse_return(),
+            TokenKind::YIELD => self.parse_yield(),
             TokenKind::OUT => self.parse_out(),
             TokenKind::BREAK => self.parse_break(),
             TokenKind::CONTINUE => self.simple_parse_keyword(Node::ContinueStatement),
             TokenKind::IF => self.parse_if(),
             TokenKind::MATCH => self.parse_match(),
+            TokenKind::TRY => self.parse_try(),
+            TokenKind::THROW => self.parse_throw(),
 
-            other => Err(format!(
+            other => Err(self.error(format!(
                 "Got unexpected token `{other:?}` while parsing primary."
-            )),
+            ))),
         }?;
 
         if self.pos == start_pos {
             self.advance()?;
         }
 
-        self.parse_postfix(node)
+        self.parse_postfix(node, current.range.start)
     }
 
     fn skip_new_lines(&mut self) {
@@ -405,7 +534,7 @@ impl Parser {
         let first = if self.current().is_ok() {
             self.parse_expression()
         } else {
-            return Err("Unexpected end of input inside parentheses".to_string());
+            return Err(self.error("Unexpected end of input inside parentheses"));
         };
 
         if let Ok(x) = self.current()
@@ -458,9 +587,9 @@ impl Parser {
                     break;
                 }
             } else {
-                return Err(format!(
+                return Err(self.error(format!(
                     "Unexpected end of input [EOF] while parsing list. Expected `]`."
-                ));
+                )));
             }
 
             values.push(self.parse_expression()?);
@@ -493,9 +622,9 @@ impl Parser {
                 if let Node::Variable(ref x) = key_base {
                     (Node::StringLiteral(x.to_string()), key_base)
                 } else {
-                    return Err(format!(
+                    return Err(this.error(format!(
                         "Can only use variables in dict field init shorthand."
-                    ));
+                    )));
                 }
             } else if let Ok(next) = this.current()
                 && next.kind == TokenKind::EQUAL
@@ -504,9 +633,9 @@ impl Parser {
                 if let Node::Variable(x) = key_base {
                     (Node::StringLiteral(x.to_string()), this.parse_expression()?)
                 } else {
-                    return Err(format!(
+                    return Err(this.error(format!(
                         "Expected identifier when parsing dict key. Have you tried using a colon (:)?"
-                    ));
+                    )));
                 }
             } else {
                 this.expect_and_consume(TokenKind::COLON)?;
@@ -549,18 +678,18 @@ impl Parser {
                 if let Node::Variable(ref x) = key_base {
                     (x.to_string(), key_base)
                 } else {
-                    return Err(format!(
+                    return Err(this.error(format!(
                         "Can only use variables in struct field field init shorthand."
-                    ));
+                    )));
                 }
             } else {
 				this.advance();
                 if let Node::Variable(x) = key_base {
                     (x.to_string(), this.parse_expression()?)
                 } else {
-                    return Err(format!(
+                    return Err(this.error(format!(
                         "Expected identifier when parsing struct field key. Have you tried using a colon (:)?"
-                    ));
+                    )));
                 }
             };
 
@@ -578,7 +707,7 @@ impl Parser {
     fn parse_expression(&mut self) -> NodeResult {
         self.skip_new_lines();
 
-        let expr = self.parse_ternary_op()?;
+        let expr = self.parse_pipeline()?;
 
         self.skip_new_lines();
 
@@ -603,24 +732,25 @@ impl Parser {
         Ok(expr)
     }
 
-    fn parse_postfix(&mut self, mut expr: Node) -> NodeResult {
+    /// `start` is where `expr` begins in the source, so calls and indexing can span the whole chain.
+    fn parse_postfix(&mut self, mut expr: Node, start: usize) -> NodeResult {
         loop {
             if let Ok(x) = self.current() {
                 match x.kind {
                     TokenKind::LPAREN => {
-                        expr = self.parse_function_call(expr)?;
+                        expr = self.parse_function_call(expr, start)?;
                         continue;
                     }
                     TokenKind::DOT => {
-                        expr = self.parse_member_access(expr)?;
+                        expr = self.parse_member_access(expr, start)?;
                         continue;
                     }
                     TokenKind::DOUBLECOLON => {
-                        expr = self.parse_member_access(expr)?;
+                        expr = self.parse_member_access(expr, start)?;
                         continue;
                     }
                     TokenKind::LBRACK => {
-                        expr = self.parse_member_access(expr)?;
+                        expr = self.parse_member_access(expr, start)?;
                         continue;
                     }
 
@@ -634,6 +764,42 @@ impl Parser {
         return Ok(expr);
     }
 
+    /// `value |> f(args)` calls `f(value, args)` and `value |> f` calls `f(value)`. It binds
+    /// looser than every other operator, so a chain reads left to right.
+    fn parse_pipeline(&mut self) -> NodeResult {
+        let start = self.current()?.range.start;
+        let mut value = self.parse_ternary_op()?;
+
+        loop {
+            self.skip_new_lines();
+
+            match self.current() {
+                Ok(next) if next.kind == TokenKind::PIPELINE => self.advance()?,
+                _ => break,
+            };
+            self.skip_new_lines();
+
+            let target = self.parse_ternary_op()?;
+            let span = self.span_from(start);
+
+            value = match target {
+                Node::FunctionCall {
+                    target, mut args, ..
+                } => {
+                    args.insert(0, value);
+                    Node::FunctionCall { target, args, span }
+                }
+                target => Node::FunctionCall {
+                    target: Box::new(target),
+                    args: vec![value],
+                    span,
+                },
+            };
+        }
+
+        Ok(value)
+    }
+
     fn parse_ternary_op(&mut self) -> NodeResult {
         let mut condition = self.parse_elvis_coalescing()?;
 
@@ -716,13 +882,14 @@ impl Parser {
                 break;
             }
 
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
             let right = self.parse_and()?;
 
             left = Node::BinOp {
                 left: Box::new(left),
                 right: Box::new(right),
-                op,
+                op: op.kind,
+                span: op.range,
             };
         }
 
@@ -737,13 +904,14 @@ impl Parser {
                 break;
             }
 
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
             let right = self.parse_equality()?;
 
             left = Node::BinOp {
                 left: Box::new(left),
                 right: Box::new(right),
-                op,
+                op: op.kind,
+                span: op.range,
             };
         }
 
@@ -758,13 +926,14 @@ impl Parser {
                 break;
             }
 
-            let op = self.advance()?.kind;
+            let op = self.advance()?;
             let right = self.parse_comparison()?;
 
             left = Node::BinOp {
                 left: Box::new(left),
                 right: Box::new(right),
-                op,
+                op: op.kind,
+                span: op.range,
             };
         }
 
@@ -799,7 +968,7 @@ impl Parser {
         })
     }
 
-    fn parse_function_call(&mut self, expr: Node) -> NodeResult {
+    fn parse_function_call(&mut self, expr: Node, start: usize) -> NodeResult {
         self.advance()?; // consume LPAREN
 
         let mut args = vec![];
@@ -811,9 +980,9 @@ impl Parser {
                 Ok(x) => x,
                 Err(_) => {
                     return {
-                        Err(format!(
+                        Err(self.error(format!(
                             "Unexpected end of input in function call. Expected `)`."
-                        ))
+                        )))
                     };
                 }
             };
@@ -834,15 +1003,16 @@ impl Parser {
             }
         }
 
-        self.expect_and_consume(TokenKind::RPAREN)?;
+        let end = self.expect_and_consume(TokenKind::RPAREN)?.range.end;
 
         Ok(Node::FunctionCall {
             target: Box::new(expr),
             args,
+            span: TokenRange { start, end },
         })
     }
 
-    fn parse_member_access(&mut self, expr: Node) -> NodeResult {
+    fn parse_member_access(&mut self, expr: Node, start: usize) -> NodeResult {
         let x = self.advance()?;
 
         if x.kind == TokenKind::DOT || x.kind == TokenKind::DOUBLECOLON {
@@ -853,18 +1023,23 @@ impl Parser {
                 member: Box::new(Node::StringLiteral(
                     member.get_text(&self.source).to_string(),
                 )),
+                span: TokenRange {
+                    start,
+                    end: member.range.end,
+                },
             });
         } else if x.kind == TokenKind::LBRACK {
             let member = self.parse_expression()?;
-            self.expect_and_consume(TokenKind::RBRACK)?;
+            let end = self.expect_and_consume(TokenKind::RBRACK)?.range.end;
 
             return Ok(Node::MemberAccess {
                 expr: Box::new(expr),
                 member: Box::new(member),
+                span: TokenRange { start, end },
             });
         }
 
-        panic!("Unknown member access parse token")
+        Err(self.error("Expected `.`, `::` or `[` for member access"))
     }
 }
 
@@ -883,6 +1058,23 @@ impl Parser {
 
     fn parse_let(&mut self, is_const: bool) -> NodeResult {
         self.advance()?;
+        self.skip_new_lines();
+
+        if matches!(
+            self.current()?.kind,
+            TokenKind::LPAREN | TokenKind::LBRACK | TokenKind::LBRACE
+        ) {
+            let pattern = self.parse_binding_pattern()?;
+
+            self.skip_new_lines();
+            self.expect_and_consume(TokenKind::EQUAL)?;
+
+            return Ok(Node::LetPattern {
+                pattern: Box::new(pattern),
+                value: Box::new(self.parse_expression()?),
+                is_const,
+            });
+        }
 
         let mut names = vec![];
 
@@ -906,7 +1098,7 @@ impl Parser {
                 && x.kind == TokenKind::COLON
             {
                 self.advance()?;
-                self.expect_and_consume(TokenKind::Identifier)?;
+                self.parse_type_hint()?;
             }
 
             self.skip_new_lines();
@@ -949,14 +1141,39 @@ impl Parser {
     }
 
     fn parse_using(&mut self) -> NodeResult {
-        self.advance()?;
+        let start = self.advance()?.range.start;
         self.skip_new_lines();
 
         let mut sequence = vec![];
         let mut wildcard = false;
         let mut imports = vec![];
+        let mut alias = None;
 
-        loop {
+        // `using "path/to/file.ign"` imports another file, which `::` walks into like a namespace
+        let file = if let TokenKind::StringLiteral(path) = self.current()?.kind {
+            self.advance()?;
+            Some(path)
+        } else {
+            None
+        };
+
+        let mut walk = file.is_none();
+        if file.is_some()
+            && let Ok(next) = self.current()
+            && matches!(next.kind, TokenKind::DOUBLECOLON | TokenKind::DOT)
+        {
+            self.advance()?;
+            if let Ok(next) = self.current()
+                && next.kind == TokenKind::STAR
+            {
+                wildcard = true;
+                self.advance()?;
+            } else {
+                walk = true;
+            }
+        }
+
+        while walk {
             if let Ok(next) = self.current()
                 && next.kind == TokenKind::LBRACE
             {
@@ -990,30 +1207,50 @@ impl Parser {
                 sequence.push(token.get_text(&self.source));
             }
 
-            if let Ok(next) = self.current() {
-                if matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI) {
+            if let Ok(next) = self.current()
+                && matches!(next.kind, TokenKind::DOUBLECOLON | TokenKind::DOT)
+            {
+                self.advance()?;
+                if let Ok(next) = self.current()
+                    && matches!(next.kind, TokenKind::STAR)
+                {
+                    wildcard = true;
                     self.advance()?;
                     break;
-                } else if matches!(next.kind, TokenKind::DOUBLECOLON | TokenKind::DOT) {
-                    self.advance()?;
-                    if let Ok(next) = self.current()
-                        && matches!(next.kind, TokenKind::STAR)
-                    {
-                        wildcard = true;
-                        self.advance()?;
-                        break;
-                    }
-                    continue;
                 }
+                continue;
             }
 
             break;
         }
 
+        if !wildcard
+            && imports.is_empty()
+            && let Ok(next) = self.current()
+            && next.kind == TokenKind::AS
+        {
+            self.advance()?;
+            alias = Some(
+                self.expect_and_consume(TokenKind::Identifier)?
+                    .get_text(&self.source),
+            );
+        }
+
+        let span = self.span_from(start);
+
+        if let Ok(next) = self.current()
+            && matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI)
+        {
+            self.advance()?;
+        }
+
         Ok(Node::UsingStatement {
+            file,
             sequence,
             imports,
             wildcard,
+            alias,
+            span,
         })
     }
 
@@ -1036,7 +1273,7 @@ impl Parser {
 
             let next = match self.current() {
                 Ok(tok) => tok,
-                Err(_) => return Err("Unexpected end of input inside block.".to_string()),
+                Err(_) => return Err(self.error("Unexpected end of input inside block.")),
             };
 
             match next.kind {
@@ -1045,18 +1282,48 @@ impl Parser {
                     self.advance()?;
                 }
 
-                _ => body.push(self.parse()?),
+                _ => match self.parse() {
+                    Ok(node) => body.push(node),
+                    Err(e) => {
+                        self.diagnostics.push(e);
+                        self.synchronize();
+                    }
+                },
             }
         }
 
         self.expect_and_consume(TokenKind::RBRACE)?;
 
-        self.parse_implicit_return(body.last_mut());
-
         Ok(Node::Block { body })
     }
 
     fn parse_function_def(&mut self, is_lambda: bool, is_const: bool) -> NodeResult {
+        let (name, args, return_type) = self.parse_function_signature(is_lambda)?;
+
+        // Lambdas can also have a bare expression as their body, like `fn(x) x > 0`
+        let block = match self.current() {
+            Ok(next) if is_lambda && !matches!(next.kind, TokenKind::LBRACE | TokenKind::FATARROW) => {
+                Node::SingleLineBlock {
+                    body: Box::new(self.parse_expression()?),
+                }
+            }
+            _ => self.parse_block()?,
+        };
+
+        Ok(Node::FunctionDefinition {
+            name,
+            args,
+            return_type,
+            is_const,
+            block: Box::new(block),
+        })
+    }
+
+    /// Parses everything in a function definition up to its block.
+    fn parse_function_signature(
+        &mut self,
+        is_lambda: bool,
+    ) -> SignatureResult {
         self.advance()?;
         self.skip_new_lines();
 
@@ -1085,10 +1352,7 @@ impl Parser {
                     && next.kind == TokenKind::COLON
                 {
                     this.expect_and_consume(TokenKind::COLON)?;
-                    Some(rc!(this
-                        .expect_and_consume(TokenKind::Identifier)?
-                        .get_text(&this.source)
-                        .to_string()))
+                    Some(this.parse_type_hint()?)
                 } else {
                     None
                 };
@@ -1111,41 +1375,113 @@ impl Parser {
             && next.kind == TokenKind::ARROW
         {
             self.advance()?;
-            Some(rc!(self
-                .expect_and_consume(TokenKind::Identifier)?
-                .get_text(&self.source)
-                .to_string()))
+            Some(self.parse_type_hint()?)
         } else {
             None
         };
 
-        Ok(Node::FunctionDefinition {
-            name,
-            args,
-            return_type,
-            is_const,
-            block: Box::new(self.parse_block()?),
+        Ok((name, args, return_type))
+    }
+
+    /// A type hint as written, like `number`, `Point`, `?string` or `number | nil`
+    fn parse_type_hint(&mut self) -> Result<Rc<String>, Diagnostic> {
+        let mut alternatives = vec![];
+
+        loop {
+            self.skip_new_lines();
+
+            let optional = match self.current() {
+                Ok(next) if next.kind == TokenKind::QUESTION => {
+                    self.advance()?;
+                    "?"
+                }
+                _ => "",
+            };
+
+            let name = match self.current()?.kind {
+                TokenKind::FN => {
+                    self.advance()?;
+                    "fn".to_string()
+                }
+                TokenKind::NIL => {
+                    self.advance()?;
+                    "nil".to_string()
+                }
+                TokenKind::LPAREN => self.parse_tuple_hint()?,
+                _ => self
+                    .expect_and_consume(TokenKind::Identifier)?
+                    .get_text(&self.source)
+                    .to_string(),
+            };
+            alternatives.push(format!("{optional}{name}"));
+
+            match self.current() {
+                Ok(next) if next.kind == TokenKind::PIPE => self.advance()?,
+                _ => break,
+            };
+        }
+
+        Ok(rc!(alternatives.join(" | ")))
+    }
+
+    /// `(number, string)` is a tuple of those types and `(number,)` a tuple of one, while
+    /// `(number | string)` only groups a hint, like parentheses in expressions.
+    fn parse_tuple_hint(&mut self) -> Result<String, Diagnostic> {
+        self.advance()?;
+
+        let mut elements = vec![];
+        let mut is_tuple = false;
+
+        loop {
+            self.skip_new_lines();
+            if self.current()?.kind == TokenKind::RPAREN {
+                is_tuple |= elements.is_empty();
+                break;
+            }
+
+            elements.push(self.parse_type_hint()?.to_string());
+            self.skip_new_lines();
+
+            if self.current()?.kind == TokenKind::COMMA {
+                self.advance()?;
+                is_tuple = true;
+            } else {
+                break;
+            }
+        }
+        self.expect_and_consume(TokenKind::RPAREN)?;
+
+        Ok(match elements.as_slice() {
+            [element] if is_tuple => format!("({element},)"),
+            _ => format!("({})", elements.join(", ")),
         })
     }
 
     fn parse_return(&mut self) -> NodeResult {
         self.advance()?;
+        Ok(Node::ReturnStatement(self.parse_optional_value()?))
+    }
 
+    fn parse_yield(&mut self) -> NodeResult {
+        self.advance()?;
+        Ok(Node::YieldStatement(self.parse_optional_value()?))
+    }
+
+    /// The value after `return` or `yield`, unless the statement ends right there
+    fn parse_optional_value(&mut self) -> Result<Option<Box<Node>>, Diagnostic> {
         if let Ok(next) = self.current()
-            && !matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI)
+            && !matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI | TokenKind::COMMA | TokenKind::RBRACE)
         {
-            Ok(Node::ReturnStatement(Some(Box::new(
-                self.parse_expression()?,
-            ))))
+            Ok(Some(Box::new(self.parse_expression()?)))
         } else {
-            Ok(Node::ReturnStatement(None))
+            Ok(None)
         }
     }
     fn parse_break(&mut self) -> NodeResult {
         self.advance()?;
 
         if let Ok(next) = self.current()
-            && !matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI)
+            && !matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI | TokenKind::COMMA | TokenKind::RBRACE)
         {
             Ok(Node::BreakStatement(Some(Box::new(
                 self.parse_expression()?,
@@ -1158,7 +1494,7 @@ impl Parser {
         self.advance()?;
 
         if let Ok(next) = self.current()
-            && !matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI)
+            && !matches!(next.kind, TokenKind::NEWLINE | TokenKind::SEMI | TokenKind::COMMA | TokenKind::RBRACE)
         {
             Ok(Node::OutStatement(Some(Box::new(self.parse_expression()?))))
         } else {
@@ -1166,6 +1502,34 @@ impl Parser {
         }
     }
 
+    fn parse_try(&mut self) -> NodeResult {
+        self.advance()?;
+
+        let block = self.parse_block()?;
+        self.expect_and_consume(TokenKind::CATCH)?;
+
+        let error_name = if let Ok(next) = self.current()
+            && next.kind == TokenKind::Identifier
+        {
+            self.advance()?;
+            Some(rc!(next.get_text(&self.source)))
+        } else {
+            None
+        };
+
+        Ok(Node::TryCatch {
+            block: Box::new(block),
+            error_name,
+            catch_block: Box::new(self.parse_block()?),
+        })
+    }
+
+    fn parse_throw(&mut self) -> NodeResult {
+        self.advance()?;
+
+        Ok(Node::ThrowStatement(Box::new(self.parse_expression()?)))
+    }
+
     fn parse_if(&mut self) -> NodeResult {
         self.advance()?;
 
@@ -1231,10 +1595,7 @@ impl Parser {
     fn parse_for(&mut self) -> NodeResult {
         self.advance()?;
 
-        let var_name = rc!(self
-            .expect_and_consume(TokenKind::Identifier)?
-            .get_text(&self.source)
-            .to_string());
+        let pattern = self.parse_binding_pattern()?;
 
         self.expect_and_consume(TokenKind::IN)?;
 
@@ -1242,14 +1603,14 @@ impl Parser {
         let block = Box::new(self.parse_block()?);
 
         Ok(Node::ForLoop {
-            var_name,
+            pattern: Box::new(pattern),
             expr,
             block,
         })
     }
 
     fn parse_class_def(&mut self) -> NodeResult {
-        self.advance()?;
+        let start = self.advance()?.range.start;
         self.skip_new_lines();
 
         let name = self
@@ -1277,9 +1638,7 @@ impl Parser {
                         break;
                     }
                 } else {
-                    return Err(
-                        "Unexpected end of input while parsing class interfaces.".to_string()
-                    );
+                    return Err(self.error("Unexpected end of input while parsing class interfaces."));
                 }
 
                 self.expect_and_consume(TokenKind::COMMA)?;
@@ -1291,6 +1650,7 @@ impl Parser {
             }
         }
 
+        let span = self.span_from(start);
         self.expect_and_consume(TokenKind::LBRACE)?;
 
         let mut let_statements = vec![];
@@ -1304,7 +1664,7 @@ impl Parser {
                     break;
                 }
             } else {
-                return Err("Unexpected end of input while parsing class.".to_string());
+                return Err(self.error("Unexpected end of input while parsing class."));
             }
 
             if let Ok(next) = self.current() {
@@ -1316,9 +1676,9 @@ impl Parser {
                     }
 
                     _ => {
-                        return Err(format!(
+                        return Err(self.error(format!(
                             "Class definitions can only take functions or let statements."
-                        ));
+                        )));
                     }
                 }
             }
@@ -1331,9 +1691,108 @@ impl Parser {
             interfaces,
             let_statements,
             functions,
+            span,
+        })
+    }
+
+    fn parse_interface_def(&mut self) -> NodeResult {
+        self.advance()?;
+        self.skip_new_lines();
+
+        let name = rc!(self
+            .expect_and_consume(TokenKind::Identifier)?
+            .get_text(&self.source)
+            .to_string());
+
+        self.skip_new_lines();
+        self.expect_and_consume(TokenKind::LBRACE)?;
+
+        let mut let_statements = vec![];
+        let mut functions = vec![];
+
+        loop {
+            self.skip_new_lines();
+
+            if let Ok(next) = self.current() {
+                match next.kind {
+                    TokenKind::RBRACE => break,
+
+                    // Only the signature is required, implementing classes provide the body
+                    TokenKind::FN => {
+                        let (name, args, return_type) = self.parse_function_signature(false)?;
+
+                        functions.push(Node::FunctionDefinition {
+                            name,
+                            args,
+                            return_type,
+                            is_const: false,
+                            block: Box::new(Node::Block { body: vec![] }),
+                        });
+                    }
+                    TokenKind::LET => let_statements.push(self.parse_let(false)?),
+                    TokenKind::SEMI => {
+                        self.advance()?;
+                    }
+
+                    _ => {
+                        return Err(self.error(
+                            "Interface definitions can only take function signatures or let statements.",
+                        ));
+                    }
+                }
+            } else {
+                return Err(self.error("Unexpected end of input while parsing interface."));
+            }
+        }
+
+        self.expect_and_consume(TokenKind::RBRACE)?;
+
+        Ok(Node::InterfaceDef {
+            name,
+            let_statements,
+            functions,
         })
     }
 
+    fn parse_impl_block(&mut self) -> NodeResult {
+        self.advance()?;
+        self.skip_new_lines();
+
+        let name = rc!(self
+            .expect_and_consume(TokenKind::Identifier)?
+            .get_text(&self.source)
+            .to_string());
+
+        self.skip_new_lines();
+        self.expect_and_consume(TokenKind::LBRACE)?;
+
+        let mut functions = vec![];
+
+        loop {
+            self.skip_new_lines();
+
+            if let Ok(next) = self.current() {
+                match next.kind {
+                    TokenKind::RBRACE => break,
+                    TokenKind::FN => functions.push(self.parse_function_def(false, false)?),
+                    TokenKind::SEMI => {
+                        self.advance()?;
+                    }
+
+                    _ => {
+                        return Err(self.error("Impl blocks can only take functions."));
+                    }
+                }
+            } else {
+                return Err(self.error("Unexpected end of input while parsing impl block."));
+            }
+        }
+
+        self.expect_and_consume(TokenKind::RBRACE)?;
+
+        Ok(Node::ImplBlock { name, functions })
+    }
+
     fn parse_range(&mut self) -> NodeResult {
         let left = self.parse_add_sub()?;
 
@@ -1378,29 +1837,6 @@ impl Parser {
         return Ok(left);
     }
 
-    fn parse_implicit_return(&mut self, last: Option<&mut Node>) {
-        if let Some(last) = last {
-            if let Node::ExprStmt(expr) = last {
-                let is_returnable = !matches!(
-                    **expr,
-                    Node::LetStatement { .. }
-                        | Node::SetVariable { .. }
-                        | Node::ShorthandAssignment { .. }
-                        | Node::WhileLoop { .. }
-                        | Node::ForLoop { .. }
-                        | Node::Loop { .. }
-                        | Node::OutStatement(..)
-                        | Node::ReturnStatement(..)
-                        | Node::BreakStatement(..)
-                );
-
-                if is_returnable {
-                    *last = Node::ExprStmt(Box::new(Node::OutStatement(Some(expr.clone()))));
-                }
-            }
-        }
-    }
-
     fn parse_match(&mut self) -> NodeResult {
         self.advance()?;
 
@@ -1413,13 +1849,30 @@ impl Parser {
             TokenKind::RBRACE,
             Some(TokenKind::COMMA),
             |this| {
-                let condition = this.parse_expression()?;
+                let pattern = this.parse_pattern()?;
+
+                let guard = if let Ok(next) = this.current()
+                    && next.kind == TokenKind::IF
+                {
+                    this.advance()?;
+                    Some(this.parse_expression()?)
+                } else {
+                    None
+                };
+
+                this.skip_new_lines();
+                this.expect_or_warn(TokenKind::FATARROW);
                 this.skip_new_lines();
-                this.expect_and_consume(TokenKind::FATARROW);
 
-                let value = this.parse_expression()?;
+                let value = if let Ok(next) = this.current()
+                    && next.kind == TokenKind::LBRACE
+                {
+                    this.parse_block()?
+                } else {
+                    this.parse_expression()?
+                };
 
-                branches.push((condition, value));
+                branches.push((pattern, guard, value));
 
                 Ok(())
             },
@@ -1431,6 +1884,251 @@ impl Parser {
         })
     }
 
+    /// Parses a `match` pattern, including alternatives separated by `|`.
+    fn parse_pattern(&mut self) -> PatternResult {
+        let mut alternatives = vec![self.parse_single_pattern()?];
+
+        while let Ok(next) = self.current()
+            && next.kind == TokenKind::PIPE
+        {
+            self.advance()?;
+            self.skip_new_lines();
+            alternatives.push(self.parse_single_pattern()?);
+        }
+
+        if alternatives.len() == 1 {
+            Ok(alternatives.pop().unwrap())
+        } else {
+            Ok(Pattern::Or(alternatives))
+        }
+    }
+
+    fn parse_single_pattern(&mut self) -> PatternResult {
+        let token = self.current()?;
+
+        match token.kind {
+            TokenKind::Identifier => {
+                let name = token.get_text(&self.source).to_string();
+                let next = self.peek().map(|t| t.kind);
+
+                if name == "_" {
+                    self.advance()?;
+                    Ok(Pattern::Wildcard)
+                } else if next == Some(TokenKind::LBRACE) {
+                    self.parse_struct_pattern()
+                } else if matches!(next, Some(TokenKind::DOT | TokenKind::DOUBLECOLON)) {
+                    self.parse_path_pattern()
+                } else {
+                    self.advance()?;
+                    Ok(Pattern::Binding(rc!(name)))
+                }
+            }
+
+            TokenKind::LPAREN => self.parse_sequence_pattern(true),
+            TokenKind::LBRACK => self.parse_sequence_pattern(false),
+
+            _ => self.parse_value_pattern(),
+        }
+    }
+
+    /// What `let` and `for` bind to: a name, or a pattern taking the value apart like
+    /// `(a, b)`, `[x, ..rest]` or `{ name, age }`.
+    fn parse_binding_pattern(&mut self) -> PatternResult {
+        if self.current()?.kind == TokenKind::LBRACE {
+            return Ok(Pattern::Struct {
+                name: None,
+                fields: self.parse_field_patterns()?,
+            });
+        }
+
+        self.parse_single_pattern()
+    }
+
+    /// A literal, optionally followed by `..end` to match a range.
+    fn parse_value_pattern(&mut self) -> PatternResult {
+        let value = self.parse_add_sub()?;
+        self.parse_range_pattern(value)
+    }
+
+    fn parse_range_pattern(&mut self, value: Node) -> PatternResult {
+        if let Ok(x) = self.current()
+            && x.kind == TokenKind::DOUBLEDOT
+        {
+            self.advance()?;
+
+            let inclusive = if let Ok(x) = self.current()
+                && x.kind == TokenKind::EQUAL
+            {
+                self.advance()?;
+                true
+            } else {
+                false
+            };
+
+            return Ok(Pattern::Range {
+                start: value,
+                end: self.parse_add_sub()?,
+                inclusive,
+            });
+        }
+
+        Ok(Pattern::Value(value))
+    }
+
+    /// A path like `Color.Red` or `Std::Math::pi`, or an enum variant with its fields like
+    /// `Shape.Circle(r)` and `Shape.Rect { w, h }`.
+    fn parse_path_pattern(&mut self) -> PatternResult {
+        let start = self.current()?.range.start;
+        let name = self.advance()?.get_text(&self.source).to_string();
+        let mut target = Node::Variable(rc!(name));
+
+        while let Ok(next) = self.current()
+            && matches!(next.kind, TokenKind::DOT | TokenKind::DOUBLECOLON)
+        {
+            self.advance()?;
+            let member = self.expect_and_consume(TokenKind::Identifier)?;
+
+            target = Node::MemberAccess {
+                expr: Box::new(target),
+                member: Box::new(Node::StringLiteral(
+                    member.get_text(&self.source).to_string(),
+                )),
+                span: TokenRange {
+                    start,
+                    end: member.range.end,
+                },
+            };
+        }
+
+        match self.current().map(|t| t.kind) {
+            Ok(TokenKind::LPAREN) => {
+                let mut items = vec![];
+
+                self.parse_surrounded(
+                    TokenKind::LPAREN,
+                    TokenKind::RPAREN,
+                    Some(TokenKind::COMMA),
+                    |this| {
+                        items.push(this.parse_pattern()?);
+                        Ok(())
+                    },
+                )?;
+
+                Ok(Pattern::Variant {
+                    target,
+                    items,
+                    fields: vec![],
+                })
+            }
+            Ok(TokenKind::LBRACE) => {
+                let fields = self.parse_field_patterns()?;
+
+                Ok(Pattern::Variant {
+                    target,
+                    items: vec![],
+                    fields,
+                })
+            }
+            _ => self.parse_range_pattern(target),
+        }
+    }
+
+    fn parse_sequence_pattern(&mut self, is_tuple: bool) -> PatternResult {
+        let (left, right) = if is_tuple {
+            (TokenKind::LPAREN, TokenKind::RPAREN)
+        } else {
+            (TokenKind::LBRACK, TokenKind::RBRACK)
+        };
+
+        let mut items = vec![];
+        let mut rest = None;
+        let mut has_comma = false;
+
+        self.parse_surrounded(left, right, Some(TokenKind::COMMA), |this| {
+            if let Ok(next) = this.current()
+                && next.kind == TokenKind::DOUBLEDOT
+            {
+                this.advance()?;
+
+                if rest.is_some() {
+                    return Err(this.error("Only one `..` is allowed in a pattern."));
+                }
+
+                let name = if let Ok(next) = this.current()
+                    && next.kind == TokenKind::Identifier
+                {
+                    Some(rc!(this.advance()?.get_text(&this.source).to_string()))
+                } else {
+                    None
+                };
+                rest = Some((items.len(), name));
+            } else {
+                items.push(this.parse_pattern()?);
+            }
+
+            if let Ok(next) = this.current() {
+                has_comma |= next.kind == TokenKind::COMMA;
+            }
+
+            Ok(())
+        })?;
+
+        // `(x)` only groups a pattern, a tuple of one needs a comma: `(x,)`
+        if is_tuple && !has_comma && rest.is_none() && items.len() == 1 {
+            return Ok(items.pop().unwrap());
+        }
+
+        Ok(Pattern::Sequence {
+            is_tuple,
+            items,
+            rest,
+        })
+    }
+
+    fn parse_struct_pattern(&mut self) -> PatternResult {
+        let name = rc!(self
+            .expect_and_consume(TokenKind::Identifier)?
+            .get_text(&self.source)
+            .to_string());
+
+        Ok(Pattern::Struct {
+            name: Some(name),
+            fields: self.parse_field_patterns()?,
+        })
+    }
+
+    fn parse_field_patterns(&mut self) -> Result<Vec<(String, Pattern)>, Diagnostic> {
+        let mut fields = vec![];
+
+        self.parse_surrounded(
+            TokenKind::LBRACE,
+            TokenKind::RBRACE,
+            Some(TokenKind::COMMA),
+            |this| {
+                let field = this
+                    .expect_and_consume(TokenKind::Identifier)?
+                    .get_text(&this.source)
+                    .to_string();
+
+                // `{ x }` is short for `{ x: x }`
+                let pattern = if let Ok(next) = this.current()
+                    && next.kind == TokenKind::COLON
+                {
+                    this.advance()?;
+                    this.parse_pattern()?
+                } else {
+                    Pattern::Binding(rc!(field.clone()))
+                };
+
+                fields.push((field, pattern));
+
+                Ok(())
+            },
+        )?;
+
+        Ok(fields)
+    }
+
     fn parse_enum(&mut self) -> NodeResult {
         self.advance()?;
 
@@ -1438,10 +2136,7 @@ impl Parser {
             .expect_and_consume(TokenKind::Identifier)?
             .get_text(&self.source);
 
-        self.expect_and_consume(TokenKind::LBRACE)?;
-
         let mut items = vec![];
-        let mut id = 0;
 
         self.parse_surrounded(
             TokenKind::LBRACE,
@@ -1452,23 +2147,62 @@ impl Parser {
                     .expect_and_consume(TokenKind::Identifier)?
                     .get_text(&this.source);
 
-                let item_value = if let Ok(next) = this.current()
-                    && next.kind == TokenKind::EQUAL
-                {
-                    this.advance()?;
-                    this.parse_expression()?
-                } else {
-                    let v = Node::NumberLiteral(id as f64);
-                    id += 1;
-                    v
+                let item = match this.current().map(|t| t.kind) {
+                    Ok(TokenKind::EQUAL) => {
+                        this.advance()?;
+                        Some(EnumItem::Value(this.parse_expression()?))
+                    }
+                    Ok(kind @ (TokenKind::LPAREN | TokenKind::LBRACE)) => {
+                        let is_struct = kind == TokenKind::LBRACE;
+                        let right = if is_struct {
+                            TokenKind::RBRACE
+                        } else {
+                            TokenKind::RPAREN
+                        };
+
+                        let mut fields = vec![];
+                        this.parse_surrounded(kind, right, Some(TokenKind::COMMA), |this| {
+                            let field = this.expect_and_consume(TokenKind::Identifier)?;
+                            fields.push(field.get_text(&this.source));
+                            Ok(())
+                        })?;
+
+                        Some(EnumItem::Variant { fields, is_struct })
+                    }
+                    _ => None,
                 };
 
-                items.push((item_name, item_value));
+                items.push((item_name, item));
 
                 Ok(())
             },
         )?;
 
+        // Plain items are numbered, unless other items carry data
+        let has_variants = items
+            .iter()
+            .any(|(_, item)| matches!(item, Some(EnumItem::Variant { .. })));
+        let mut id = 0;
+
+        let items = items
+            .into_iter()
+            .map(|(item_name, item)| {
+                let item = item.unwrap_or_else(|| {
+                    if has_variants {
+                        EnumItem::Variant {
+                            fields: vec![],
+                            is_struct: false,
+                        }
+                    } else {
+                        id += 1;
+                        EnumItem::Value(Node::NumberLiteral((id - 1) as f64))
+                    }
+                });
+
+                (item_name, item)
+            })
+            .collect();
+
         Ok(Node::EnumDef { name, items })
     }
 
@@ -1490,13 +2224,11 @@ impl Parser {
                     .expect_and_consume(TokenKind::Identifier)?
                     .get_text(&this.source);
 
-                this.expect_and_consume(TokenKind::COLON);
+                this.expect_or_warn(TokenKind::COLON);
 
-                let field_type = this
-                    .expect_and_consume(TokenKind::Identifier)?
-                    .get_text(&this.source);
+                let field_type = this.parse_type_hint()?;
 
-                fields.push((field_name, field_type));
+                fields.push((field_name, field_type.to_string()));
 
                 Ok(())
             },
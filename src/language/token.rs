@@ -44,6 +44,7 @@ pub enum TokenKind {
     CONST,
     FN,
     RETURN,
+    YIELD,
     LOOP,
     WHILE,
     FOR,
@@ -58,8 +59,12 @@ pub enum TokenKind {
     USING,
     AS,
     ENUM,
-    // INTERFACE,
+    INTERFACE,
+    IMPL,
     STRUCT,
+    TRY,
+    CATCH,
+    THROW,
 
     // Punctuation
     LPAREN, // Parenthesis ()
@@ -97,6 +102,7 @@ pub enum TokenKind {
     TILDA,          // ~
     BACKTICK,
     PIPE,
+    PIPELINE, // |>
     DOT,
     DOUBLEDOT, // ..
     COMMA,
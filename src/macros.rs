@@ -52,18 +52,28 @@ macro_rules! hashmap {
 #[macro_export]
 macro_rules! lib_function {
     ($this:expr, $lib:expr, $member:expr, $val:expr) => {
-        Value::Function(TFunction::with_lib(
-            rc_str!($lib),
-            rc_str!($member),
+        Value::Function(std::rc::Rc::new(TFunction::with_lib(
+            &$lib,
+            &$member,
             Some(Box::new({ $val }($this.clone()))),
-        ))
+        )))
     };
 
     ($lib:literal, $member:expr) => {
-        Value::Function(TFunction::with_lib(rc_str!($lib), rc_str!($member), None))
+        Value::Function(std::rc::Rc::new(TFunction::with_lib(&$lib, &$member, None)))
     };
 
     ($lib:expr, $member:expr) => {
-        Value::Function(TFunction::with_lib(rc_str!($lib), rc_str!($member), None))
+        Value::Function(std::rc::Rc::new(TFunction::with_lib(&$lib, &$member, None)))
+    };
+}
+
+#[macro_export]
+macro_rules! runtime_error {
+    ($kind:ident, $($arg:tt)*) => {
+        crate::virtual_machine::error::RuntimeError::new(
+            crate::virtual_machine::error::ErrorKind::$kind,
+            format!($($arg)*),
+        )
     };
 }
@@ -1 +1,2 @@
+pub mod source;
 pub mod to_index;
@@ -0,0 +1,73 @@
+use std::{
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+/// A position in a source file. `line` and `col` start at 1, `len` is in chars.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Location {
+    pub file: Rc<str>,
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.col)
+    }
+}
+
+/// Char offsets at which each line of `text` starts (`\r\n` and lone `\r` count as one break).
+pub fn line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    let mut chars = text.chars().enumerate().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' => starts.push(i + 1),
+            '\r' if chars.peek().is_none_or(|(_, next)| *next != '\n') => starts.push(i + 1),
+            _ => {}
+        }
+    }
+
+    starts
+}
+
+/// Turns a char offset into a 1-based `(line, col)` pair.
+pub fn line_col(line_starts: &[usize], offset: usize) -> (usize, usize) {
+    let line = line_starts.partition_point(|&start| start <= offset).max(1);
+
+    (line, offset - line_starts[line - 1] + 1)
+}
+
+/// The line `location` points at, with carets under the offending range.
+///
+/// ```text
+///    |
+///  3 |     let x = a / 0
+///    |               ^
+/// ```
+pub fn excerpt(text: &str, location: &Location) -> Option<String> {
+    let line = text.lines().nth(location.line.checked_sub(1)?)?;
+    let line = line.trim_end_matches('\r');
+    let line_len = line.chars().count();
+
+    let col = location.col.saturating_sub(1).min(line_len);
+    let len = location.len.min(line_len - col).max(1);
+
+    // Keep tabs so the carets line up with the source line
+    let padding: String = line
+        .chars()
+        .take(col)
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect();
+
+    let number = location.line.to_string();
+    let gutter = " ".repeat(number.len());
+
+    Some(format!(
+        "{gutter} |\n{number} | {line}\n{gutter} | {padding}{}",
+        "^".repeat(len)
+    ))
+}
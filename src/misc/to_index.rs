@@ -1,11 +1,15 @@
-pub fn to_index(idx: f64, len: usize) -> usize {
-    if idx as i32 >= 0 {
-        if idx < len as f64 {
-            return idx as usize;
-        } else {
-            panic!("OutOfBoundsError: Index is {idx} but len is {len}.")
-        }
-    }
+use crate::virtual_machine::error::RuntimeResult;
+
+pub fn to_index(idx: f64, len: usize) -> RuntimeResult<usize> {
+    let target = if idx >= 0.0 {
+        idx as usize
+    } else {
+        len.wrapping_sub(-idx as usize)
+    };
 
-    return len - idx as usize;
+    if target < len {
+        Ok(target)
+    } else {
+        Err(runtime_error!(IndexError, "Index is {idx} but len is {len}."))
+    }
 }
@@ -1,17 +1,112 @@
-use crate::virtual_machine::{inst::Inst, value::Value};
+use crate::{
+    misc::source::Location,
+    virtual_machine::{inst::Inst, reg_inst::RegInst, value::Value},
+};
 use bincode::{Decode, Encode};
+use std::rc::Rc;
+
+/// First bytes of every bytecode file
+pub const MAGIC: &[u8; 4] = b"IGB\0";
+
+/// Bumped whenever the encoding of `Chunk` (or anything inside it) changes
+pub const FORMAT_VERSION: u16 = 1;
 
 #[derive(Encode, Decode)]
 pub struct Chunk {
     pub constants: Vec<Value>,
     pub instructions: Vec<Inst>,
+    /// The chunk's code for the register VM, empty if it runs on the stack VM
+    pub registers: Vec<RegInst>,
+    pub files: Vec<Rc<str>>,
+    pub lines: Vec<LineInfo>,
 }
 
 impl Chunk {
-    pub fn new(constants: Vec<Value>, instructions: Vec<Inst>) -> Self {
+    pub fn new(
+        constants: Vec<Value>,
+        instructions: Vec<Inst>,
+        registers: Vec<RegInst>,
+        files: Vec<Rc<str>>,
+        lines: Vec<LineInfo>,
+    ) -> Self {
         Self {
             constants,
             instructions,
+            registers,
+            files,
+            lines,
+        }
+    }
+
+    pub fn header() -> Vec<u8> {
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        header
+    }
+
+    /// Checks the header of a bytecode file and returns the encoded chunk after it.
+    pub fn strip_header(bytes: &[u8]) -> Result<&[u8], String> {
+        let Some(rest) = bytes.strip_prefix(MAGIC.as_slice()) else {
+            return Err(
+                "not an Ignite bytecode file, or compiled by an incompatible version".into(),
+            );
+        };
+
+        match rest {
+            [lo, hi, chunk @ ..] if u16::from_le_bytes([*lo, *hi]) == FORMAT_VERSION => Ok(chunk),
+            [lo, hi, ..] => Err(format!(
+                "compiled by an incompatible version (format {}, expected {FORMAT_VERSION})",
+                u16::from_le_bytes([*lo, *hi])
+            )),
+            _ => Err("truncated header".into()),
+        }
+    }
+}
+
+/// Source position of the instructions from `start` up to the next entry in the line table.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq)]
+pub struct LineInfo {
+    pub start: usize,
+    /// Index into the chunk's `files`
+    pub file: usize,
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+impl LineInfo {
+    /// Finds the entry covering the instruction at `pos`.
+    pub fn find(lines: &[LineInfo], pos: usize) -> Option<&LineInfo> {
+        let idx = lines.partition_point(|line| line.start <= pos);
+
+        lines.get(idx.checked_sub(1)?)
+    }
+
+    pub fn location(&self, files: &[Rc<str>]) -> Option<Location> {
+        Some(Location {
+            file: files.get(self.file)?.clone(),
+            line: self.line,
+            col: self.col,
+            len: self.len,
+        })
+    }
+
+    fn same_place(&self, other: &LineInfo) -> bool {
+        (self.file, self.line, self.col, self.len) == (other.file, other.line, other.col, other.len)
+    }
+
+    /// Appends `info` unless the table already places instruction `info.start` there.
+    pub fn push(lines: &mut Vec<LineInfo>, info: LineInfo) {
+        match lines.last_mut() {
+            Some(last) if last.start == info.start => *last = info,
+            Some(last) if last.same_place(&info) => {}
+            _ => lines.push(info),
+        }
+
+        // Replacing the last entry can make it a repeat of the one before
+        let n = lines.len();
+        if n >= 2 && lines[n - 2].same_place(&lines[n - 1]) {
+            lines.pop();
         }
     }
 }
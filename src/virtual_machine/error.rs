@@ -0,0 +1,157 @@
+use crate::{
+    misc::source::Location,
+    virtual_machine::{
+        types::{list::TList, r#struct::TStruct, structdef::TStructDef},
+        value::Value,
+    },
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt::{self, Display},
+    rc::Rc,
+};
+
+pub type RuntimeResult<T = Value> = Result<T, RuntimeError>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    TypeError,
+    ValueError,
+    NameError,
+    PropertyError,
+    IndexError,
+    ArgumentError,
+    ZeroDivisionError,
+    ConstError,
+    IOError,
+    InternalError,
+    /// Raised by `throw` in a script
+    Error,
+}
+
+impl ErrorKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "TypeError" => Self::TypeError,
+            "ValueError" => Self::ValueError,
+            "NameError" => Self::NameError,
+            "PropertyError" => Self::PropertyError,
+            "IndexError" => Self::IndexError,
+            "ArgumentError" => Self::ArgumentError,
+            "ZeroDivisionError" => Self::ZeroDivisionError,
+            "ConstError" => Self::ConstError,
+            "IOError" => Self::IOError,
+            "InternalError" => Self::InternalError,
+            "Error" => Self::Error,
+            _ => return None,
+        })
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Index of the instruction that failed (filled in by `VM::run`)
+    pub pos: Option<usize>,
+    /// Where the failing instruction came from, if the chunk has a line table
+    pub location: Option<Location>,
+    /// Instruction indices (and their source locations) of the calls that led to the error,
+    /// outermost first
+    pub trace: Vec<(usize, Option<Location>)>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            pos: None,
+            location: None,
+            trace: vec![],
+        }
+    }
+
+    /// The `Error` struct a `catch` block binds: `message`, `kind` and `trace` (innermost first).
+    pub fn to_value(&self) -> Value {
+        let place = |pos: Option<usize>, location: &Option<Location>| match (location, pos) {
+            (Some(location), _) => Value::string(location.to_string()),
+            (None, Some(pos)) => Value::string(format!("instruction {pos}")),
+            (None, None) => Value::NIL,
+        };
+
+        let mut trace = vec![];
+        if self.pos.is_some() {
+            trace.push(place(self.pos, &self.location));
+        }
+        for (pos, location) in self.trace.iter().rev() {
+            trace.push(place(Some(*pos), location));
+        }
+
+        let fields = HashMap::from([
+            ("message".to_string(), "string".to_string()),
+            ("kind".to_string(), "string".to_string()),
+            ("trace".to_string(), "list".to_string()),
+        ]);
+        let values = HashMap::from([
+            ("message".to_string(), Value::string(&self.message)),
+            ("kind".to_string(), Value::string(self.kind.to_string())),
+            (
+                "trace".to_string(),
+                Value::List(TList::new(Rc::new(RefCell::new(trace)))),
+            ),
+        ]);
+
+        Value::Struct(Rc::new(TStruct::new(
+            Rc::new(TStructDef::new("Error".to_string(), Rc::new(fields))),
+            Rc::new(RefCell::new(values)),
+        )))
+    }
+
+    /// The error raised by `throw value`. Rethrowing a caught `Error` keeps its kind and message.
+    pub fn from_value(value: Value) -> Self {
+        if let Value::Struct(error) = &value
+            && error.base.name == "Error"
+        {
+            let values = error.values.borrow();
+            let field = |name: &str| values.get(name).map(|v| v.to_string(false));
+
+            let kind = field("kind")
+                .and_then(|kind| ErrorKind::from_name(&kind))
+                .unwrap_or(ErrorKind::Error);
+
+            return Self::new(kind, field("message").unwrap_or_default());
+        }
+
+        Self::new(ErrorKind::Error, value.to_string(false))
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)?;
+
+        match (&self.location, self.pos) {
+            (Some(location), _) => write!(f, "\n    at {location}")?,
+            (None, Some(pos)) => write!(f, "\n    at instruction {pos}")?,
+            _ => {}
+        }
+        for (call, location) in self.trace.iter().rev() {
+            match location {
+                Some(location) => write!(f, "\n    called from {location}")?,
+                None => write!(f, "\n    called from instruction {call}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for RuntimeError {}
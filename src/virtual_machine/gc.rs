@@ -0,0 +1,412 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::{Rc, Weak},
+};
+
+use crate::virtual_machine::{
+    types::{
+        class::TObject,
+        function::{TFunction, Upvalue},
+        r#struct::TStruct,
+        variant::TVariant,
+    },
+    value::Value,
+};
+
+/// Collections below this many allocations apart aren't worth the scan
+const MIN_THRESHOLD: usize = 1000;
+
+thread_local! {
+    static HEAP: RefCell<Heap> = RefCell::new(Heap::default());
+}
+
+/// The mutable cells values can point back into themselves through. Values are reference
+/// counted, so these are only tracked to find the cycles that counting alone never frees.
+pub enum Tracked {
+    Items(Weak<RefCell<Vec<Value>>>),
+    Dict(Weak<RefCell<HashMap<Value, Value>>>),
+    Fields(Weak<RefCell<HashMap<String, Value>>>),
+    Upvalue(Weak<RefCell<Upvalue>>),
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Node> {
+        Some(match self {
+            Tracked::Items(x) => Node::Items(x.upgrade()?),
+            Tracked::Dict(x) => Node::Dict(x.upgrade()?),
+            Tracked::Fields(x) => Node::Fields(x.upgrade()?),
+            Tracked::Upvalue(x) => Node::Upvalue(x.upgrade()?),
+        })
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            Tracked::Items(x) => x.strong_count() > 0,
+            Tracked::Dict(x) => x.strong_count() > 0,
+            Tracked::Fields(x) => x.strong_count() > 0,
+            Tracked::Upvalue(x) => x.strong_count() > 0,
+        }
+    }
+
+    fn addr(&self) -> usize {
+        match self {
+            Tracked::Items(x) => x.as_ptr() as *const () as usize,
+            Tracked::Dict(x) => x.as_ptr() as *const () as usize,
+            Tracked::Fields(x) => x.as_ptr() as *const () as usize,
+            Tracked::Upvalue(x) => x.as_ptr() as *const () as usize,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct Stats {
+    pub collections: usize,
+    pub freed: usize,
+    pub tracked: usize,
+}
+
+struct Heap {
+    tracked: Vec<Tracked>,
+    /// Allocations since the last collection
+    allocations: usize,
+    threshold: usize,
+    /// Collect on every allocation, to shake out anything holding on to a value without a reference
+    stress: bool,
+    collecting: bool,
+    stats: Stats,
+}
+
+impl Default for Heap {
+    fn default() -> Self {
+        Self {
+            tracked: vec![],
+            allocations: 0,
+            threshold: MIN_THRESHOLD,
+            stress: false,
+            collecting: false,
+            stats: Stats::default(),
+        }
+    }
+}
+
+/// Starts tracking a freshly allocated cell, collecting first if enough piled up since last time.
+pub fn track(cell: Tracked) {
+    let should_collect = HEAP.with_borrow_mut(|heap| {
+        heap.tracked.push(cell);
+        heap.allocations += 1;
+        !heap.collecting && (heap.stress || heap.allocations >= heap.threshold)
+    });
+
+    if should_collect {
+        collect();
+    }
+}
+
+pub fn set_stress(stress: bool) {
+    HEAP.with_borrow_mut(|heap| heap.stress = stress);
+}
+
+pub fn stats() -> Stats {
+    HEAP.with_borrow(|heap| Stats {
+        tracked: heap.tracked.iter().filter(|cell| cell.is_alive()).count(),
+        ..heap.stats
+    })
+}
+
+/// Frees every tracked cell that's only referenced from other unreachable cells, and returns
+/// how many there were.
+///
+/// Nothing outside the heap has to be walked: a cell referenced more times than the heap
+/// itself accounts for is held from somewhere else (the stack, a global, a Rust local in the
+/// middle of an instruction, ...), so it and everything it reaches stays.
+pub fn collect() -> usize {
+    let Some(tracked) = HEAP.with_borrow_mut(|heap| {
+        if heap.collecting {
+            return None;
+        }
+        heap.collecting = true;
+        Some(std::mem::take(&mut heap.tracked))
+    }) else {
+        return 0;
+    };
+
+    let mut scan = Scan::default();
+    for cell in tracked.iter() {
+        if let Some(node) = cell.upgrade() {
+            scan.discover(node);
+        }
+    }
+    scan.count_references();
+
+    let garbage = scan.unreachable();
+    let freed = garbage.len();
+
+    // Emptying the cells breaks the cycles, and the values drop once nothing holds them anymore
+    let mut contents = vec![];
+    for i in garbage {
+        scan.nodes[i].node.clear(&mut contents);
+    }
+    drop(scan);
+    drop(contents);
+
+    let mut seen = HashSet::new();
+    let survivors: Vec<_> = tracked
+        .into_iter()
+        .filter(|cell| cell.is_alive() && seen.insert(cell.addr()))
+        .collect();
+
+    HEAP.with_borrow_mut(|heap| {
+        // Cells allocated while collecting were pushed to the emptied list
+        let allocated = std::mem::replace(&mut heap.tracked, survivors);
+        heap.tracked.extend(allocated);
+
+        heap.threshold = heap.tracked.len().max(MIN_THRESHOLD);
+        heap.allocations = 0;
+        heap.collecting = false;
+        heap.stats.collections += 1;
+        heap.stats.freed += freed;
+    });
+
+    freed
+}
+
+/// A reference counted part of a value that other values can be reached through
+#[derive(Clone)]
+enum Node {
+    // Tracked cells
+    Items(Rc<RefCell<Vec<Value>>>),
+    Dict(Rc<RefCell<HashMap<Value, Value>>>),
+    Fields(Rc<RefCell<HashMap<String, Value>>>),
+    Upvalue(Rc<RefCell<Upvalue>>),
+
+    // What they're held through
+    Function(Rc<TFunction>),
+    Struct(Rc<TStruct>),
+    Object(Rc<TObject>),
+    Variant(Rc<TVariant>),
+    VariantValues(Rc<Vec<Value>>),
+}
+
+impl Node {
+    /// The node `value` points to. The rest of the values either can't hold others or
+    /// aren't traced, which only ever keeps what they hold alive.
+    fn of(value: &Value) -> Option<Node> {
+        Some(match value {
+            Value::List(x) | Value::Tuple(x) => Node::Items(Rc::clone(&x.values)),
+            Value::Dict(x) => Node::Dict(Rc::clone(&x.values)),
+            Value::Function(x) => Node::Function(Rc::clone(x)),
+            Value::Struct(x) => Node::Struct(Rc::clone(x)),
+            Value::Object(x) => Node::Object(Rc::clone(x)),
+            Value::Variant(x) => Node::Variant(Rc::clone(x)),
+            _ => return None,
+        })
+    }
+
+    fn addr(&self) -> usize {
+        match self {
+            Node::Items(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Dict(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Fields(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Upvalue(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Function(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Struct(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Object(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::Variant(x) => Rc::as_ptr(x) as *const () as usize,
+            Node::VariantValues(x) => Rc::as_ptr(x) as *const () as usize,
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Items(x) => Rc::strong_count(x),
+            Node::Dict(x) => Rc::strong_count(x),
+            Node::Fields(x) => Rc::strong_count(x),
+            Node::Upvalue(x) => Rc::strong_count(x),
+            Node::Function(x) => Rc::strong_count(x),
+            Node::Struct(x) => Rc::strong_count(x),
+            Node::Object(x) => Rc::strong_count(x),
+            Node::Variant(x) => Rc::strong_count(x),
+            Node::VariantValues(x) => Rc::strong_count(x),
+        }
+    }
+
+    /// Calls `f` with every node this one holds a reference to, or returns false if it's
+    /// borrowed right now and can't be looked into.
+    fn for_each_child(&self, f: &mut dyn FnMut(Node)) -> bool {
+        fn each<'a>(values: impl Iterator<Item = &'a Value>, f: &mut dyn FnMut(Node)) {
+            values.filter_map(Node::of).for_each(f);
+        }
+
+        match self {
+            Node::Items(x) => match x.try_borrow() {
+                Ok(items) => each(items.iter(), f),
+                Err(_) => return false,
+            },
+            Node::Dict(x) => match x.try_borrow() {
+                Ok(map) => each(map.iter().flat_map(|(k, v)| [k, v]), f),
+                Err(_) => return false,
+            },
+            Node::Fields(x) => match x.try_borrow() {
+                Ok(fields) => each(fields.values(), f),
+                Err(_) => return false,
+            },
+            Node::Upvalue(x) => match x.try_borrow() {
+                Ok(upvalue) => {
+                    if let Upvalue::Closed(value) = &*upvalue {
+                        each(std::iter::once(value), f);
+                    }
+                }
+                Err(_) => return false,
+            },
+            Node::Function(x) => {
+                x.upvalues.iter().for_each(|cell| f(Node::Upvalue(Rc::clone(cell))));
+                each(x.this.iter().map(|this| &**this), f);
+            }
+            Node::Struct(x) => f(Node::Fields(Rc::clone(&x.values))),
+            Node::Object(x) => f(Node::Fields(Rc::clone(&x.values))),
+            Node::Variant(x) => f(Node::VariantValues(Rc::clone(&x.values))),
+            Node::VariantValues(x) => each(x.iter(), f),
+        }
+
+        true
+    }
+
+    /// Empties a tracked cell into `contents`, to be dropped once the collection is done.
+    fn clear(&self, contents: &mut Vec<Value>) {
+        match self {
+            Node::Items(x) => {
+                if let Ok(mut items) = x.try_borrow_mut() {
+                    contents.append(&mut items);
+                }
+            }
+            Node::Dict(x) => {
+                if let Ok(mut map) = x.try_borrow_mut() {
+                    contents.extend(map.drain().flat_map(|(k, v)| [k, v]));
+                }
+            }
+            Node::Fields(x) => {
+                if let Ok(mut fields) = x.try_borrow_mut() {
+                    contents.extend(fields.drain().map(|(_, v)| v));
+                }
+            }
+            Node::Upvalue(x) => {
+                if let Ok(mut upvalue) = x.try_borrow_mut()
+                    && let Upvalue::Closed(value) = std::mem::replace(&mut *upvalue, Upvalue::Closed(Value::NIL))
+                {
+                    contents.push(value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn is_tracked(&self) -> bool {
+        matches!(self, Node::Items(_) | Node::Dict(_) | Node::Fields(_) | Node::Upvalue(_))
+    }
+}
+
+struct ScanNode {
+    node: Node,
+    /// References to the node from anywhere, not counting the scan's own
+    references: usize,
+    /// References to the node from other nodes
+    internal: usize,
+    children: Vec<usize>,
+    /// Whether it could be looked into, a node borrowed in the middle of an instruction can't
+    traced: bool,
+}
+
+#[derive(Default)]
+struct Scan {
+    nodes: Vec<ScanNode>,
+    index: HashMap<usize, usize>,
+}
+
+impl Scan {
+    /// The index of `node`, which has to be the scan's only reference to it if it's new.
+    fn discover(&mut self, node: Node) -> usize {
+        let addr = node.addr();
+        if let Some(&i) = self.index.get(&addr) {
+            return i;
+        }
+
+        self.nodes.push(ScanNode {
+            references: node.strong_count() - 1,
+            node,
+            internal: 0,
+            children: vec![],
+            traced: false,
+        });
+        self.index.insert(addr, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    /// Follows every node from the tracked cells on, counting how often each one is referenced
+    /// from the others.
+    fn count_references(&mut self) {
+        let mut i = 0;
+
+        while i < self.nodes.len() {
+            let node = self.nodes[i].node.clone();
+            let mut children = vec![];
+
+            // Each child is discovered before the next one is cloned, so a new node's count
+            // only ever includes the one clone being looked at
+            let traced = node.for_each_child(&mut |child| {
+                let child = self.discover(child);
+                self.nodes[child].internal += 1;
+                children.push(child);
+            });
+
+            self.nodes[i].children = children;
+            self.nodes[i].traced = traced;
+            i += 1;
+        }
+    }
+
+    /// The tracked cells nothing outside of the heap can reach.
+    fn unreachable(&self) -> Vec<usize> {
+        let mut reachable = vec![false; self.nodes.len()];
+        let mut stack: Vec<usize> = (0..self.nodes.len())
+            .filter(|&i| {
+                let node = &self.nodes[i];
+                !node.traced || node.references > node.internal
+            })
+            .collect();
+
+        while let Some(i) = stack.pop() {
+            if reachable[i] {
+                continue;
+            }
+            reachable[i] = true;
+            stack.extend(self.nodes[i].children.iter().filter(|&&child| !reachable[child]));
+        }
+
+        (0..self.nodes.len())
+            .filter(|&i| !reachable[i] && self.nodes[i].node.is_tracked())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::virtual_machine::{value::Value, vm::tests::eval};
+
+    #[test]
+    fn collects_list_closure_cycle() {
+        // The list holds the closure and the closure captures the list, so neither count
+        // drops to zero once `make` returns
+        let (_, result) = eval(
+            "fn make() {
+                let items = []
+                items.push(fn() { items.len() })
+            }
+            make()
+            Std::GC::collect()",
+        );
+
+        // The list and the captured variable
+        assert_eq!(result.unwrap(), Value::Number(2.0));
+    }
+}
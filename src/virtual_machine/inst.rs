@@ -2,6 +2,15 @@ use bincode::{Decode, Encode};
 
 use crate::virtual_machine::value::Value;
 
+/// Where a closure gets a captured variable from when it's created
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub enum Capture {
+    /// A slot of the frame creating it
+    Local(usize),
+    /// One of the upvalues of the function creating it
+    Upvalue(usize),
+}
+
 #[allow(unused, non_camel_case_types)]
 #[derive(Encode, Decode, Debug, Clone, PartialEq)]
 pub enum Inst {
@@ -26,6 +35,23 @@ pub enum Inst {
     DICT(usize),              // ✅
     ENUM(String, Vec<Value>), // ✅
     STRUCT(Vec<String>),      // ✅
+    CLASS {
+        name: String,
+        fields: Vec<String>,
+        methods: Vec<String>,
+        interfaces: usize,
+    }, // ✅
+    IMPL {
+        methods: Vec<String>,
+        statics: Vec<String>,
+    },
+    // Binds every member of the namespace on the stack as a global
+    IMPORT_ALL,
+    // Pops the values of `members` into the namespace of an imported file
+    MODULE {
+        name: String,
+        members: Vec<String>,
+    },
 
     PATCH_ME(String), // ✅
 
@@ -55,18 +81,18 @@ pub enum Inst {
     STORE_GLOBAL_CONST(u64), // ✅
     SET_VAR(u64),            // ✅
 
-    PUSH_SCOPE,                                  // ✅
-    POP_SCOPE,                                   // ✅
-    LOAD_LOCAL { id: u64, depth: usize },        // ✅
-    STORE_LOCAL { id: u64, depth: usize },       // ✅
-    STORE_LOCAL_CONST { id: u64, depth: usize }, // ✅
+    // Locals live in slots of the current frame
+    LOAD_LOCAL(usize),  // ✅
+    STORE_LOCAL(usize), // ✅
 
     // UpValues
-    MAKE_CLOSURE { entry: usize, captures: Vec<usize> }, // ✅
-    LOAD_UPVALUE { id: u64, scope_idx: usize },          // ✅
+    MAKE_CLOSURE { entry: usize, arity: usize, captures: Vec<Capture>, generator: bool }, // ✅
+    LOAD_UPVALUE(usize),   // ✅
+    STORE_UPVALUE(usize),  // ✅
+    CLOSE_UPVALUES(usize), // moves the variables closures captured from slot `n` on into their upvalues
 
-    // Load from local or global
-    LOAD(u64), // ✅
+    // Load from the namespaces `using path::*` imported into local slots, or from the globals
+    LOAD { name: String, namespaces: Vec<usize> }, // ✅
 
     JUMP(usize),            // ✅
     JUMP_IF_FALSE(usize),   // ✅
@@ -79,14 +105,36 @@ pub enum Inst {
 
     CALL(usize),      // ✅
     CALL_VOID(usize), // ✅
+    /// A `CALL` in tail position, which replaces the current frame instead of adding one.
+    /// `iterators` are the loops it returns out of. The `RETURN` after it runs when the
+    /// frame can't be reused (generators, `try`, calling a library function or a class, ...)
+    TAIL_CALL { args: usize, iterators: usize },
     RETURN,           // ✅
+    YIELD,            // ✅
+
+    // Type hints, `param` is `None` for the return value
+    CHECK_TYPE { hint: String, function: String, param: Option<String> }, // ✅
 
     // Get iterator (for loop)
     GET_ITER,        // ✅
     FOR_ITER(usize), // ✅
+    END_ITER,        // ✅
+
+    // Error handling
+    TRY(usize), // register a catch handler at the given address
+    END_TRY,
+    THROW,
 
     // Match statement
     MATCH,
+    MATCH_SEQ { tuple: bool, len: usize, rest: bool }, // is a list/tuple of `len` items (or more with `rest`)
+    MATCH_TYPE(String),                                // is a struct or object of the named type
+    MATCH_VARIANT(usize),                              // was built by the variant on top (with `n` positional fields)
+    MATCH_RANGE(bool),                                 // is a number in start..end (inclusive or not)
+    SLICE(usize, usize),                               // the list/tuple without `n` items at the start and `m` at the end
+
+    // Destructuring
+    UNPACK { len: usize, rest: bool }, // errors unless the value is a list/tuple of `len` items (or more with `rest`)
 
     // FString concatenation
     CONCAT_STR(usize),
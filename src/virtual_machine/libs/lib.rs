@@ -1,19 +1,29 @@
-use crate::virtual_machine::{value::Value, vm::VM};
+use crate::virtual_machine::{error::RuntimeResult, value::Value, vm::VM};
+
+pub type LibFunction = Box<dyn Fn(&mut VM, Vec<Value>) -> RuntimeResult>;
 
 pub trait Library {
     fn get_name(&self) -> &str;
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value>;
+    /// The function hashed to `name`, or `None` if the library has no such function
+    fn get_function(&self, name: u64) -> Option<LibFunction>;
 }
 
 #[macro_export]
 macro_rules! get_args {
 	($args:expr, $count: expr) => {{
-		let v: [Value; $count] = $args.try_into().expect(&format!("Expected {} arguments", $count));
+		let len = $args.len();
+		let v: [Value; $count] = match $args.try_into() {
+			Ok(v) => v,
+			Err(_) => return Err(runtime_error!(ArgumentError, "Expected {} arguments, got {len}", $count)),
+		};
 		v
 	}};
 
 	($args: expr) => {
-		&$args[0]
+		match $args.first() {
+			Some(v) => v,
+			None => return Err(runtime_error!(ArgumentError, "Expected an argument")),
+		}
 	}
-}
\ No newline at end of file
+}
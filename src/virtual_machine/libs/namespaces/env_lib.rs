@@ -0,0 +1,47 @@
+use std::cell::RefCell;
+
+use crate::virtual_machine::{
+    error::RuntimeResult,
+    libs::lib::{LibFunction, Library},
+    types::list::TList,
+    value::Value,
+    vm::VM,
+};
+
+pub struct EnvLib;
+
+impl EnvLib {
+    fn args(vm: &mut VM, _args: Vec<Value>) -> RuntimeResult {
+        let values = vm.script_args.iter().map(Value::string).collect();
+
+        Ok(Value::List(TList::new(rc!(RefCell::new(values)))))
+    }
+
+    fn var(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let name = &args[0];
+
+        if let Value::String(name) = name {
+            Ok(std::env::var(&*name.0)
+                .map(Value::string)
+                .unwrap_or(Value::NIL))
+        } else {
+            Err(runtime_error!(TypeError, "`Env.var()` expects a string name"))
+        }
+    }
+}
+
+// LIBRARY
+impl Library for EnvLib {
+    fn get_name(&self) -> &str {
+        "Env"
+    }
+
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
+            x if x == hash_u64!("args") => Box::new(Self::args),
+            x if x == hash_u64!("var") => Box::new(Self::var),
+
+            _ => return None,
+        })
+    }
+}
@@ -1,19 +1,26 @@
 use crate::{
     get_args,
-    virtual_machine::{libs::lib::Library, value::Value, vm::VM},
+    virtual_machine::{
+        error::RuntimeResult,
+        libs::lib::{LibFunction, Library},
+        value::Value,
+        vm::VM,
+    },
 };
 use std::fs;
 
 pub struct FSLib;
 
 impl FSLib {
-    fn read(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn read(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let path = get_args!(args);
 
         if let Value::String(path) = path {
-            Value::string(fs::read_to_string(&*path.0).expect("Couldn't read file."))
+            fs::read_to_string(&*path.0)
+                .map(Value::string)
+                .map_err(|e| runtime_error!(IOError, "Couldn't read file `{}`: {e}", path.0))
         } else {
-            panic!("`FS.read()` expects a string path")
+            Err(runtime_error!(TypeError, "`FS.read()` expects a string path"))
         }
     }
 }
@@ -24,12 +31,12 @@ impl Library for FSLib {
         "FS"
     }
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value> {
-        match name {
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
             // INPUT
             x if x == hash_u64!("read") => Box::new(Self::read),
 
-            _ => panic!("Unknown function `{name}` on lib {}", self.get_name()),
-        }
+            _ => return None,
+        })
     }
 }
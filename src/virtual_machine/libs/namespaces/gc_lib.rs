@@ -0,0 +1,45 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::virtual_machine::{
+    error::RuntimeResult,
+    gc,
+    libs::lib::{LibFunction, Library},
+    types::dict::TDict,
+    value::Value,
+    vm::VM,
+};
+
+pub struct GCLib;
+
+impl GCLib {
+    /// Frees the values only kept alive by cycles, returning how many lists, dicts, structs,
+    /// objects and captured variables that was
+    fn collect(_vm: &mut VM, _args: Vec<Value>) -> RuntimeResult {
+        Ok(Value::Number(gc::collect() as f64))
+    }
+
+    fn stats(_vm: &mut VM, _args: Vec<Value>) -> RuntimeResult {
+        let stats = gc::stats();
+        Ok(Value::Dict(TDict::new(rc!(RefCell::new(HashMap::from([
+            (Value::string("collections"), Value::Number(stats.collections as f64)),
+            (Value::string("freed"), Value::Number(stats.freed as f64)),
+            (Value::string("tracked"), Value::Number(stats.tracked as f64)),
+        ]))))))
+    }
+}
+
+// LIBRARY
+impl Library for GCLib {
+    fn get_name(&self) -> &str {
+        "GC"
+    }
+
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
+            x if x == hash_u64!("collect") => Box::new(Self::collect),
+            x if x == hash_u64!("stats") => Box::new(Self::stats),
+
+            _ => return None,
+        })
+    }
+}
@@ -1,14 +1,18 @@
 use std::io::{Write, stdin, stdout};
 
-use crate::{
-    virtual_machine::{libs::lib::Library, types::string::TString, value::Value, vm::VM},
+use crate::virtual_machine::{
+    error::RuntimeResult,
+    libs::lib::{LibFunction, Library},
+    types::string::TString,
+    value::Value,
+    vm::VM,
 };
 
 pub struct IOLib;
 
 impl IOLib {
     // Input
-    fn read_line(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn read_line(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let msg = args
             .iter()
             .map(|x| x.to_string(false))
@@ -22,12 +26,12 @@ impl IOLib {
         let mut buf = String::new();
         stdin()
             .read_line(&mut buf)
-            .expect("Couldn't read_line() from console");
+            .map_err(|e| runtime_error!(IOError, "Couldn't read_line() from console: {e}"))?;
 
-        Value::String(TString::from_str(buf.trim()))
+        Ok(Value::String(TString::from_str(buf.trim())))
     }
 
-    fn read_line_raw(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn read_line_raw(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let msg = args
             .iter()
             .map(|x| x.to_string(false))
@@ -41,49 +45,49 @@ impl IOLib {
         let mut buf = String::new();
         stdin()
             .read_line(&mut buf)
-            .expect("Couldn't read_line_raw() from console");
+            .map_err(|e| runtime_error!(IOError, "Couldn't read_line_raw() from console: {e}"))?;
 
-        Value::String(TString::new(buf))
+        Ok(Value::String(TString::new(buf)))
     }
 
 	// Output
-	fn clear(_vm: &mut VM, _args: Vec<Value>) -> Value {
+	fn clear(_vm: &mut VM, _args: Vec<Value>) -> RuntimeResult {
 		print!("\x1B[2J\x1B[1;1H");
 
-		Value::NIL
+		Ok(Value::NIL)
 	}
 
-	fn reset(_vm: &mut VM, _args: Vec<Value>) -> Value {
+	fn reset(_vm: &mut VM, _args: Vec<Value>) -> RuntimeResult {
 		print!("{esc}c", esc = 27 as char);
 
-		Value::NIL
+		Ok(Value::NIL)
 	}
 
-	fn write(_vm: &mut VM, args: Vec<Value>) -> Value {
+	fn write(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let msg = args
             .iter()
-            .map(|x| x.to_string(false))
-			.rev()
-            .collect::<Vec<_>>()
+            .rev()
+            .map(|x| vm.stringify(x))
+            .collect::<RuntimeResult<Vec<_>>>()?
             .join(" ");
 
         print!("{msg}");
         let _ = stdout().flush();
 
-		Value::NIL
+		Ok(Value::NIL)
 	}
 
-	fn write_line(_vm: &mut VM, args: Vec<Value>) -> Value {
+	fn write_line(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let msg = args
             .iter()
-            .map(|x| x.to_string(false))
-			.rev()
-            .collect::<Vec<_>>()
+            .rev()
+            .map(|x| vm.stringify(x))
+            .collect::<RuntimeResult<Vec<_>>>()?
             .join(" ");
 
         println!("{msg}");
 
-		Value::NIL
+		Ok(Value::NIL)
 	}
 }
 
@@ -93,8 +97,8 @@ impl Library for IOLib {
         "IO"
     }
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value> {
-        match name {
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
             // INPUT
             x if x == hash_u64!("read_line") => Box::new(Self::read_line),
             x if x == hash_u64!("read_line_raw") => Box::new(Self::read_line_raw),
@@ -105,7 +109,7 @@ impl Library for IOLib {
             x if x == hash_u64!("write") => Box::new(Self::write),
             x if x == hash_u64!("write_line") => Box::new(Self::write_line),
 
-            _ => panic!("Unknown function `{name}` on lib {}", self.get_name()),
-        }
+            _ => return None,
+        })
     }
 }
@@ -1,3 +1,5 @@
+pub mod env_lib;
 pub mod fs_lib;
+pub mod gc_lib;
 pub mod io_lib;
 pub mod math_lib;
@@ -1,71 +1,100 @@
 use crate::{
     get_args,
-    virtual_machine::{libs::lib::Library, types::string::TString, value::Value, vm::VM},
+    virtual_machine::{
+        error::RuntimeResult,
+        libs::lib::{LibFunction, Library},
+        types::string::TString,
+        value::Value,
+        vm::VM,
+    },
 };
 
 pub struct TypeLib;
 
 impl TypeLib {
-    pub fn r#typeof(_vm: &mut VM, args: Vec<Value>) -> Value {
+    pub fn r#typeof(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let value = get_args!(args);
 
-        Value::String(TString::new(value.get_type()))
+        Ok(Value::String(TString::new(value.get_type())))
+    }
+
+    pub fn implements(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let [interface, value] = get_args!(args, 2);
+
+        let Value::Interface(interface) = interface else {
+            return Err(runtime_error!(
+                TypeError,
+                "Expected an interface, got `{}`",
+                interface.get_type()
+            ));
+        };
+
+        Ok(Value::Bool(match value {
+            Value::Object(obj) => obj.class.implements(&interface),
+            Value::Class(class) => class.implements(&interface),
+            _ => false,
+        }))
     }
 
     // TYPES
 
-    pub fn string(_vm: &mut VM, args: Vec<Value>) -> Value {
+    pub fn string(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let value = get_args!(args);
 
         if matches!(value, Value::String(_)) {
-            value.clone()
+            Ok(value.clone())
         } else {
-            Value::string(value)
+            Ok(Value::string(vm.stringify(value)?))
         }
     }
 
-    pub fn number(_vm: &mut VM, args: Vec<Value>) -> Value {
+    pub fn number(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let value = get_args!(args);
 
         if let Value::String(x) = value {
-            Value::Number(
+            Ok(Value::Number(
                 x.0.parse::<f64>()
-                    .expect("Failed to convert string to number"),
-            )
+                    .map_err(|_| runtime_error!(ValueError, "Failed to convert {x:?} to number"))?,
+            ))
         } else if let Value::Char(x) = value {
-            Value::Number(*x as u64 as f64)
+            Ok(Value::Number(*x as u64 as f64))
         } else if let Value::Bool(x) = value {
-            Value::Number(*x as u64 as f64)
+            Ok(Value::Number(*x as u64 as f64))
         } else if let Value::NIL = value {
-            Value::Number(0.0)
+            Ok(Value::Number(0.0))
         } else {
-            Value::Number(value.as_number())
+            Ok(Value::Number(value.as_number()?))
         }
     }
 
-    pub fn bool(_vm: &mut VM, args: Vec<Value>) -> Value {
+    pub fn bool(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let value = get_args!(args);
 
         if let Value::Number(x) = value
             && *x == 0.0
         {
-            Value::Bool(false)
+            Ok(Value::Bool(false))
         } else {
-            Value::Bool(value.is_truthy())
+            Ok(Value::Bool(value.is_truthy()))
         }
     }
 
-    pub fn char(_vm: &mut VM, args: Vec<Value>) -> Value {
+    pub fn char(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let value = get_args!(args);
 
         if let Value::Number(x) = value {
-            Value::Char(char::from_u32(*x as u32).unwrap())
+            char::from_u32(*x as u32)
+                .map(Value::Char)
+                .ok_or_else(|| runtime_error!(ValueError, "{x} is not a valid char code"))
         } else if matches!(value, Value::Char(_)) {
-            value.clone()
+            Ok(value.clone())
         } else if let Value::String(v) = value {
-            Value::Char(v.0.chars().nth(0).unwrap())
+            v.0.chars()
+                .nth(0)
+                .map(Value::Char)
+                .ok_or_else(|| runtime_error!(ValueError, "Cannot convert an empty string to char"))
         } else {
-            panic!("Cannot convert {value:?} to char");
+            Err(runtime_error!(ValueError, "Cannot convert {value:?} to char"))
         }
     }
 }
@@ -75,16 +104,17 @@ impl Library for TypeLib {
         "type"
     }
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value> {
-        match name {
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
             name if hash_u64!("typeof") == name => Box::new(Self::r#typeof),
+            name if hash_u64!("implements") == name => Box::new(Self::implements),
 
             name if hash_u64!("string") == name => Box::new(Self::r#string),
             name if hash_u64!("number") == name => Box::new(Self::r#number),
             name if hash_u64!("bool") == name => Box::new(Self::r#bool),
             name if hash_u64!("char") == name => Box::new(Self::r#char),
 
-            _ => panic!("Unknown type lib function"),
-        }
+            _ => return None,
+        })
     }
 }
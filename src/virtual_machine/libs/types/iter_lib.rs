@@ -0,0 +1,147 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    get_args,
+    virtual_machine::{
+        error::RuntimeResult,
+        libs::lib::{LibFunction, Library},
+        types::{
+            function::TFunction,
+            iterator::{IteratorState, TIterator},
+            list::TList,
+        },
+        value::Value,
+        vm::VM,
+    },
+};
+
+pub const ITER_FUNCTIONS: [&str; 8] = [
+    "next", "map", "filter", "zip", "take", "enumerate", "chain", "collect",
+];
+
+pub struct IterLib;
+
+impl IterLib {
+    /// `iter(value)`, an iterator over anything a `for` loop can go through
+    fn iter(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let value = get_args!(args);
+
+        Ok(Value::Iterator(TIterator::from_value(vm, value.clone())?))
+    }
+
+    /// The next item, or `Done` once there are none left
+    fn next(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let iterator = Self::this(&args, "next")?;
+
+        Ok(iterator.next(vm)?.unwrap_or(Value::Done))
+    }
+
+    fn map(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let [iterator, function] = get_args!(args, 2);
+
+        Ok(Value::Iterator(TIterator::new(IteratorState::Map {
+            source: Self::this(&[iterator], "map")?,
+            function: Self::function(function, "map")?,
+        })))
+    }
+
+    fn filter(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let [iterator, function] = get_args!(args, 2);
+
+        Ok(Value::Iterator(TIterator::new(IteratorState::Filter {
+            source: Self::this(&[iterator], "filter")?,
+            function: Self::function(function, "filter")?,
+        })))
+    }
+
+    fn zip(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        Ok(Value::Iterator(TIterator::new(IteratorState::Zip(
+            Self::sources(vm, args, "zip")?,
+        ))))
+    }
+
+    fn take(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let [iterator, count] = get_args!(args, 2);
+
+        Ok(Value::Iterator(TIterator::new(IteratorState::Take {
+            source: Self::this(&[iterator], "take")?,
+            remaining: count.as_number()?.max(0.0) as usize,
+        })))
+    }
+
+    fn enumerate(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        Ok(Value::Iterator(TIterator::new(IteratorState::Enumerate {
+            source: Self::this(&args, "enumerate")?,
+            index: 0,
+        })))
+    }
+
+    fn chain(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        Ok(Value::Iterator(TIterator::new(IteratorState::Chain {
+            sources: Self::sources(vm, args, "chain")?,
+            current: 0,
+        })))
+    }
+
+    fn collect(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let iterator = Self::this(&args, "collect")?;
+
+        Ok(Value::List(TList::new(rc!(RefCell::new(
+            iterator.collect(vm)?
+        )))))
+    }
+
+    // HELPERS
+
+    fn this(args: &[Value], name: &str) -> RuntimeResult<TIterator> {
+        match args.first() {
+            Some(Value::Iterator(iterator)) => Ok(iterator.clone()),
+            _ => Err(runtime_error!(
+                TypeError,
+                "Can only use iter.{name} on Iterators"
+            )),
+        }
+    }
+
+    fn function(value: Value, name: &str) -> RuntimeResult<Rc<TFunction>> {
+        match value {
+            Value::Function(f) => Ok(f),
+            _ => Err(runtime_error!(TypeError, "Expected function in iter.{name}")),
+        }
+    }
+
+    /// The iterator a method was called on followed by the iterables passed to it
+    fn sources(vm: &mut VM, mut args: Vec<Value>, name: &str) -> RuntimeResult<Vec<TIterator>> {
+        let mut sources = vec![Self::this(&args, name)?];
+
+        // Arguments come after `this` in reverse
+        args.remove(0);
+        for value in args.into_iter().rev() {
+            sources.push(TIterator::from_value(vm, value)?);
+        }
+
+        Ok(sources)
+    }
+}
+
+impl Library for IterLib {
+    fn get_name(&self) -> &str {
+        "iter"
+    }
+
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
+            x if x == hash_u64!("iter") => Box::new(Self::iter),
+            x if x == hash_u64!("next") => Box::new(Self::next),
+            x if x == hash_u64!("map") => Box::new(Self::map),
+            x if x == hash_u64!("filter") => Box::new(Self::filter),
+            x if x == hash_u64!("zip") => Box::new(Self::zip),
+            x if x == hash_u64!("take") => Box::new(Self::take),
+            x if x == hash_u64!("enumerate") => Box::new(Self::enumerate),
+            x if x == hash_u64!("chain") => Box::new(Self::chain),
+            x if x == hash_u64!("collect") => Box::new(Self::collect),
+
+            _ => return None,
+        })
+    }
+}
@@ -1,115 +1,184 @@
 use crate::{
     get_args,
     misc::to_index::to_index,
-    virtual_machine::{libs::lib::Library, types::list::TList, value::Value, vm::VM},
+    virtual_machine::{
+        error::RuntimeResult,
+        libs::lib::{LibFunction, Library},
+        types::{
+            iterator::{IteratorState, TIterator},
+            list::TList,
+        },
+        value::Value,
+        vm::VM,
+    },
 };
 use std::{cell::RefCell, cmp::Ordering};
 
-pub const LIST_FUNCTIONS: [&str; 16] = [
+pub const LIST_FUNCTIONS: [&str; 21] = [
     "len", "push", "insert", "remove", "map", "pop", "clear", "append", "concat", "copy", "count",
-    "sort", "reverse", "fill", "rep", "push_n",
+    "sort", "reverse", "fill", "rep", "push_n", "enumerate", "filter", "take", "collect",
+    "iter",
 ];
 
 pub struct ListLib;
 
 impl ListLib {
-    fn len(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn len(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let list = get_args!(args);
 
         if let Value::List(inner) = list {
-            Value::Number(inner.values.borrow().len() as f64)
+            Ok(Value::Number(inner.values.borrow().len() as f64))
         } else {
-            panic!("Can only use list.len on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.len on Lists"))
         }
     }
 
-    fn push(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn push(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [list, new_value] = get_args!(args, 2);
 
         if let Value::List(x) = list {
             x.values.borrow_mut().push(new_value);
         } else {
-            panic!("Can only use list.push on Lists")
+            return Err(runtime_error!(TypeError, "Can only use list.push on Lists"))
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn insert(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn insert(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [list, new_value, index] = get_args!(args, 3);
 
         if let Value::List(x) = list {
             x.values
                 .borrow_mut()
-                .insert(index.as_number() as usize, new_value);
+                .insert(index.as_number()? as usize, new_value);
         } else {
-            panic!("Can only use list.insert on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.insert on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn remove(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn remove(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [index, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
             if let Value::Number(idx) = index {
-                let target_index = to_index(idx, inner.values.borrow().len());
-                return inner.values.borrow_mut().remove(target_index);
+                let target_index = to_index(idx, inner.values.borrow().len())?;
+                return Ok(inner.values.borrow_mut().remove(target_index));
             } else {
-                panic!("Expected number as index in list.remove");
+                Err(runtime_error!(TypeError, "Expected number as index in list.remove"))
             }
         } else {
-            panic!("Can only use list.remove on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.remove on Lists"))
         }
     }
 
-    fn pop(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn pop(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let list = get_args!(args);
 
         if let Value::List(inner) = list {
-            inner.values.borrow_mut().pop().unwrap_or(Value::NIL)
+            Ok(inner.values.borrow_mut().pop().unwrap_or(Value::NIL))
         } else {
-            panic!("Can only use list.pop on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.pop on Lists"))
         }
     }
 
-    fn clear(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn clear(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let list = get_args!(args);
 
         if let Value::List(inner) = list {
             inner.values.borrow_mut().clear();
         } else {
-            panic!("Can only use list.clear on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.clear on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn map(vm: &mut VM, args: Vec<Value>) -> Value {
+    fn map(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [list, func] = get_args!(args, 2);
         let mut new_array = vec![];
 
         if let Value::List(inner) = list {
             if let Value::Function(f) = func {
-                for i in inner.values.borrow().iter() {
-                    vm.stack.push(i.clone());
-                    vm.call_function(f.clone(), 1);
-                    vm.run(false, true);
-                    let new_value = vm.pop();
-                    new_array.push(new_value);
+                let values = inner.values.borrow().clone();
+                for i in values {
+                    new_array.push(vm.call_value(f.clone(), vec![i])?);
                 }
 
-                return Value::List(TList::new(rc!(RefCell::new(new_array))));
+                Ok(Value::List(TList::new(rc!(RefCell::new(new_array)))))
             } else {
-                panic!("Expected function in list.map");
+                Err(runtime_error!(TypeError, "Expected function in list.map"))
             }
         } else {
-            panic!("Can only use list.map on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.map on Lists"))
         }
     }
 
-    fn append(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn filter(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let [list, func] = get_args!(args, 2);
+        let mut new_array = vec![];
+
+        if let Value::List(inner) = list {
+            if let Value::Function(f) = func {
+                let values = inner.values.borrow().clone();
+                for i in values {
+                    if vm.call_value(f.clone(), vec![i.clone()])?.is_truthy() {
+                        new_array.push(i);
+                    }
+                }
+
+                Ok(Value::List(TList::new(rc!(RefCell::new(new_array)))))
+            } else {
+                Err(runtime_error!(TypeError, "Expected function in list.filter"))
+            }
+        } else {
+            Err(runtime_error!(TypeError, "Can only use list.filter on Lists"))
+        }
+    }
+
+    fn take(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let [list, count] = get_args!(args, 2);
+
+        if let Value::List(inner) = list {
+            let count = count.as_number()?.max(0.0) as usize;
+            let values = inner.values.borrow().iter().take(count).cloned().collect();
+
+            Ok(Value::List(TList::new(rc!(RefCell::new(values)))))
+        } else {
+            Err(runtime_error!(TypeError, "Can only use list.take on Lists"))
+        }
+    }
+
+    /// A lazy iterator over the items, to chain adapters without building a list at each step
+    fn iter(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let list = get_args!(args);
+
+        if let Value::List(inner) = list {
+            Ok(Value::Iterator(TIterator::new(IteratorState::Sequence {
+                values: inner.values.clone(),
+                index: 0,
+            })))
+        } else {
+            Err(runtime_error!(TypeError, "Can only use list.iter on Lists"))
+        }
+    }
+
+    /// A new list with the same items, so `collect()` ends a pipeline whether or not it
+    /// started from a list
+    fn collect(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let list = get_args!(args);
+
+        if let Value::List(inner) = list {
+            let values = inner.values.borrow().clone();
+            Ok(Value::List(TList::new(rc!(RefCell::new(values)))))
+        } else {
+            Err(runtime_error!(TypeError, "Can only use list.collect on Lists"))
+        }
+    }
+
+    fn append(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [other, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
@@ -119,16 +188,19 @@ impl ListLib {
                     .borrow_mut()
                     .extend(other_inner.values.borrow().clone());
             } else {
-                panic!("Can only `append` another List/Tuple on a List")
+                return Err(runtime_error!(
+                    TypeError,
+                    "Can only `append` another List/Tuple on a List"
+                ))
             }
         } else {
-            panic!("Can only use list.append on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.append on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn concat(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn concat(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [other, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
@@ -139,37 +211,63 @@ impl ListLib {
                 ]
                 .concat();
 
-                return Value::List(TList::new(rc!(RefCell::new(new_values))));
+                Ok(Value::List(TList::new(rc!(RefCell::new(new_values)))))
             } else {
-                panic!("Can only `concat` another List/Tuple with a List")
+                Err(runtime_error!(
+                    TypeError,
+                    "Can only `concat` another List/Tuple with a List"
+                ))
             }
         } else {
-            panic!("Can only use list.concat on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.concat on Lists"))
         }
     }
 
-    fn copy(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn copy(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let list = get_args!(args);
 
         if let Value::List(inner) = list {
-            Value::List(inner.clone())
+            Ok(Value::List(inner.clone()))
         } else {
-            panic!("Can only use list.copy on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.copy on Lists"))
         }
     }
 
-    fn count(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn enumerate(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
+        let list = get_args!(args);
+
+        if let Value::List(inner) = list {
+            Ok(Value::List(TList::new(rc!(RefCell::new(
+                inner
+                    .values
+                    .borrow()
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| {
+                        Value::Tuple(TList::new_tuple(rc!(RefCell::new(vec![
+                            Value::Number(i as f64),
+                            x.clone()
+                        ]))))
+                    })
+                    .collect::<Vec<_>>()
+            )))))
+        } else {
+            Err(runtime_error!(TypeError, "Can only use list.enumerate on Lists"))
+        }
+    }
+
+    fn count(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [item, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
             let count = inner.values.borrow().iter().filter(|x| x == &&item).count();
-            Value::Number(count as f64)
+            Ok(Value::Number(count as f64))
         } else {
-            panic!("Can only use list.count on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.count on Lists"))
         }
     }
 
-    fn sort(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn sort(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let list = get_args!(args);
 
         if let Value::List(inner) = list {
@@ -178,57 +276,57 @@ impl ListLib {
                 .borrow_mut()
                 .sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
         } else {
-            panic!("Can only use list.sort on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.sort on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn reverse(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn reverse(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let list = get_args!(args);
 
         if let Value::List(inner) = list {
             inner.values.borrow_mut().reverse();
         } else {
-            panic!("Can only use list.reverse on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.reverse on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn fill(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn fill(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [value, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
             inner.values.borrow_mut().fill(value);
         } else {
-            panic!("Can only use list.fill on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.fill on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn rep(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn rep(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [value, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
             if let Value::Number(n) = value {
-                return Value::List(TList::new(rc!(RefCell::new(
+                return Ok(Value::List(TList::new(rc!(RefCell::new(
                     std::iter::repeat(&*inner.values.borrow())
                         .take(n as usize)
                         .flatten()
                         .cloned()
                         .collect()
-                ))));
+                )))));
             } else {
-                panic!("Can only list.repeat with a number")
+                Err(runtime_error!(TypeError, "Can only list.repeat with a number"))
             }
         } else {
-            panic!("Can only use list.repeat on Lists");
+            Err(runtime_error!(TypeError, "Can only use list.repeat on Lists"))
         }
     }
 
-    fn push_n(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn push_n(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [list, value, num] = get_args!(args, 3);
 
         if let Value::List(inner) = list {
@@ -238,13 +336,16 @@ impl ListLib {
                     .borrow_mut()
                     .extend((0..n as usize).map(|_| value.clone()));
             } else {
-                panic!("list.push_n requires a `number` of times as first argument")
+                return Err(runtime_error!(
+                    TypeError,
+                    "list.push_n requires a `number` of times as first argument"
+                ))
             }
         } else {
-            panic!("Can only use list.push_n on Lists");
+            return Err(runtime_error!(TypeError, "Can only use list.push_n on Lists"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 }
 
@@ -254,26 +355,31 @@ impl Library for ListLib {
         "list"
     }
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value> {
-        match name {
-            x if x == hash_u64!("len") => return Box::new(Self::len),
-            x if x == hash_u64!("push") => return Box::new(Self::push),
-            x if x == hash_u64!("insert") => return Box::new(Self::insert),
-            x if x == hash_u64!("remove") => return Box::new(Self::remove),
-            x if x == hash_u64!("map") => return Box::new(Self::map),
-            x if x == hash_u64!("pop") => return Box::new(Self::pop),
-            x if x == hash_u64!("clear") => return Box::new(Self::clear),
-            x if x == hash_u64!("append") => return Box::new(Self::append),
-            x if x == hash_u64!("concat") => return Box::new(Self::concat),
-            x if x == hash_u64!("copy") => return Box::new(Self::copy),
-            x if x == hash_u64!("count") => return Box::new(Self::count),
-            x if x == hash_u64!("sort") => return Box::new(Self::sort),
-            x if x == hash_u64!("reverse") => return Box::new(Self::reverse),
-            x if x == hash_u64!("fill") => return Box::new(Self::fill),
-            x if x == hash_u64!("rep") => return Box::new(Self::rep),
-            x if x == hash_u64!("push_n") => return Box::new(Self::push_n),
-
-            _ => panic!("Unknown function `{name}` on lib {}", self.get_name()),
-        }
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
+            x if x == hash_u64!("len") => Box::new(Self::len),
+            x if x == hash_u64!("push") => Box::new(Self::push),
+            x if x == hash_u64!("insert") => Box::new(Self::insert),
+            x if x == hash_u64!("remove") => Box::new(Self::remove),
+            x if x == hash_u64!("map") => Box::new(Self::map),
+            x if x == hash_u64!("pop") => Box::new(Self::pop),
+            x if x == hash_u64!("clear") => Box::new(Self::clear),
+            x if x == hash_u64!("append") => Box::new(Self::append),
+            x if x == hash_u64!("concat") => Box::new(Self::concat),
+            x if x == hash_u64!("copy") => Box::new(Self::copy),
+            x if x == hash_u64!("count") => Box::new(Self::count),
+            x if x == hash_u64!("sort") => Box::new(Self::sort),
+            x if x == hash_u64!("reverse") => Box::new(Self::reverse),
+            x if x == hash_u64!("fill") => Box::new(Self::fill),
+            x if x == hash_u64!("rep") => Box::new(Self::rep),
+            x if x == hash_u64!("push_n") => Box::new(Self::push_n),
+            x if x == hash_u64!("enumerate") => Box::new(Self::enumerate),
+            x if x == hash_u64!("filter") => Box::new(Self::filter),
+            x if x == hash_u64!("take") => Box::new(Self::take),
+            x if x == hash_u64!("collect") => Box::new(Self::collect),
+            x if x == hash_u64!("iter") => Box::new(Self::iter),
+
+            _ => return None,
+        })
     }
 }
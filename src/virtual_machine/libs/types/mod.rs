@@ -1,4 +1,5 @@
 pub mod dict_lib;
+pub mod iter_lib;
 pub mod list_lib;
 pub mod string_lib;
 pub mod tuple_lib;
@@ -1,6 +1,7 @@
 use crate::{
     get_args, virtual_machine::{
-        libs::lib::Library,
+        error::RuntimeResult,
+        libs::lib::{LibFunction, Library},
         types::{list::TList, string::TString},
         value::Value,
         vm::VM,
@@ -45,22 +46,22 @@ pub const STRING_FUNCTIONS: [&str; 31] = [
 pub struct StringLib;
 
 impl StringLib {
-    fn len(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn len(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Number(inner.0.len() as f64)
+            Ok(Value::Number(inner.0.len() as f64))
         } else {
-            panic!("Can only use string.len on strings");
+            Err(runtime_error!(TypeError, "Can only use string.len on strings"))
         }
     }
 
-    fn join(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn join(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, list] = get_args!(args, 2);
 
         if let Value::List(inner) = list {
             if let Value::String(t) = string {
-                Value::String(TString::new(
+                Ok(Value::String(TString::new(
                     inner
                         .values
                         .borrow()
@@ -68,249 +69,252 @@ impl StringLib {
                         .map(|x| x.to_string(false))
                         .collect::<Vec<_>>()
                         .join(&t.0),
-                ))
+                )))
             } else {
-                panic!("Expected string for separator");
+                Err(runtime_error!(TypeError, "Expected string for separator"))
             }
         } else {
-            panic!("Can only use string.concat on a list");
+            Err(runtime_error!(TypeError, "Can only use string.concat on a list"))
         }
     }
 
-    fn concat(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn concat(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, other] = get_args!(args, 2);
 
         if let Value::String(inner) = string {
-            return Value::String(TString::new(format!(
+            Ok(Value::String(TString::new(format!(
                 "{}{}",
                 inner.0,
                 other.to_string(false)
-            )));
+            ))))
         } else {
-            panic!("Can only use string.concat on strings");
+            Err(runtime_error!(TypeError, "Can only use string.concat on strings"))
         }
     }
 
-    fn copy(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn copy(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString(std::rc::Rc::from(&*inner.0)))
+            Ok(Value::String(TString::from_str(&inner.0)))
         } else {
-            panic!("Can only use string.copy on strings");
+            Err(runtime_error!(TypeError, "Can only use string.copy on strings"))
         }
     }
 
-    fn count(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn count(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, item] = get_args!(args, 2);
 
         if let Value::String(inner) = string {
             let count = inner.0.matches(&*item.to_string(false)).count();
-            Value::Number(count as f64)
+            Ok(Value::Number(count as f64))
         } else {
-            panic!("Can only use string.count on strings");
+            Err(runtime_error!(TypeError, "Can only use string.count on strings"))
         }
     }
 
-    fn reverse(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn reverse(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString::new(inner.0.chars().rev().collect::<String>()))
+            Ok(Value::String(TString::new(inner.0.chars().rev().collect::<String>())))
         } else {
-            panic!("Can only use string.reverse on strings");
+            Err(runtime_error!(TypeError, "Can only use string.reverse on strings"))
         }
     }
 
-    fn rep(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn rep(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, value] = get_args!(args, 2);
 
         if let Value::String(inner) = string {
             if let Value::Number(n) = value {
-                return Value::String(TString::new(inner.0.repeat(n as usize)));
+                Ok(Value::String(TString::new(inner.0.repeat(n as usize))))
             } else {
-                panic!("Can only string.repeat with a number")
+                Err(runtime_error!(TypeError, "Can only string.repeat with a number"))
             }
         } else {
-            panic!("Can only use string.repeat on strings");
+            Err(runtime_error!(TypeError, "Can only use string.repeat on strings"))
         }
     }
 
-    fn bytes(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn bytes(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            return Value::List(TList::new(rc!(RefCell::new(
+            Ok(Value::List(TList::new(rc!(RefCell::new(
                 inner
                     .0
                     .bytes()
                     .map(|x| Value::Number(x as f64))
                     .collect::<Vec<_>>()
-            ))));
+            )))))
         } else {
-            panic!("Can only use string.bytes on strings");
+            Err(runtime_error!(TypeError, "Can only use string.bytes on strings"))
         }
     }
 
-    fn chars(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn chars(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            return Value::List(TList::new(rc!(RefCell::new(
+            Ok(Value::List(TList::new(rc!(RefCell::new(
                 inner.0.chars().map(|x| Value::Char(x)).collect::<Vec<_>>()
-            ))));
+            )))))
         } else {
-            panic!("Can only use string.chars on strings");
+            Err(runtime_error!(TypeError, "Can only use string.chars on strings"))
         }
     }
 
-    fn split(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn split(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
         let new_value = args.get(1).unwrap_or(&Value::NIL);
 
         if let Value::String(inner) = string {
             if let Value::String(value) = new_value {
-                Value::List(TList::new(rc!(RefCell::new(
+                Ok(Value::List(TList::new(rc!(RefCell::new(
                     inner
                         .0
                         .split(&*value.0)
                         .map(|x: &str| Value::String(TString::from_str(x)))
                         .collect::<Vec<_>>()
-                ))))
+                )))))
             } else if let Value::Char(c) = new_value {
-                Value::List(TList::new(rc!(RefCell::new(
+                Ok(Value::List(TList::new(rc!(RefCell::new(
                     inner
                         .0
                         .split(*c)
                         .map(|x: &str| Value::String(TString::from_str(x)))
                         .collect::<Vec<_>>()
-                ))))
+                )))))
             } else if Value::NIL == *new_value {
-                Value::List(TList::new(rc!(RefCell::new(
+                Ok(Value::List(TList::new(rc!(RefCell::new(
                     inner
                         .0
                         .split(" ")
                         .map(|x: &str| Value::String(TString::from_str(x)))
                         .collect::<Vec<_>>()
-                ))))
+                )))))
             } else {
-                panic!("Can only split() a string value with a string separator")
+                Err(runtime_error!(
+                    TypeError,
+                    "Can only split() a string value with a string separator"
+                ))
             }
         } else {
-            panic!("Can only use string.split on strings")
+            Err(runtime_error!(TypeError, "Can only use string.split on strings"))
         }
     }
 
-    fn upper(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn upper(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString::new(inner.0.to_uppercase()))
+            Ok(Value::String(TString::new(inner.0.to_uppercase())))
         } else {
-            panic!("Can only use string.upper on strings");
+            Err(runtime_error!(TypeError, "Can only use string.upper on strings"))
         }
     }
 
-    fn lower(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn lower(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString::new(inner.0.to_lowercase()))
+            Ok(Value::String(TString::new(inner.0.to_lowercase())))
         } else {
-            panic!("Can only use string.lower on strings");
+            Err(runtime_error!(TypeError, "Can only use string.lower on strings"))
         }
     }
 
     // --- Trim ---
 
-    fn trim(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn trim(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString::new(inner.0.trim().to_string()))
+            Ok(Value::String(TString::new(inner.0.trim().to_string())))
         } else {
-            panic!("Can only use string.trim on strings");
+            Err(runtime_error!(TypeError, "Can only use string.trim on strings"))
         }
     }
 
-    fn ltrim(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn ltrim(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString::new(inner.0.trim_start().to_string()))
+            Ok(Value::String(TString::new(inner.0.trim_start().to_string())))
         } else {
-            panic!("Can only use string.ltrim on strings");
+            Err(runtime_error!(TypeError, "Can only use string.ltrim on strings"))
         }
     }
 
-    fn rtrim(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn rtrim(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::String(TString::new(inner.0.trim_end().to_string()))
+            Ok(Value::String(TString::new(inner.0.trim_end().to_string())))
         } else {
-            panic!("Can only use string.rtrim on strings");
+            Err(runtime_error!(TypeError, "Can only use string.rtrim on strings"))
         }
     }
 
     // --- Search & Replace ---
 
     /// replace(old, new) -> string
-    fn replace(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn replace(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, new_val, old_val] = get_args!(args, 3);
 
         if let Value::String(inner) = string {
             let old = old_val.to_string(false);
             let new = new_val.to_string(false);
-            Value::String(TString::new(inner.0.replace(&*old, &new)))
+            Ok(Value::String(TString::new(inner.0.replace(&*old, &new))))
         } else {
-            panic!("Can only use string.replace on strings");
+            Err(runtime_error!(TypeError, "Can only use string.replace on strings"))
         }
     }
 
     /// find(sub) -> number (index) or nil if not found
-    fn find(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn find(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, sub] = get_args!(args, 2);
 
         if let Value::String(inner) = string {
             let needle = sub.to_string(false);
             match inner.0.find(&*needle) {
-                Some(idx) => Value::Number(idx as f64),
-                None => Value::NIL,
+                Some(idx) => Ok(Value::Number(idx as f64)),
+                None => Ok(Value::NIL),
             }
         } else {
-            panic!("Can only use string.find on strings");
+            Err(runtime_error!(TypeError, "Can only use string.find on strings"))
         }
     }
 
     /// starts_with(prefix) -> bool
-    fn starts_with(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn starts_with(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, prefix] = get_args!(args, 2);
 
         if let Value::String(inner) = string {
             let p = prefix.to_string(false);
-            Value::Bool(inner.0.starts_with(&*p))
+            Ok(Value::Bool(inner.0.starts_with(&*p)))
         } else {
-            panic!("Can only use string.starts_with on strings");
+            Err(runtime_error!(TypeError, "Can only use string.starts_with on strings"))
         }
     }
 
     /// ends_with(suffix) -> bool
-    fn ends_with(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn ends_with(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, suffix] = get_args!(args, 2);
 
         if let Value::String(inner) = string {
             let s = suffix.to_string(false);
-            Value::Bool(inner.0.ends_with(&*s))
+            Ok(Value::Bool(inner.0.ends_with(&*s)))
         } else {
-            panic!("Can only use string.ends_with on strings");
+            Err(runtime_error!(TypeError, "Can only use string.ends_with on strings"))
         }
     }
 
     // --- Case Conversion ---
 
     /// title() -> string  (Title Case Every Word)
-    fn title(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn title(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
@@ -329,34 +333,39 @@ impl StringLib {
                 })
                 .collect::<Vec<_>>()
                 .join(" ");
-            Value::String(TString::new(titled))
+            Ok(Value::String(TString::new(titled)))
         } else {
-            panic!("Can only use string.title on strings");
+            Err(runtime_error!(TypeError, "Can only use string.title on strings"))
         }
     }
 
     // --- Padding / Alignment ---
 
     /// center(width) or center(width, fill_char) -> string
-    fn center(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn center(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, fill_val, width_val] = get_args!(args, 3);
 
         if let Value::String(inner) = string {
             let width = if let Value::Number(n) = width_val {
                 n as usize
             } else {
-                panic!("string.center: width must be a number");
+                return Err(runtime_error!(TypeError, "string.center: width must be a number"));
             };
 
             let fill = match fill_val {
                 Value::Char(c) => c,
                 Value::NIL => ' ',
-                _ => panic!("string.center: fill must be a char or nil"),
+                _ => {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "string.center: fill must be a char or nil"
+                    ));
+                }
             };
 
             let len = inner.0.chars().count();
             if len >= width {
-                return Value::String(inner.clone());
+                return Ok(Value::String(inner.clone()));
             }
             let total_pad = width - len;
             let left_pad = total_pad / 2;
@@ -367,157 +376,167 @@ impl StringLib {
                 inner.0,
                 fill.to_string().repeat(right_pad)
             );
-            Value::String(TString::new(result))
+            Ok(Value::String(TString::new(result)))
         } else {
-            panic!("Can only use string.center on strings");
+            Err(runtime_error!(TypeError, "Can only use string.center on strings"))
         }
     }
 
     /// ljust(width) or ljust(width, fill_char) -> string
-    fn ljust(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn ljust(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, fill_val, width_val] = get_args!(args, 3);
 
         if let Value::String(inner) = string {
             let width = if let Value::Number(n) = width_val {
                 n as usize
             } else {
-                panic!("string.ljust: width must be a number");
+                return Err(runtime_error!(TypeError, "string.ljust: width must be a number"));
             };
 
             let fill = match fill_val {
                 Value::Char(c) => c,
                 Value::NIL => ' ',
-                _ => panic!("string.ljust: fill must be a char or nil"),
+                _ => {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "string.ljust: fill must be a char or nil"
+                    ));
+                }
             };
 
             let len = inner.0.chars().count();
             if len >= width {
-                return Value::String(inner.clone());
+                return Ok(Value::String(inner.clone()));
             }
             let result = format!("{}{}", inner.0, fill.to_string().repeat(width - len));
-            Value::String(TString::new(result))
+            Ok(Value::String(TString::new(result)))
         } else {
-            panic!("Can only use string.ljust on strings");
+            Err(runtime_error!(TypeError, "Can only use string.ljust on strings"))
         }
     }
 
     /// rjust(width) or rjust(width, fill_char) -> string
-    fn rjust(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn rjust(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let [string, fill_val, width_val] = get_args!(args, 3);
 
         if let Value::String(inner) = string {
             let width = if let Value::Number(n) = width_val {
                 n as usize
             } else {
-                panic!("string.rjust: width must be a number");
+                return Err(runtime_error!(TypeError, "string.rjust: width must be a number"));
             };
 
             let fill = match fill_val {
                 Value::Char(c) => c,
                 Value::NIL => ' ',
-                _ => panic!("string.rjust: fill must be a char or nil"),
+                _ => {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "string.rjust: fill must be a char or nil"
+                    ));
+                }
             };
 
             let len = inner.0.chars().count();
             if len >= width {
-                return Value::String(inner.clone());
+                return Ok(Value::String(inner.clone()));
             }
             let result = format!("{}{}", fill.to_string().repeat(width - len), inner.0);
-            Value::String(TString::new(result))
+            Ok(Value::String(TString::new(result)))
         } else {
-            panic!("Can only use string.rjust on strings");
+            Err(runtime_error!(TypeError, "Can only use string.rjust on strings"))
         }
     }
 
     // --- Predicate / is_* ---
 
     /// is_upper() -> bool  (non-empty and all cased chars are uppercase)
-    fn is_upper(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_upper(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
             let has_cased = inner.0.chars().any(|c| c.is_alphabetic());
-            Value::Bool(has_cased && inner.0.chars().all(|c| !c.is_lowercase()))
+            Ok(Value::Bool(has_cased && inner.0.chars().all(|c| !c.is_lowercase())))
         } else {
-            panic!("Can only use string.is_upper on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_upper on strings"))
         }
     }
 
     /// is_lower() -> bool  (non-empty and all cased chars are lowercase)
-    fn is_lower(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_lower(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
             let has_cased = inner.0.chars().any(|c| c.is_alphabetic());
-            Value::Bool(has_cased && inner.0.chars().all(|c| !c.is_uppercase()))
+            Ok(Value::Bool(has_cased && inner.0.chars().all(|c| !c.is_uppercase())))
         } else {
-            panic!("Can only use string.is_lower on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_lower on strings"))
         }
     }
 
     /// is_numeric() -> bool  (all chars are numeric / digit)
-    fn is_numeric(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_numeric(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_numeric()))
+            Ok(Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_numeric())))
         } else {
-            panic!("Can only use string.is_numeric on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_numeric on strings"))
         }
     }
 
     /// is_alphanumeric() -> bool
-    fn is_alphanumeric(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_alphanumeric(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_alphanumeric()))
+            Ok(Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_alphanumeric())))
         } else {
-            panic!("Can only use string.is_alphanumeric on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_alphanumeric on strings"))
         }
     }
 
     /// is_alpha() -> bool  (all chars are alphabetic)
-    fn is_alpha(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_alpha(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_alphabetic()))
+            Ok(Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_alphabetic())))
         } else {
-            panic!("Can only use string.is_alpha on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_alpha on strings"))
         }
     }
 
     /// is_ascii() -> bool  (all chars are ASCII)
-    fn is_ascii(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_ascii(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Bool(inner.0.is_ascii())
+            Ok(Value::Bool(inner.0.is_ascii()))
         } else {
-            panic!("Can only use string.is_ascii on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_ascii on strings"))
         }
     }
 
     /// is_empty() -> bool
-    fn is_empty(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_empty(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Bool(inner.0.is_empty())
+            Ok(Value::Bool(inner.0.is_empty()))
         } else {
-            panic!("Can only use string.is_empty on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_empty on strings"))
         }
     }
 
     /// is_whitespace() -> bool  (non-empty and all chars are whitespace)
-    fn is_whitespace(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn is_whitespace(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let string = get_args!(args);
 
         if let Value::String(inner) = string {
-            Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_whitespace()))
+            Ok(Value::Bool(!inner.0.is_empty() && inner.0.chars().all(|c| c.is_whitespace())))
         } else {
-            panic!("Can only use string.is_whitespace on strings");
+            Err(runtime_error!(TypeError, "Can only use string.is_whitespace on strings"))
         }
     }
 }
@@ -528,8 +547,8 @@ impl Library for StringLib {
         "string"
     }
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value> {
-        match name {
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
             // Original
             x if x == hash_u64!("len") => Box::new(Self::len),
             x if x == hash_u64!("concat") => Box::new(Self::concat),
@@ -568,7 +587,7 @@ impl Library for StringLib {
             x if x == hash_u64!("is_empty") => Box::new(Self::is_empty),
             x if x == hash_u64!("is_whitespace") => Box::new(Self::is_whitespace),
 
-            _ => panic!("Unknown function `{name}` on lib {}", self.get_name()),
-        }
+            _ => return None,
+        })
     }
 }
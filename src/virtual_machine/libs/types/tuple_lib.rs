@@ -1,7 +1,14 @@
-use std::{cell::RefCell, cmp::Ordering};
+use std::{cell::RefCell, cmp::Ordering, rc::Rc};
 
 use crate::{
-    get_args, virtual_machine::{libs::lib::Library, types::list::TList, value::Value, vm::VM}
+    get_args,
+    virtual_machine::{
+        error::RuntimeResult,
+        libs::lib::{LibFunction, Library},
+        types::list::TList,
+        value::Value,
+        vm::VM,
+    },
 };
 
 pub const TUPLE_FUNCTIONS: [&str; 10] = [
@@ -11,52 +18,48 @@ pub const TUPLE_FUNCTIONS: [&str; 10] = [
 pub struct TupleLib;
 
 impl TupleLib {
-    fn to_list(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn to_list(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let tuple = get_args!(args);
 
         if let Value::Tuple(inner) = tuple {
-            let mut new_inner = inner.clone();
-            new_inner.is_tuple = true;
-            Value::List(new_inner)
+            let values = inner.values.borrow().clone();
+            Ok(Value::List(TList::new(Rc::new(RefCell::new(values)))))
         } else {
-            panic!("Can only use tuple.to_list on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.to_list on Tuples"))
         }
     }
 
-    fn len(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn len(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let tuple = get_args!(args);
 
         if let Value::Tuple(inner) = tuple {
-            Value::Number(inner.values.borrow().len() as f64)
+            Ok(Value::Number(inner.values.borrow().len() as f64))
         } else {
-            panic!("Can only use tuple.len on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.len on Tuples"))
         }
     }
 
-    fn map(vm: &mut VM, args: Vec<Value>) -> Value {
+    fn map(vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
 		let [tuple, func] = get_args!(args, 2);
         let mut new_array = vec![];
 
         if let Value::Tuple(inner) = tuple {
             if let Value::Function(f) = func {
-                for i in inner.values.borrow().iter() {
-                    vm.stack.push(i.clone());
-                    vm.call_function(f.clone(), 1);
-                    vm.run(false, true);
-                    let new_value = vm.pop();
-                    new_array.push(new_value);
+                let values = inner.values.borrow().clone();
+                for i in values {
+                    new_array.push(vm.call_value(f.clone(), vec![i])?);
                 }
 
-                return Value::Tuple(TList::new_tuple(rc!(RefCell::new(new_array))));
+                Ok(Value::Tuple(TList::new_tuple(rc!(RefCell::new(new_array)))))
             } else {
-                panic!("Expected function in tuple.map");
+                Err(runtime_error!(TypeError, "Expected function in tuple.map"))
             }
         } else {
-            panic!("Can only use tuple.map on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.map on Tuples"))
         }
     }
 
-    fn concat(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn concat(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
 		let [tuple, other] = get_args!(args, 2);
 
         if let Value::Tuple(inner) = tuple {
@@ -67,7 +70,7 @@ impl TupleLib {
                 ]
                 .concat();
 
-                return Value::Tuple(TList::new_tuple(rc!(RefCell::new(new_values))));
+                Ok(Value::Tuple(TList::new_tuple(rc!(RefCell::new(new_values)))))
             } else if let Value::List(other_inner) = other {
                 let new_values = [
                     inner.values.borrow().clone(),
@@ -75,37 +78,40 @@ impl TupleLib {
                 ]
                 .concat();
 
-                return Value::Tuple(TList::new_tuple(rc!(RefCell::new(new_values))));
+                Ok(Value::Tuple(TList::new_tuple(rc!(RefCell::new(new_values)))))
             } else {
-                panic!("Can only `concat` another List/Tuple with a Tuple")
+                Err(runtime_error!(
+                    TypeError,
+                    "Can only `concat` another List/Tuple with a Tuple"
+                ))
             }
         } else {
-            panic!("Can only use tuple.concat on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.concat on Tuples"))
         }
     }
 
-    fn copy(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn copy(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let tuple = get_args!(args);
 
         if let Value::Tuple(inner) = tuple {
-            Value::Tuple(inner.clone())
+            Ok(Value::Tuple(inner.clone()))
         } else {
-            panic!("Can only use tuple.copy on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.copy on Tuples"))
         }
     }
 
-    fn count(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn count(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
 		let [tuple, item] = get_args!(args, 2);
 
         if let Value::Tuple(inner) = tuple {
             let count = inner.values.borrow().iter().filter(|x| x == &&item).count();
-            Value::Number(count as f64)
+            Ok(Value::Number(count as f64))
         } else {
-            panic!("Can only use tuple.count on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.count on Tuples"))
         }
     }
 
-    fn sort(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn sort(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let tuple = get_args!(args);
 
         if let Value::Tuple(inner) = tuple {
@@ -114,41 +120,41 @@ impl TupleLib {
                 .borrow_mut()
                 .sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
         } else {
-            panic!("Can only use tuple.sort on Tuples");
+            return Err(runtime_error!(TypeError, "Can only use tuple.sort on Tuples"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn reverse(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn reverse(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
         let tuple = get_args!(args);
 
         if let Value::Tuple(inner) = tuple {
             inner.values.borrow_mut().reverse();
         } else {
-            panic!("Can only use tuple.reverse on Tuples");
+            return Err(runtime_error!(TypeError, "Can only use tuple.reverse on Tuples"));
         }
 
-        Value::NIL
+        Ok(Value::NIL)
     }
 
-    fn rep(_vm: &mut VM, args: Vec<Value>) -> Value {
+    fn rep(_vm: &mut VM, args: Vec<Value>) -> RuntimeResult {
 		let [tuple, value] = get_args!(args, 2);
 
         if let Value::Tuple(inner) = tuple {
             if let Value::Number(n) = value {
-                return Value::Tuple(TList::new_tuple(rc!(RefCell::new(
+                return Ok(Value::Tuple(TList::new_tuple(rc!(RefCell::new(
                     std::iter::repeat(&*inner.values.borrow())
                         .take(n as usize)
                         .flatten()
                         .cloned()
                         .collect()
-                ))));
+                )))));
             } else {
-                panic!("Can only tuple.repeat with a number")
+                Err(runtime_error!(TypeError, "Can only tuple.repeat with a number"))
             }
         } else {
-            panic!("Can only use tuple.repeat on Tuples");
+            Err(runtime_error!(TypeError, "Can only use tuple.repeat on Tuples"))
         }
     }
 }
@@ -159,19 +165,19 @@ impl Library for TupleLib {
         "tuple"
     }
 
-    fn get_function(&self, name: u64) -> Box<dyn Fn(&mut VM, Vec<Value>) -> Value> {
-        match name {
-            x if x == hash_u64!("len") => return Box::new(Self::len),
-            x if x == hash_u64!("map") => return Box::new(Self::map),
-            x if x == hash_u64!("concat") => return Box::new(Self::concat),
-            x if x == hash_u64!("copy") => return Box::new(Self::copy),
-            x if x == hash_u64!("count") => return Box::new(Self::count),
-            x if x == hash_u64!("sort") => return Box::new(Self::sort),
-            x if x == hash_u64!("reverse") => return Box::new(Self::reverse),
-            x if x == hash_u64!("rep") => return Box::new(Self::rep),
-            x if x == hash_u64!("to_list") => return Box::new(Self::to_list),
-
-            _ => panic!("Unknown function `{name}` on lib {}", self.get_name()),
-        }
+    fn get_function(&self, name: u64) -> Option<LibFunction> {
+        Some(match name {
+            x if x == hash_u64!("len") => Box::new(Self::len),
+            x if x == hash_u64!("map") => Box::new(Self::map),
+            x if x == hash_u64!("concat") => Box::new(Self::concat),
+            x if x == hash_u64!("copy") => Box::new(Self::copy),
+            x if x == hash_u64!("count") => Box::new(Self::count),
+            x if x == hash_u64!("sort") => Box::new(Self::sort),
+            x if x == hash_u64!("reverse") => Box::new(Self::reverse),
+            x if x == hash_u64!("rep") => Box::new(Self::rep),
+            x if x == hash_u64!("to_list") => Box::new(Self::to_list),
+
+            _ => return None,
+        })
     }
 }
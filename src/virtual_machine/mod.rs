@@ -1,8 +1,12 @@
 pub mod chunk;
+pub mod error;
+pub mod gc;
 pub mod inst;
 pub mod libs;
 pub mod traits;
 pub mod types;
 pub mod value;
 pub mod namespaces;
+pub mod reg_inst;
+pub mod register_vm;
 pub mod vm;
@@ -1,4 +1,6 @@
-use crate::virtual_machine::{traits::member_accessible::IMemberAccessible, value::Value, vm::VM};
+use crate::virtual_machine::{
+    error::RuntimeResult, traits::member_accessible::IMemberAccessible, value::Value, vm::VM,
+};
 use bincode::{Decode, Encode};
 use std::{
     collections::HashMap,
@@ -51,42 +53,56 @@ impl Hash for TNamespace {
 }
 
 impl IMemberAccessible for TNamespace {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
         if let Value::String(t) = member {
-            if let Some((value, _)) = self.env.get(&*t.0) {
-                return value.clone();
+            if let Some((value, _)) = self.env.get(t.0.as_str()) {
+                Ok(value.clone())
             } else {
-                panic!(
+                Err(runtime_error!(
+                    PropertyError,
                     "Tried to get unknown member {} on namespace:{}",
                     member.to_string(true),
                     self.name
-                )
+                ))
             }
         } else {
-            panic!("Can only get string members on a namespace.")
+            Err(runtime_error!(
+                PropertyError,
+                "Can only get string members on a namespace."
+            ))
         }
     }
 
-    fn set_member(&mut self, member: &Value, value: Value) {
+    fn set_member(&mut self, member: &Value, value: Value) -> RuntimeResult<()> {
         if self.locked {
-            panic!("Cannot set a member of locked namespace `{}`", self.name);
+            return Err(runtime_error!(
+                ConstError,
+                "Cannot set a member of locked namespace `{}`",
+                self.name
+            ));
         }
 
         if let Value::String(t) = member {
-            if let Some((_, is_const)) = self.env.get(&*t.0) {
+            if let Some((_, is_const)) = self.env.get(t.0.as_str()) {
                 if !*is_const {
-                    self.env.insert(t.0.clone(), (value, false));
+                    self.env.insert(Rc::from(t.0.as_str()), (value, false));
                 } else {
-					panic!(
+					return Err(runtime_error!(
+                        ConstError,
                         "Cannot set a constant member `{}` of namespace:{}.",
                         member.to_string(false),
                         self.name
-                    )
+					))
                 }
             }
         } else {
-            panic!("Can only set string members on a namespace.")
+            return Err(runtime_error!(
+                PropertyError,
+                "Can only set string members on a namespace."
+            ));
         }
+
+        Ok(())
     }
 }
 
@@ -2,7 +2,7 @@ use crate::{
     virtual_machine::{
         namespaces::{
             namespace::TNamespace,
-            std_namespaces::{n_fs::std_fs, n_io::std_io, n_math::std_math},
+            std_namespaces::{n_env::std_env, n_fs::std_fs, n_gc::std_gc, n_io::std_io, n_math::std_math},
         },
         value::Value,
     },
@@ -15,6 +15,8 @@ pub fn load_standard_namespace() -> Value {
     namespace.env.insert(rc_str!("Math"), (std_math(), true));
     namespace.env.insert(rc_str!("IO"), (std_io(), true));
     namespace.env.insert(rc_str!("FS"), (std_fs(), true));
+    namespace.env.insert(rc_str!("Env"), (std_env(), true));
+    namespace.env.insert(rc_str!("GC"), (std_gc(), true));
 
     return Value::Namespace(rc!(RefCell::new(namespace)));
 }
@@ -1,3 +1,5 @@
+pub mod n_env;
 pub mod n_fs;
+pub mod n_gc;
 pub mod n_io;
 pub mod n_math;
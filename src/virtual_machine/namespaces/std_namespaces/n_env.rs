@@ -0,0 +1,16 @@
+use crate::{
+    namespace_lib_function,
+    virtual_machine::{
+        namespaces::namespace::TNamespace, types::function::TFunction, value::Value,
+    },
+};
+use std::cell::RefCell;
+
+pub fn std_env() -> Value {
+    let mut namespace = TNamespace::new("Env", true);
+
+    namespace_lib_function!(namespace, "args");
+    namespace_lib_function!(namespace, "var");
+
+    Value::Namespace(rc!(RefCell::new(namespace)))
+}
@@ -0,0 +1,16 @@
+use crate::{
+    namespace_lib_function,
+    virtual_machine::{
+        namespaces::namespace::TNamespace, types::function::TFunction, value::Value,
+    },
+};
+use std::cell::RefCell;
+
+pub fn std_gc() -> Value {
+    let mut namespace = TNamespace::new("GC", true);
+
+    namespace_lib_function!(namespace, "collect");
+    namespace_lib_function!(namespace, "stats");
+
+    Value::Namespace(rc!(RefCell::new(namespace)))
+}
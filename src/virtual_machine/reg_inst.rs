@@ -0,0 +1,56 @@
+use bincode::{Decode, Encode};
+
+/// Instructions of the register VM. Operands name registers of the current frame, which start
+/// with the function's parameters, and `k` indexes the chunk's constants.
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
+#[derive(Encode, Decode, Debug, Clone, PartialEq)]
+pub enum RegInst {
+    EXIT,
+    /// Makes room for the `size` registers the function (or the top-level code) uses
+    ENTER(u8),
+
+    LOAD_K { dst: u8, k: usize },
+    LOAD_NIL(u8),
+    LOAD_BOOL { dst: u8, value: bool },
+    LOAD_GLOBAL { dst: u8, id: u64 },
+    MOVE { dst: u8, src: u8 },
+
+    // dst = a <op> b
+    ADD { dst: u8, a: u8, b: u8 },
+    SUB { dst: u8, a: u8, b: u8 },
+    MUL { dst: u8, a: u8, b: u8 },
+    DIV { dst: u8, a: u8, b: u8 },
+    POW { dst: u8, a: u8, b: u8 },
+    MOD { dst: u8, a: u8, b: u8 },
+    EQ { dst: u8, a: u8, b: u8 },
+    NEQ { dst: u8, a: u8, b: u8 },
+    LT { dst: u8, a: u8, b: u8 },
+    GT { dst: u8, a: u8, b: u8 },
+    LE { dst: u8, a: u8, b: u8 },
+    GE { dst: u8, a: u8, b: u8 },
+    AND { dst: u8, a: u8, b: u8 },
+    OR { dst: u8, a: u8, b: u8 },
+    NEG { dst: u8, src: u8 },
+    NOT { dst: u8, src: u8 },
+
+    // Collections of the `len` registers from `base` on
+    LIST { dst: u8, base: u8, len: u8 },
+    TUPLE { dst: u8, base: u8, len: u8 },
+    /// A range from the start, end and step in the three registers from `base` on
+    RANGE { dst: u8, base: u8, inclusive: bool },
+
+    GET_PROP { dst: u8, target: u8, key: u8 },
+    SET_PROP { target: u8, key: u8, src: u8 },
+
+    JUMP(usize),
+    JUMP_IF_FALSE { cond: u8, target: usize },
+    JUMP_IF_NOT_NIL { src: u8, target: usize },
+
+    GET_ITER { dst: u8, src: u8 },
+    /// Stores the next item of the iterator in `iter` into `dst`, or jumps to `exit` once it runs out
+    FOR_ITER { iter: u8, dst: u8, exit: usize },
+
+    /// Calls the function in `base` with the `args` registers after it, and leaves the result in `base`
+    CALL { base: u8, args: u8 },
+    RETURN(u8),
+}
@@ -0,0 +1,280 @@
+use crate::virtual_machine::{
+    error::RuntimeResult,
+    inst::Inst,
+    reg_inst::RegInst,
+    types::{iterator::TIterator, list::TList, range::TRange},
+    value::Value,
+    vm::VM,
+};
+use simply_colored::*;
+use std::{cell::RefCell, rc::Rc};
+
+/// A call into register code, to go back to once it returns.
+pub struct RegisterFrame {
+    /// The caller's first register
+    base: usize,
+    return_addr: usize,
+}
+
+impl VM {
+    pub fn print_register_code(&self) {
+        for (i, inst) in self.register_code.iter().enumerate() {
+            let s = format!("{inst:?}");
+            let (opcode, rest) = s.split_once([' ', '(']).unwrap_or((&s, ""));
+
+            let color = match inst {
+                RegInst::EXIT | RegInst::RETURN(_) => RED,
+                RegInst::ENTER(_) => MAGENTA,
+                RegInst::LIST { .. } | RegInst::TUPLE { .. } | RegInst::RANGE { .. } => GREEN,
+                _ => YELLOW,
+            };
+            print!(
+                "{MAGENTA}{i:>2}{RESET}   {color}{opcode}{RESET} {BLUE}{}{RESET}",
+                rest.trim_end_matches(')')
+            );
+
+            match inst {
+                RegInst::LOAD_K { k, .. } => print!("{BLACK}   {:>3?}{RESET}", self.constants[*k]),
+                RegInst::LOAD_GLOBAL { id, .. } => {
+                    print!("{BLACK}   {}{RESET}", self.lookup_intern(*id))
+                }
+                _ => {}
+            }
+            println!();
+        }
+    }
+
+    /// Runs `register_code` from `pos`, the register VM's counterpart of `run`. Register code
+    /// has no `try`, so an error ends the run.
+    pub fn run_registers(&mut self) -> RuntimeResult<()> {
+        let result = self.execute_registers();
+
+        if let Err(mut e) = result {
+            if e.pos.is_none() {
+                e.pos = Some(self.pos);
+                e.location = self.location(self.pos);
+                e.trace = self
+                    .register_frames
+                    .iter()
+                    .map(|frame| (frame.return_addr, self.location(frame.return_addr)))
+                    .collect();
+            }
+            self.register_frames.clear();
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    fn execute_registers(&mut self) -> RuntimeResult<()> {
+        let code = Rc::clone(&self.register_code);
+        let mut base = 0;
+
+        // The register `$x` of the current frame
+        macro_rules! r {
+            ($x:expr) => {
+                self.registers[base + *$x as usize]
+            };
+        }
+
+        // `dst = a <op> b`, with the stack VM's semantics for `op`
+        macro_rules! binary {
+            ($op:expr, $dst:expr, $a:expr, $b:expr) => {
+                r!($dst) = Self::binary_op(&$op, &r!($a), &r!($b))?
+            };
+        }
+
+        while self.pos < code.len() {
+            match &code[self.pos] {
+                RegInst::EXIT => return Ok(()),
+                RegInst::ENTER(size) => {
+                    let len = base + *size as usize;
+                    if self.registers.len() < len {
+                        self.registers.resize(len, Value::NIL);
+                    }
+                }
+
+                RegInst::LOAD_K { dst, k } => r!(dst) = self.constants[*k].clone(),
+                RegInst::LOAD_NIL(dst) => r!(dst) = Value::NIL,
+                RegInst::LOAD_BOOL { dst, value } => r!(dst) = Value::Bool(*value),
+                RegInst::LOAD_GLOBAL { dst, id } => {
+                    if let Some((value, _)) = self.globals.get(id) {
+                        r!(dst) = value.clone();
+                    } else {
+                        return Err(runtime_error!(
+                            NameError,
+                            "Global `{}` doesn't exist.",
+                            self.lookup_intern(*id)
+                        ));
+                    }
+                }
+                RegInst::MOVE { dst, src } => r!(dst) = r!(src).clone(),
+
+                RegInst::ADD { dst, a, b } => binary!(Inst::ADD, dst, a, b),
+                RegInst::SUB { dst, a, b } => binary!(Inst::SUB, dst, a, b),
+                RegInst::MUL { dst, a, b } => binary!(Inst::MUL, dst, a, b),
+                RegInst::DIV { dst, a, b } => binary!(Inst::DIV, dst, a, b),
+                RegInst::POW { dst, a, b } => binary!(Inst::POW, dst, a, b),
+                RegInst::MOD { dst, a, b } => binary!(Inst::MOD, dst, a, b),
+                RegInst::EQ { dst, a, b } => binary!(Inst::EQ, dst, a, b),
+                RegInst::NEQ { dst, a, b } => binary!(Inst::NEQ, dst, a, b),
+                RegInst::LT { dst, a, b } => binary!(Inst::LT, dst, a, b),
+                RegInst::GT { dst, a, b } => binary!(Inst::GT, dst, a, b),
+                RegInst::LE { dst, a, b } => binary!(Inst::LE, dst, a, b),
+                RegInst::GE { dst, a, b } => binary!(Inst::GE, dst, a, b),
+                RegInst::AND { dst, a, b } => binary!(Inst::AND, dst, a, b),
+                RegInst::OR { dst, a, b } => binary!(Inst::OR, dst, a, b),
+                RegInst::NEG { dst, src } => r!(dst) = Value::Number(-r!(src).as_number()?),
+                RegInst::NOT { dst, src } => r!(dst) = Value::Bool(!r!(src).is_truthy()),
+
+                RegInst::LIST {
+                    dst,
+                    base: first,
+                    len,
+                }
+                | RegInst::TUPLE {
+                    dst,
+                    base: first,
+                    len,
+                } => {
+                    let start = base + *first as usize;
+                    let values = rc!(RefCell::new(
+                        self.registers[start..start + *len as usize].to_vec()
+                    ));
+
+                    r!(dst) = if let RegInst::TUPLE { .. } = &code[self.pos] {
+                        Value::Tuple(TList::new_tuple(values))
+                    } else {
+                        Value::List(TList::new(values))
+                    };
+                }
+
+                RegInst::RANGE {
+                    dst,
+                    base: first,
+                    inclusive,
+                } => {
+                    let start = base + *first as usize;
+
+                    r!(dst) = Value::Range(rc!(TRange {
+                        start: self.registers[start].clone(),
+                        end: self.registers[start + 1].clone(),
+                        step: self.registers[start + 2].clone(),
+                        inclusive: *inclusive,
+                    }));
+                }
+
+                RegInst::GET_PROP { dst, target, key } => {
+                    let target = r!(target).clone();
+                    let key = r!(key).clone();
+                    r!(dst) = self.get_property(target, &key)?;
+                }
+                RegInst::SET_PROP { target, key, src } => {
+                    let target = r!(target).clone();
+                    let key = r!(key).clone();
+                    let value = r!(src).clone();
+                    self.set_property(target, &key, value)?;
+                }
+
+                RegInst::JUMP(target) => {
+                    self.pos = *target;
+                    continue;
+                }
+                RegInst::JUMP_IF_FALSE { cond, target } => {
+                    if !r!(cond).is_truthy() {
+                        self.pos = *target;
+                        continue;
+                    }
+                }
+                RegInst::JUMP_IF_NOT_NIL { src, target } => {
+                    if r!(src) != Value::NIL {
+                        self.pos = *target;
+                        continue;
+                    }
+                }
+
+                RegInst::GET_ITER { dst, src } => {
+                    let value = r!(src).clone();
+                    r!(dst) = Value::Iterator(TIterator::from_value(self, value)?);
+                }
+                RegInst::FOR_ITER { iter, dst, exit } => {
+                    let Value::Iterator(iterator) = r!(iter).clone() else {
+                        unreachable!("FOR_ITER without an iterator")
+                    };
+
+                    if let Some(value) = iterator.next(self)? {
+                        r!(dst) = value;
+                    } else {
+                        r!(iter) = Value::NIL;
+                        self.pos = *exit;
+                        continue;
+                    }
+                }
+
+                RegInst::CALL { base: func, args } => {
+                    let func = base + *func as usize;
+                    let args = *args as usize;
+
+                    match self.registers[func].clone() {
+                        Value::Function(f) if f.handler.is_some() => {
+                            let (library, method) = f.handler.unwrap();
+
+                            // Library functions take their arguments like `CALL` pops them
+                            let mut values: Vec<_> =
+                                f.this.clone().map(|this| *this).into_iter().collect();
+                            values.extend(
+                                self.registers[func + 1..func + 1 + args]
+                                    .iter()
+                                    .rev()
+                                    .cloned(),
+                            );
+
+                            self.registers[func] = self.call_library(library, method, values)?;
+                        }
+                        Value::Function(f) if f.this.is_none() && !f.generator => {
+                            // Missing arguments are nil, extra ones are left in the callee's
+                            // temporaries
+                            let callee = func + 1;
+                            if self.registers.len() < callee + f.arity {
+                                self.registers.resize(callee + f.arity, Value::NIL);
+                            }
+                            for i in args..f.arity {
+                                self.registers[callee + i] = Value::NIL;
+                            }
+
+                            self.register_frames.push(RegisterFrame {
+                                base,
+                                return_addr: self.pos,
+                            });
+                            base = callee;
+                            self.pos = f.entry;
+                            continue;
+                        }
+                        func => {
+                            return Err(runtime_error!(
+                                TypeError,
+                                "Tried calling non-function: {func:?}"
+                            ));
+                        }
+                    }
+                }
+                RegInst::RETURN(src) => {
+                    let value = std::mem::replace(&mut r!(src), Value::NIL);
+                    let frame = self
+                        .register_frames
+                        .pop()
+                        .expect("RETURN outside of a function");
+
+                    // The result replaces the function in the caller's registers
+                    self.registers[base - 1] = value;
+                    base = frame.base;
+                    self.pos = frame.return_addr;
+                }
+            }
+
+            self.pos += 1;
+        }
+
+        Ok(())
+    }
+}
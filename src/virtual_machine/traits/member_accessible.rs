@@ -1,13 +1,21 @@
-use crate::virtual_machine::{value::Value, vm::VM};
+use crate::virtual_machine::{error::RuntimeResult, value::Value, vm::VM};
 use std::fmt::Debug;
 
 pub trait IMemberAccessible: Debug {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
-        panic!("Cannot get member `{}` on {self:?}", member.to_string(true));
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 
     #[allow(unused)]
-    fn set_member(&mut self, member: &Value, _value: Value) {
-        panic!("Cannot set member `{}` on {self:?}", member.to_string(true));
+    fn set_member(&mut self, member: &Value, _value: Value) -> RuntimeResult<()> {
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot set member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 }
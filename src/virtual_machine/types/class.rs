@@ -0,0 +1,133 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
+
+use crate::virtual_machine::{
+    error::RuntimeResult,
+    gc::{self, Tracked},
+    traits::member_accessible::IMemberAccessible,
+    types::{function::TFunction, interface::TInterface},
+    value::Value,
+    vm::VM,
+};
+use bincode::{Decode, Encode};
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct TClass {
+    pub name: String,
+    pub fields: Vec<String>, // in declaration order
+    pub methods: HashMap<String, TFunction>,
+    pub interfaces: Vec<Rc<TInterface>>,
+}
+
+impl PartialOrd for TClass {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl TClass {
+    pub fn new(
+        name: String,
+        fields: Vec<String>,
+        methods: HashMap<String, TFunction>,
+        interfaces: Vec<Rc<TInterface>>,
+    ) -> Self {
+        Self {
+            name,
+            fields,
+            methods,
+            interfaces,
+        }
+    }
+
+    pub fn implements(&self, interface: &TInterface) -> bool {
+        self.interfaces.iter().any(|i| **i == *interface)
+    }
+
+    /// A new instance with every field set to nil, ready to be passed to `init`.
+    pub fn instantiate(self: &Rc<Self>) -> TObject {
+        let values = self
+            .fields
+            .iter()
+            .map(|field| (field.clone(), Value::NIL))
+            .collect();
+
+        TObject::new(Rc::clone(self), Rc::new(RefCell::new(values)))
+    }
+}
+
+impl Debug for TClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("Class:{}", self.name)).unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TClass {}
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct TObject {
+    pub class: Rc<TClass>,
+    pub values: Rc<RefCell<HashMap<String, Value>>>,
+}
+
+impl PartialOrd for TObject {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl TObject {
+    pub fn new(class: Rc<TClass>, values: Rc<RefCell<HashMap<String, Value>>>) -> Self {
+        gc::track(Tracked::Fields(Rc::downgrade(&values)));
+        Self { class, values }
+    }
+}
+
+impl Debug for TObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("Object:{}", self.class.name)).unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TObject {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
+        if let Value::String(member) = member {
+            if let Some(v) = self.values.borrow().get(&*member.0) {
+                return Ok(v.clone());
+            }
+
+            // Methods are bound to the object they were read from, which becomes `self`
+            if let Some(method) = self.class.methods.get(&*member.0) {
+                let mut method = method.clone();
+                method.this = Some(Box::new(Value::Object(rc!(self.clone()))));
+
+                return Ok(Value::Function(rc!(method)));
+            }
+        }
+
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
+    }
+
+    fn set_member(&mut self, member: &Value, value: Value) -> RuntimeResult<()> {
+        if let Value::String(member) = member
+            && let Some(v) = self.values.borrow_mut().get_mut(&*member.0)
+        {
+            *v = value;
+            return Ok(());
+        }
+
+        Err(runtime_error!(
+            PropertyError,
+            "Tried setting unknown field `{}` on object of class {}",
+            member.to_string(false),
+            self.class.name
+        ))
+    }
+}
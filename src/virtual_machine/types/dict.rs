@@ -2,6 +2,8 @@ use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
 
 use crate::{
     virtual_machine::{
+        error::RuntimeResult,
+        gc::{self, Tracked},
         traits::member_accessible::IMemberAccessible, types::function::TFunction,
         libs::types::dict_lib::DICT_FUNCTIONS, value::Value, vm::VM,
     },
@@ -21,6 +23,7 @@ impl PartialOrd for TDict {
 
 impl TDict {
     pub fn new(values: Rc<RefCell<HashMap<Value, Value>>>) -> Self {
+        gc::track(Tracked::Dict(Rc::downgrade(&values)));
         Self { values }
     }
 }
@@ -34,23 +37,29 @@ impl Debug for TDict {
 
 // MEMBER ACCESS
 impl IMemberAccessible for TDict {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
         if let Value::String(member) = member {
-            if DICT_FUNCTIONS.contains(&&*member.0) {
-                return lib_function!(self, "dict", member.0.clone(), Value::Dict);
+            if DICT_FUNCTIONS.contains(&member.0.as_str()) {
+                return Ok(lib_function!(self, "dict", member.0.clone(), Value::Dict));
             }
         }
 
         if let Some(x) = self.values.borrow().get(member) {
-            return x.clone();
+            return Ok(x.clone());
         }
 
-        panic!("Cannot get member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 
-    fn set_member(&mut self, member: &Value, value: Value) {
+    fn set_member(&mut self, member: &Value, value: Value) -> RuntimeResult<()> {
         self.values.borrow_mut().insert(member.clone(), value);
 
+        Ok(())
+
         // panic!("Cannot set member `{}` on {self:?}", member.to_string(true));
     }
 }
@@ -1,6 +1,8 @@
 use std::{collections::HashMap, fmt::Debug, rc::Rc};
 
-use crate::virtual_machine::{traits::member_accessible::IMemberAccessible, value::Value, vm::VM};
+use crate::virtual_machine::{
+    error::RuntimeResult, traits::member_accessible::IMemberAccessible, value::Value, vm::VM,
+};
 use bincode::{Decode, Encode};
 
 #[derive(Encode, Decode, Clone, PartialEq)]
@@ -30,11 +32,15 @@ impl Debug for TEnum {
 
 // MEMBER ACCESS
 impl IMemberAccessible for TEnum {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
         if let Some(x) = self.values.get(member) {
-            return x.clone();
+            return Ok(x.clone());
         }
 
-        panic!("Cannot get member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 }
@@ -1,38 +1,54 @@
 use bincode::{Decode, Encode};
 use std::{
-    cell::RefCell, collections::HashMap, fmt::Debug, hash::{Hash, Hasher}, rc::Rc
+    cell::RefCell, fmt::Debug, hash::{Hash, Hasher}, rc::Rc
 };
 
 use crate::{virtual_machine::value::Value};
 
+/// A variable a closure captured. It stays in its frame's slot while the scope declaring it
+/// is open, so the frame and every closure see the same value, and moves into the upvalue
+/// once the scope ends.
+#[derive(Encode, Decode, Clone)]
+pub enum Upvalue {
+    /// Index into `VM::slots`
+    Open(usize),
+    Closed(Value),
+}
+
+pub type UpvalueCell = Rc<RefCell<Upvalue>>;
+
 #[derive(Encode, Decode, Clone)]
 pub struct TFunction {
     pub entry: usize,
+    /// Number of parameters, including `self` for methods (unused by library functions)
+    pub arity: usize,
     pub handler: Option<(u64, u64)>,
     pub this: Option<Box<Value>>,
-    pub upvalues: Vec<Rc<RefCell<HashMap<u64, (Value, bool)>>>>,
+    pub upvalues: Vec<UpvalueCell>,
+    /// Calling it returns a generator instead of running the body (it has a `yield`)
+    pub generator: bool,
 }
 
 impl TFunction {
-    pub fn new(entry: usize) -> Self {
+    pub fn new(entry: usize, arity: usize) -> Self {
         Self {
             entry,
+            arity,
             handler: None,
             this: None,
 			upvalues: vec![],
+            generator: false,
         }
     }
 
-    pub fn with_lib(
-        lib: Rc<str>,
-        method: Rc<str>,
-        this: Option<Box<Value>>,
-    ) -> Self {
+    pub fn with_lib(lib: &str, method: &str, this: Option<Box<Value>>) -> Self {
         Self {
             entry: 0,
-            handler: Some((hash_u64!(lib.as_ref()), hash_u64!(method.as_ref()))),
+            arity: 0,
+            handler: Some((hash_u64!(lib), hash_u64!(method))),
             this,
 			upvalues: vec![],
+            generator: false,
         }
     }
 }
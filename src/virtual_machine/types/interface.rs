@@ -0,0 +1,76 @@
+use std::fmt::Debug;
+
+use crate::virtual_machine::{
+    error::RuntimeResult, traits::member_accessible::IMemberAccessible, types::class::TClass,
+};
+use bincode::{Decode, Encode};
+
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct TInterface {
+    pub name: String,
+    pub fields: Vec<String>,
+    pub methods: Vec<(String, usize)>, // (name, arity without `self`)
+}
+
+impl PartialOrd for TInterface {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl TInterface {
+    pub fn new(name: String, fields: Vec<String>, methods: Vec<(String, usize)>) -> Self {
+        Self {
+            name,
+            fields,
+            methods,
+        }
+    }
+
+    /// Errors unless `class` has every field and method this interface asks for.
+    pub fn check(&self, class: &TClass) -> RuntimeResult<()> {
+        for field in self.fields.iter() {
+            if !class.fields.contains(field) {
+                return Err(runtime_error!(
+                    TypeError,
+                    "Class `{}` doesn't implement `{}`: missing field `{field}`",
+                    class.name,
+                    self.name
+                ));
+            }
+        }
+
+        for (method, arity) in self.methods.iter() {
+            let Some(f) = class.methods.get(method) else {
+                return Err(runtime_error!(
+                    TypeError,
+                    "Class `{}` doesn't implement `{}`: missing method `{method}`",
+                    class.name,
+                    self.name
+                ));
+            };
+
+            if f.arity != arity + 1 {
+                return Err(runtime_error!(
+                    TypeError,
+                    "Class `{}` doesn't implement `{}`: method `{method}` takes {} argument(s), expected {arity}",
+                    class.name,
+                    self.name,
+                    f.arity - 1
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Debug for TInterface {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("Interface:{}", self.name)).unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TInterface {}
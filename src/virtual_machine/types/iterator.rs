@@ -0,0 +1,409 @@
+use std::{cell::RefCell, fmt::Debug, rc::Rc};
+
+use crate::virtual_machine::{
+    error::RuntimeResult, libs::types::iter_lib::ITER_FUNCTIONS,
+    traits::member_accessible::IMemberAccessible,
+    types::function::{TFunction, UpvalueCell}, types::list::TList,
+    value::Value, vm::{Handler, VM},
+};
+use bincode::{Decode, Encode};
+
+/// Where an iterator gets its items from. The adapters (`Map`, `Filter`, ...) pull from
+/// other iterators one item at a time, so chaining them never builds a list.
+#[derive(Encode, Decode, Clone)]
+pub enum IteratorState {
+    /// Items of a list or tuple, read as the loop goes so pushes during it are seen
+    Sequence {
+        values: Rc<RefCell<Vec<Value>>>,
+        index: usize,
+    },
+    Range {
+        start: f64,
+        end: f64,
+        step: f64,
+        inclusive: bool,
+        index: usize,
+    },
+    /// The `next()` method of a struct or object, which returns `Done` once it's done
+    Next(Rc<TFunction>),
+
+    Map {
+        source: TIterator,
+        function: Rc<TFunction>,
+    },
+    Filter {
+        source: TIterator,
+        function: Rc<TFunction>,
+    },
+    Zip(Vec<TIterator>),
+    Take {
+        source: TIterator,
+        remaining: usize,
+    },
+    Enumerate {
+        source: TIterator,
+        index: usize,
+    },
+    Chain {
+        sources: Vec<TIterator>,
+        current: usize,
+    },
+
+    /// A call to a function with a `yield`, whose frame is taken out while it runs and gone
+    /// once it returns
+    Generator {
+        frame: Option<Box<GeneratorFrame>>,
+        running: bool,
+    },
+}
+
+/// Everything a generator's call frame had when it stopped at a `yield`, with the stack
+/// lengths of its `try` handlers relative to the frame.
+#[derive(Encode, Decode, Clone)]
+pub struct GeneratorFrame {
+    pub pos: usize,
+    /// Set after the first `yield`, which then evaluates to nil when resumed
+    pub started: bool,
+    pub stack: Vec<Value>,
+    pub slots: Vec<Value>,
+    /// Upvalues that were open on its slots, by slot, to reopen when it resumes
+    pub open_upvalues: Vec<(usize, UpvalueCell)>,
+    pub iterators: Vec<TIterator>,
+    pub upvalues: Vec<UpvalueCell>,
+    pub handlers: Vec<Handler>,
+}
+
+#[derive(Encode, Decode, Clone)]
+pub struct TIterator {
+    pub state: Rc<RefCell<IteratorState>>,
+}
+
+impl PartialEq for TIterator {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl PartialOrd for TIterator {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl TIterator {
+    pub fn new(state: IteratorState) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+
+    /// A generator that will run the function at `entry` with `args` (already padded to its
+    /// arity) on the first `next()`.
+    pub fn generator(
+        entry: usize,
+        upvalues: Vec<UpvalueCell>,
+        args: Vec<Value>,
+    ) -> Self {
+        Self::new(IteratorState::Generator {
+            frame: Some(Box::new(GeneratorFrame {
+                pos: entry,
+                started: false,
+                stack: args,
+                slots: vec![],
+                open_upvalues: vec![],
+                iterators: vec![],
+                upvalues,
+                handlers: vec![],
+            })),
+            running: false,
+        })
+    }
+
+    /// An iterator over `value`: the items of a list or tuple, the chars of a string, the keys
+    /// of a dict, the numbers of a range, or whatever the `iter()`/`next()` methods of a
+    /// struct or object give.
+    pub fn from_value(vm: &mut VM, value: Value) -> RuntimeResult<Self> {
+        let values = match value {
+            Value::Iterator(iterator) => return Ok(iterator),
+            Value::List(x) | Value::Tuple(x) => x.values,
+            Value::String(s) => Rc::new(RefCell::new(s.0.chars().map(Value::Char).collect())),
+            Value::Dict(d) => Rc::new(RefCell::new(d.values.borrow().keys().cloned().collect())),
+
+            Value::Range(range) => {
+                return Ok(Self::new(IteratorState::Range {
+                    start: range.start.as_number()?,
+                    end: range.end.as_number()?,
+                    step: range.step.as_number()?,
+                    inclusive: range.inclusive,
+                    index: 0,
+                }));
+            }
+
+            Value::Struct(_) | Value::Object(_) => {
+                if let Some(iter) = value.special_method("iter") {
+                    let result = vm.call_value(iter, vec![])?;
+
+                    return match result.special_method("next") {
+                        Some(next) => Ok(Self::new(IteratorState::Next(next))),
+                        None if matches!(result, Value::Struct(_) | Value::Object(_)) => {
+                            Err(runtime_error!(
+                                TypeError,
+                                "`iter()` of `{}` returned `{}`, which has no `next()` method",
+                                value.get_type(),
+                                result.get_type()
+                            ))
+                        }
+                        None => Self::from_value(vm, result),
+                    };
+                }
+
+                if let Some(next) = value.special_method("next") {
+                    return Ok(Self::new(IteratorState::Next(next)));
+                }
+
+                return Err(runtime_error!(
+                    TypeError,
+                    "Cannot iterate over `{}`, it has no `iter()` or `next()` method",
+                    value.get_type()
+                ));
+            }
+
+            _ => {
+                return Err(runtime_error!(
+                    TypeError,
+                    "Cannot iterate over `{}`",
+                    value.get_type()
+                ));
+            }
+        };
+
+        Ok(Self::new(IteratorState::Sequence { values, index: 0 }))
+    }
+
+    /// The next item, or `None` once the iterator has run out. Functions are called with the
+    /// state released, since they can use this iterator again.
+    pub fn next(&self, vm: &mut VM) -> RuntimeResult<Option<Value>> {
+        let mut state = self.state.borrow_mut();
+
+        match &mut *state {
+            IteratorState::Sequence { values, index } => {
+                let value = values.borrow().get(*index).cloned();
+                if value.is_some() {
+                    *index += 1;
+                }
+                Ok(value)
+            }
+
+            IteratorState::Range {
+                start,
+                end,
+                step,
+                inclusive,
+                index,
+            } => {
+                let current = *start + *index as f64 * *step;
+
+                let in_bounds = match (*inclusive, *step >= 0.0) {
+                    (true, true) => current <= *end,
+                    (true, false) => current >= *end,
+                    (false, true) => current < *end,
+                    (false, false) => current > *end,
+                };
+
+                if in_bounds {
+                    *index += 1;
+                    Ok(Some(Value::Number(current)))
+                } else {
+                    Ok(None)
+                }
+            }
+
+            IteratorState::Next(next) => {
+                let next = next.clone();
+                drop(state);
+
+                let value = vm.call_value(next, vec![])?;
+                Ok((value != Value::Done).then_some(value))
+            }
+
+            IteratorState::Map { source, function } => {
+                let (source, function) = (source.clone(), function.clone());
+                drop(state);
+
+                match source.next(vm)? {
+                    Some(value) => Ok(Some(vm.call_value(function, vec![value])?)),
+                    None => Ok(None),
+                }
+            }
+
+            IteratorState::Filter { source, function } => {
+                let (source, function) = (source.clone(), function.clone());
+                drop(state);
+
+                while let Some(value) = source.next(vm)? {
+                    if vm.call_value(function.clone(), vec![value.clone()])?.is_truthy() {
+                        return Ok(Some(value));
+                    }
+                }
+                Ok(None)
+            }
+
+            IteratorState::Zip(sources) => {
+                let sources = sources.clone();
+                drop(state);
+
+                let mut items = vec![];
+                for source in sources {
+                    match source.next(vm)? {
+                        Some(value) => items.push(value),
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(Value::Tuple(TList::new_tuple(rc!(RefCell::new(items))))))
+            }
+
+            IteratorState::Take { source, remaining } => {
+                if *remaining == 0 {
+                    return Ok(None);
+                }
+                *remaining -= 1;
+
+                let source = source.clone();
+                drop(state);
+                source.next(vm)
+            }
+
+            IteratorState::Enumerate { source, index } => {
+                let i = *index;
+                *index += 1;
+
+                let source = source.clone();
+                drop(state);
+
+                Ok(source.next(vm)?.map(|value| {
+                    Value::Tuple(TList::new_tuple(rc!(RefCell::new(vec![
+                        Value::Number(i as f64),
+                        value
+                    ]))))
+                }))
+            }
+
+            IteratorState::Chain { sources, current } => {
+                let sources = sources.clone();
+                let start = *current;
+                drop(state);
+
+                for (i, source) in sources.iter().enumerate().skip(start) {
+                    if let Some(value) = source.next(vm)? {
+                        return Ok(Some(value));
+                    }
+
+                    // Don't ask the finished ones again
+                    if let IteratorState::Chain { current, .. } = &mut *self.state.borrow_mut() {
+                        *current = i + 1;
+                    }
+                }
+                Ok(None)
+            }
+
+            IteratorState::Generator { frame, running } => {
+                if *running {
+                    return Err(runtime_error!(ValueError, "Generator is already running"));
+                }
+                let Some(frame) = frame.take() else {
+                    return Ok(None);
+                };
+                *running = true;
+                drop(state);
+
+                let result = vm.resume_generator(*frame);
+
+                let mut state = self.state.borrow_mut();
+                let IteratorState::Generator { frame, running } = &mut *state else {
+                    unreachable!()
+                };
+                *running = false;
+
+                // An error finishes the generator, like returning does
+                Ok(result?.map(|(value, suspended)| {
+                    *frame = Some(Box::new(suspended));
+                    value
+                }))
+            }
+        }
+    }
+
+    /// Runs the iterator to the end, collecting what's left of it.
+    pub fn collect(&self, vm: &mut VM) -> RuntimeResult<Vec<Value>> {
+        let mut values = vec![];
+        while let Some(value) = self.next(vm)? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+}
+
+impl Debug for TIterator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Iterator").unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TIterator {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
+        if let Value::String(member) = member
+            && ITER_FUNCTIONS.contains(&member.0.as_str())
+        {
+            return Ok(lib_function!(self, "iter", member.0.clone(), Value::Iterator));
+        }
+
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::virtual_machine::vm::tests::eval;
+
+    #[test]
+    fn generator_is_finished_after_throwing() {
+        let (_, result) = eval(
+            "fn gen() {
+                yield 1
+                throw \"boom\"
+                yield 2
+            }
+            let g = gen()
+            let seen = []
+            try {
+                for x in g { seen.push(x) }
+            } catch e {
+                seen.push(e.message)
+            }
+            seen.push(g.collect().len())
+            seen",
+        );
+
+        assert_eq!(result.unwrap().to_string(false), r#"[1, "boom", 0]"#);
+    }
+
+    #[test]
+    fn generator_cannot_iterate_itself() {
+        let (_, result) = eval(
+            "let g = nil
+            fn gen() {
+                for x in g { yield x }
+            }
+            g = gen()
+            g.collect()",
+        );
+
+        assert_eq!(result.unwrap_err().message, "Generator is already running");
+    }
+}
@@ -3,6 +3,8 @@ use std::{cell::RefCell, fmt::Debug, rc::Rc};
 use crate::{
     misc::to_index::to_index,
     virtual_machine::{
+        error::RuntimeResult,
+        gc::{self, Tracked},
         libs::types::list_lib::LIST_FUNCTIONS, libs::types::tuple_lib::TUPLE_FUNCTIONS,
         traits::member_accessible::IMemberAccessible, types::function::TFunction, value::Value,
         vm::VM,
@@ -10,71 +12,83 @@ use crate::{
 };
 use bincode::{Decode, Encode};
 
+/// The items of a list or a tuple, which `Value::List` and `Value::Tuple` tell apart
 #[derive(Encode, Decode, Clone, PartialEq, PartialOrd)]
 pub struct TList {
     pub values: Rc<RefCell<Vec<Value>>>,
-    pub is_tuple: bool,
 }
 
 impl TList {
     pub fn new(values: Rc<RefCell<Vec<Value>>>) -> Self {
-        Self {
-            values,
-            is_tuple: false,
-        }
+        gc::track(Tracked::Items(Rc::downgrade(&values)));
+        Self { values }
     }
     pub fn new_tuple(values: Rc<RefCell<Vec<Value>>>) -> Self {
-        Self {
-            values,
-            is_tuple: true,
-        }
+        Self::new(values)
     }
-}
 
-impl Debug for TList {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str("List").unwrap();
-        Ok(())
-    }
-}
-
-// MEMBER ACCESS
-impl IMemberAccessible for TList {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
+    /// An item, or a method of the tuple (if `is_tuple`) or list
+    fn member(&self, member: &Value, is_tuple: bool) -> RuntimeResult {
         if let Value::Number(index) = member {
             let len = self.values.borrow().len();
-            let target_index = to_index(*index, len);
+            let target_index = to_index(*index, len)?;
 
-            return self.values.borrow()[target_index].clone();
+            return Ok(self.values.borrow()[target_index].clone());
         }
 
         if let Value::String(member) = member {
-            match self.is_tuple {
+            match is_tuple {
                 true => {
-                    if TUPLE_FUNCTIONS.contains(&&*member.0) {
-                        return lib_function!(self, "tuple", member.0.clone(), Value::Tuple);
+                    if TUPLE_FUNCTIONS.contains(&member.0.as_str()) {
+                        return Ok(lib_function!(self, "tuple", member.0.clone(), Value::Tuple));
                     }
                 }
                 false => {
-                    if LIST_FUNCTIONS.contains(&&*member.0) {
-                        return lib_function!(self, "list", member.0.clone(), Value::List);
+                    if LIST_FUNCTIONS.contains(&member.0.as_str()) {
+                        return Ok(lib_function!(self, "list", member.0.clone(), Value::List));
                     }
                 }
             }
         }
 
-        panic!("Cannot get member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
+    }
+
+    pub fn get_tuple_member(&self, member: &Value) -> RuntimeResult {
+        self.member(member, true)
+    }
+}
+
+impl Debug for TList {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("List").unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TList {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
+        self.member(member, false)
     }
 
-    fn set_member(&mut self, member: &Value, value: Value) {
+    fn set_member(&mut self, member: &Value, value: Value) -> RuntimeResult<()> {
         if let Value::Number(index) = member {
             let len = self.values.borrow().len();
-            let target_index = to_index(*index, len);
+            let target_index = to_index(*index, len)?;
 
             self.values.borrow_mut()[target_index] = value;
-            return;
+            return Ok(());
         }
 
-        panic!("Cannot set member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot set member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 }
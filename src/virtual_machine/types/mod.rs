@@ -1,7 +1,12 @@
+pub mod class;
 pub mod dict;
 pub mod r#enum;
 pub mod r#struct;
 pub mod function;
+pub mod interface;
+pub mod iterator;
 pub mod list;
+pub mod range;
 pub mod string;
 pub mod structdef;
+pub mod variant;
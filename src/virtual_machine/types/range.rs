@@ -0,0 +1,12 @@
+use bincode::{Decode, Encode};
+
+use crate::virtual_machine::value::Value;
+
+/// `start..end` (or `start..=end`) counting by `step`
+#[derive(Encode, Decode, Debug, Clone, PartialEq, PartialOrd, Hash)]
+pub struct TRange {
+    pub start: Value,
+    pub end: Value,
+    pub step: Value,
+    pub inclusive: bool,
+}
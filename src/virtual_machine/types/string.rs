@@ -3,6 +3,7 @@ use std::{fmt::Debug, rc::Rc};
 use crate::{
     misc::to_index::to_index,
     virtual_machine::{
+        error::RuntimeResult,
         libs::types::string_lib::STRING_FUNCTIONS, traits::member_accessible::IMemberAccessible,
         types::function::TFunction, value::Value, vm::VM,
     },
@@ -10,16 +11,16 @@ use crate::{
 use bincode::{Decode, Encode};
 
 #[derive(Encode, Decode, Clone, PartialEq, PartialOrd)]
-pub struct TString(pub Rc<str>);
+pub struct TString(pub Rc<String>);
 
 impl TString {
     pub fn new(s: String) -> Self {
-        Self(Rc::from(s))
+        Self(Rc::new(s))
     }
 
     #[allow(unused)]
     pub fn from_str(s: &str) -> Self {
-        Self(Rc::from(s))
+        Self(Rc::new(s.to_string()))
     }
 
     pub fn to_string(&self) -> String {
@@ -36,58 +37,60 @@ impl Debug for TString {
 
 // MEMBER ACCESS
 impl IMemberAccessible for TString {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
         if let Value::Number(index) = member {
             let len = self.0.len();
-            let target_index = to_index(*index, len);
+            let target_index = to_index(*index, len)?;
 
-            return Value::Char(self.0.chars().nth(target_index).unwrap());
+            return Ok(Value::Char(self.0.chars().nth(target_index).unwrap()));
         }
 
         if let Value::String(member) = member {
-            if STRING_FUNCTIONS.contains(&&*member.0) {
-                return lib_function!(self, "string", member.0.clone(), Value::String);
+            if STRING_FUNCTIONS.contains(&member.0.as_str()) {
+                return Ok(lib_function!(self, "string", member.0.clone(), Value::String));
             }
         }
 
-        if let Value::Range {
-            start,
-            end,
-            step,
-            inclusive,
-        } = member
-        {
+        if let Value::Range(range) = member {
             let chars: Vec<char> = self.0.chars().collect();
             let len = chars.len();
 
-            let start_i = if let Value::Number(n) = start.as_ref() {
-                to_index(*n, len)
+            let start_i = if let Value::Number(n) = range.start {
+                to_index(n, len)?
             } else {
                 0
             };
 
-            let end_i = if let Value::Number(n) = end.as_ref() {
-                let i = to_index(*n, len);
-                if *inclusive { i + 1 } else { i }
+            let end_i = if let Value::Number(n) = range.end {
+                let i = to_index(n, len)?;
+                if range.inclusive { i + 1 } else { i }
             } else {
                 len
             };
 
-            let step_by = if let Value::Number(n) = step.as_ref() {
-                (*n as usize).max(1)
+            let step_by = if let Value::Number(n) = range.step {
+                (n as usize).max(1)
             } else {
                 1
             };
 
             let slice: String = chars[start_i..end_i].iter().step_by(step_by).collect();
 
-            return Value::String(TString::new(slice));
+            return Ok(Value::String(TString::new(slice)));
         }
 
-        panic!("Cannot get member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 
-    fn set_member(&mut self, member: &Value, _value: Value) {
-        panic!("Cannot set member `{}` on {self:?}", member.to_string(true));
+    fn set_member(&mut self, member: &Value, _value: Value) -> RuntimeResult<()> {
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot set member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 }
@@ -1,6 +1,8 @@
 use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
 
 use crate::virtual_machine::{
+    error::RuntimeResult,
+    gc::{self, Tracked},
     traits::member_accessible::IMemberAccessible, types::structdef::TStructDef, value::Value,
     vm::VM,
 };
@@ -20,6 +22,7 @@ impl PartialOrd for TStruct {
 
 impl TStruct {
     pub fn new(base: Rc<TStructDef>, values: Rc<RefCell<HashMap<String, Value>>>) -> Self {
+        gc::track(Tracked::Fields(Rc::downgrade(&values)));
         Self { base, values }
     }
 }
@@ -33,39 +36,57 @@ impl Debug for TStruct {
 
 // MEMBER ACCESS
 impl IMemberAccessible for TStruct {
-    fn get_member(&self, _vm: &mut VM, member: &Value) -> Value {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
         if let Value::String(member) = member {
             if let Some(v) = self.values.borrow().get(&*member.0) {
-                return v.clone();
+                return Ok(v.clone());
+            }
+
+            // Methods are bound to the struct they were read from, which becomes `self`
+            if let Some(method) = self.base.methods.borrow().get(&*member.0) {
+                let mut method = method.clone();
+                method.this = Some(Box::new(Value::Struct(rc!(self.clone()))));
+
+                return Ok(Value::Function(rc!(method)));
             }
         }
 
-        panic!("Cannot get member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 
-    fn set_member(&mut self, member: &Value, value: Value) {
+    fn set_member(&mut self, member: &Value, value: Value) -> RuntimeResult<()> {
         if let Value::String(member) = member {
             if let Some(v_type) = self.base.fields.get(&*member.0) {
                 if !value.type_matches(v_type) {
-                    panic!(
+                    return Err(runtime_error!(
+                        TypeError,
                         "Field '{}' expects type `{v_type}`, got `{}`. (Struct '{}')",
                         &*member.0,
-                        value.get_type(),
+                        value.hint_type(),
 						self.base.name
-                    )
+                    ))
                 }
             } else {
-                panic!(
+                return Err(runtime_error!(
+                    PropertyError,
                     "Tried setting unknown field on struct of base {}",
                     self.base.name
-                );
+                ));
             }
             if let Some(v) = self.values.borrow_mut().get_mut(&*member.0) {
                 *v = value;
-                return;
+                return Ok(());
             }
         }
 
-        panic!("Cannot set member `{}` on {self:?}", member.to_string(true));
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot set member `{}` on {self:?}",
+            member.to_string(true)
+        ))
     }
 }
@@ -1,12 +1,19 @@
-use std::{collections::HashMap, fmt::Debug, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, fmt::Debug, rc::Rc};
 
-use crate::virtual_machine::{traits::member_accessible::IMemberAccessible};
+use crate::virtual_machine::{
+    error::RuntimeResult, traits::member_accessible::IMemberAccessible,
+    types::function::TFunction, value::Value, vm::VM,
+};
 use bincode::{Decode, Encode};
 
 #[derive(Encode, Decode, Clone, PartialEq)]
 pub struct TStructDef {
 	pub name: String,
     pub fields: Rc<HashMap<String, String>>,
+    /// Functions from `impl` blocks that take `self`
+    pub methods: RefCell<HashMap<String, TFunction>>,
+    /// Functions from `impl` blocks called on the definition, like `Point::new`
+    pub statics: RefCell<HashMap<String, TFunction>>,
 }
 
 impl PartialOrd for TStructDef {
@@ -17,7 +24,12 @@ impl PartialOrd for TStructDef {
 
 impl TStructDef {
     pub fn new(name: String, fields: Rc<HashMap<String, String>>) -> Self {
-        Self { name, fields }
+        Self {
+            name,
+            fields,
+            methods: RefCell::new(HashMap::new()),
+            statics: RefCell::new(HashMap::new()),
+        }
     }
 }
 
@@ -30,5 +42,25 @@ impl Debug for TStructDef {
 
 // MEMBER ACCESS
 impl IMemberAccessible for TStructDef {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
+        if let Value::String(member) = member {
+            // Methods read from the definition aren't bound, `self` is their first argument
+            let function = self
+                .statics
+                .borrow()
+                .get(&*member.0)
+                .or(self.methods.borrow().get(&*member.0))
+                .cloned();
 
+            if let Some(function) = function {
+                return Ok(Value::Function(rc!(function)));
+            }
+        }
+
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
+    }
 }
@@ -0,0 +1,107 @@
+use std::{fmt::Debug, rc::Rc};
+
+use crate::virtual_machine::{
+    error::RuntimeResult, traits::member_accessible::IMemberAccessible, value::Value, vm::VM,
+};
+use bincode::{Decode, Encode};
+
+/// A variant of an enum that carries data, like `Circle(r)` or `Rect { w, h }`
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct TVariantDef {
+    pub enum_name: String,
+    pub name: String,
+    pub fields: Vec<String>, // in declaration order
+    pub is_struct: bool,
+}
+
+impl PartialOrd for TVariantDef {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl TVariantDef {
+    pub fn new(enum_name: String, name: String, fields: Vec<String>, is_struct: bool) -> Self {
+        Self {
+            enum_name,
+            name,
+            fields,
+            is_struct,
+        }
+    }
+
+    /// Builds a variant from values given in field order.
+    pub fn construct(self: &Rc<Self>, values: Vec<Value>) -> RuntimeResult<TVariant> {
+        if values.len() != self.fields.len() {
+            return Err(runtime_error!(
+                TypeError,
+                "Variant `{}.{}` takes {} value(s), got {}",
+                self.enum_name,
+                self.name,
+                self.fields.len(),
+                values.len()
+            ));
+        }
+
+        Ok(TVariant::new(Rc::clone(self), Rc::new(values)))
+    }
+}
+
+impl Debug for TVariantDef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("VariantDef:{}.{}", self.enum_name, self.name))
+            .unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TVariantDef {}
+
+/// A value built from a variant. Variants are immutable and compare by their contents.
+#[derive(Encode, Decode, Clone, PartialEq)]
+pub struct TVariant {
+    pub def: Rc<TVariantDef>,
+    pub values: Rc<Vec<Value>>,
+}
+
+impl PartialOrd for TVariant {
+    fn partial_cmp(&self, _other: &Self) -> Option<std::cmp::Ordering> {
+        None
+    }
+}
+
+impl TVariant {
+    pub fn new(def: Rc<TVariantDef>, values: Rc<Vec<Value>>) -> Self {
+        Self { def, values }
+    }
+}
+
+impl Debug for TVariant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("Variant:{}.{}", self.def.enum_name, self.def.name))
+            .unwrap();
+        Ok(())
+    }
+}
+
+// MEMBER ACCESS
+impl IMemberAccessible for TVariant {
+    fn get_member(&self, _vm: &mut VM, member: &Value) -> RuntimeResult {
+        let index = match member {
+            Value::Number(index) if index.fract() == 0.0 && *index >= 0.0 => Some(*index as usize),
+            Value::String(member) => self.def.fields.iter().position(|f| **f == *member.0),
+            _ => None,
+        };
+
+        if let Some(value) = index.and_then(|i| self.values.get(i)) {
+            return Ok(value.clone());
+        }
+
+        Err(runtime_error!(
+            PropertyError,
+            "Cannot get member `{}` on {self:?}",
+            member.to_string(true)
+        ))
+    }
+}
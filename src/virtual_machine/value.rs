@@ -7,13 +7,16 @@ use std::{
 };
 
 use crate::virtual_machine::{
+    error::RuntimeResult,
     namespaces::namespace::TNamespace,
     types::{
-        dict::TDict, r#enum::TEnum, function::TFunction, list::TList, string::TString,
-        r#struct::TStruct, structdef::TStructDef,
+        class::{TClass, TObject}, dict::TDict, interface::TInterface, r#enum::TEnum, function::TFunction, iterator::TIterator, list::TList, range::TRange, string::TString,
+        r#struct::TStruct, structdef::TStructDef, variant::{TVariant, TVariantDef},
     },
 };
 
+/// A value is at most 16 bytes: scalars are stored inline and everything else is behind a
+/// single `Rc`, so copying one around the stack never allocates.
 #[allow(unused)]
 #[derive(Encode, Decode, Debug, Clone, PartialEq, PartialOrd)]
 pub enum Value {
@@ -23,28 +26,34 @@ pub enum Value {
     Char(char),
     String(TString),
 
-    Function(TFunction),
+    Function(Rc<TFunction>),
 
     // Collections
     List(TList),
     Tuple(TList),
     Dict(TDict),
 
-    Struct(TStruct),
+    Struct(Rc<TStruct>),
     StructDef(Rc<TStructDef>),
 
+    Class(Rc<TClass>),
+    Object(Rc<TObject>),
+    Interface(Rc<TInterface>),
+
     // Namespaces
     Namespace(Rc<RefCell<TNamespace>>),
-    Enum(TEnum),
-
-    Range {
-        start: Box<Value>,
-        end: Box<Value>,
-        step: Box<Value>,
-        inclusive: bool,
-    },
+    Enum(Rc<TEnum>),
+    Variant(Rc<TVariant>),
+    VariantDef(Rc<TVariantDef>),
+
+    Range(Rc<TRange>),
+    Iterator(TIterator),
+    /// Returned by `next()` once an iterator has run out, so nil can still be an item
+    Done,
 }
 
+const _: () = assert!(std::mem::size_of::<Value>() <= 16);
+
 impl ToString for Value {
     fn to_string(&self) -> String {
         self.to_string(false)
@@ -62,6 +71,14 @@ impl Value {
         Value::String(TString::new(x.to_string()))
     }
 
+    /// Type names that don't need a declaration, everything `get_type` returns for builtin
+    /// values plus the ones only hints use
+    pub const BUILTIN_TYPES: [&str; 20] = [
+        "nil", "number", "bool", "char", "string", "function", "list", "tuple", "dict",
+        "namespace", "enum", "variantdef", "range", "iterator", "done", "structdef", "class",
+        "interface", "any", "fn",
+    ];
+
     pub fn get_type(&self) -> String {
         match self {
             Value::NIL => "nil",
@@ -75,9 +92,16 @@ impl Value {
             Value::Dict(_) => "dict",
             Value::Namespace(_) => "namespace",
             Value::Enum(_) => "enum",
-            Value::Range { .. } => "range",
+            Value::Variant(variant) => &variant.def.enum_name,
+            Value::VariantDef(..) => "variantdef",
+            Value::Range(_) => "range",
+            Value::Iterator(_) => "iterator",
+            Value::Done => "done",
             Value::StructDef(..) => "structdef",
             Value::Struct(data) => &data.base.name,
+            Value::Class(..) => "class",
+            Value::Object(obj) => &obj.class.name,
+            Value::Interface(..) => "interface",
         }
         .to_owned()
     }
@@ -185,20 +209,46 @@ impl Value {
             Self::Namespace(space) => format!("namespace:{}", space.borrow().name),
 
             Self::Enum(e) => format!("enum:{}", e.name),
+            Self::Variant(variant) => {
+                let def = &variant.def;
+                let values = variant.values.iter().map(|v| v.to_string(true));
+
+                if def.fields.is_empty() {
+                    format!("{}.{}", def.enum_name, def.name)
+                } else if def.is_struct {
+                    format!(
+                        "{}.{} {{ {} }}",
+                        def.enum_name,
+                        def.name,
+                        def.fields
+                            .iter()
+                            .zip(values)
+                            .map(|(k, v)| format!("{k}: {v}"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                } else {
+                    format!(
+                        "{}.{}({})",
+                        def.enum_name,
+                        def.name,
+                        values.collect::<Vec<_>>().join(", ")
+                    )
+                }
+            }
+            Self::VariantDef(def) => format!("variantdef:{}.{}", def.enum_name, def.name),
 
-            Self::Range {
-                start,
-                end,
-                step,
-                inclusive,
-            } => format!(
+            Self::Range(range) => format!(
                 "Range<{}..{}{}..{}>",
-                start.to_string(true),
-                if *inclusive { "=" } else { "" },
-                end.to_string(true),
-                step.to_string(true),
+                range.start.to_string(true),
+                if range.inclusive { "=" } else { "" },
+                range.end.to_string(true),
+                range.step.to_string(true),
             ),
 
+            Self::Iterator(_) => "<iterator>".to_string(),
+            Self::Done => "Done".to_string(),
+
             Value::StructDef(def) => format!("structdef:{}", def.name),
             Value::Struct(data) => format!(
                 "{} {{ {} }}",
@@ -222,9 +272,50 @@ impl Value {
                     .collect::<Vec<_>>()
                     .join(", ")
             ),
+
+            Value::Class(class) => format!("class:{}", class.name),
+            Value::Interface(interface) => format!("interface:{}", interface.name),
+            Value::Object(obj) => {
+                let values = obj.values.borrow();
+
+                format!(
+                    "{} {{ {} }}",
+                    obj.class.name,
+                    obj.class
+                        .fields
+                        .iter()
+                        .map(|k| {
+                            let value = match values.get(k) {
+                                Some(Value::Object(val))
+                                    if obj.values.as_ptr() == val.values.as_ptr() =>
+                                {
+                                    String::from("{...}")
+                                }
+                                Some(v) => v.to_string(true),
+                                None => String::from("nil"),
+                            };
+
+                            format!("{k}: {value}")
+                        })
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            }
         }
     }
 
+    /// A special method like `__add__` from a struct's `impl` or a class, bound to this value.
+    pub fn special_method(&self, name: &str) -> Option<Rc<TFunction>> {
+        let mut method = match self {
+            Value::Struct(data) => data.base.methods.borrow().get(name).cloned(),
+            Value::Object(obj) => obj.class.methods.get(name).cloned(),
+            _ => None,
+        }?;
+
+        method.this = Some(Box::new(self.clone()));
+        Some(rc!(method))
+    }
+
     pub fn is_truthy(&self) -> bool {
         match self {
             Self::NIL => false,
@@ -233,26 +324,130 @@ impl Value {
         }
     }
 
-    pub fn as_number(&self) -> f64 {
+    pub fn as_number(&self) -> RuntimeResult<f64> {
         if let Value::Number(x) = self {
-            *x
+            Ok(*x)
         } else {
-            panic!("Cannot convert `{self:?}` to number.")
+            Err(runtime_error!(TypeError, "Cannot convert `{self:?}` to number."))
         }
     }
 
+    /// Whether this value fits `type_hint`: the name of a type (`number`, `tuple`, `function`,
+    /// a struct, class, interface or enum...), `any`, `?T` for `T` or nil, alternatives like
+    /// `number | string`, or a tuple of element types like `(number, string)`.
     pub fn type_matches(&self, type_hint: &str) -> bool {
+        let alternatives = Self::split_type_hint(type_hint, '|');
+        if alternatives.len() > 1 {
+            return alternatives.iter().any(|hint| self.type_matches(hint));
+        }
+
+        let type_hint = type_hint.trim();
+        if let Some(hint) = type_hint.strip_prefix('?') {
+            return matches!(self, Value::NIL) || self.type_matches(hint);
+        }
+
+        if let Some(inner) = type_hint.strip_prefix('(').and_then(|h| h.strip_suffix(')')) {
+            let Some(elements) = Self::tuple_elements(inner) else {
+                return self.type_matches(inner);
+            };
+            let Value::Tuple(tuple) = self else {
+                return false;
+            };
+
+            let values = tuple.values.borrow();
+            return values.len() == elements.len()
+                && values.iter().zip(elements).all(|(value, hint)| value.type_matches(hint));
+        }
+
         match type_hint {
-            "number" => matches!(self, Value::Number(_)),
-            "string" => matches!(self, Value::String(_)),
-            "bool" => matches!(self, Value::Bool(_)),
-            "char" => matches!(self, Value::Char(_)),
-            "list" => matches!(self, Value::List(_)),
-            "dict" => matches!(self, Value::Dict(_)),
             "any" => true,
-            // other check (nothing)
-            _ => true,
+            "fn" => matches!(self, Value::Function(_)),
+
+            // Objects also fit the interfaces their class implements
+            name if let Value::Object(obj) = self
+                && obj.class.interfaces.iter().any(|interface| interface.name == name) =>
+            {
+                true
+            }
+            name => self.get_type() == name,
+        }
+    }
+
+    /// The type of this value the way a hint spells it, so tuples list their element types
+    pub fn hint_type(&self) -> String {
+        match self {
+            Value::Tuple(tuple) => {
+                let values = tuple.values.borrow();
+                match values.as_slice() {
+                    [value] => format!("({},)", value.hint_type()),
+                    _ => format!(
+                        "({})",
+                        values.iter().map(Value::hint_type).collect::<Vec<_>>().join(", ")
+                    ),
+                }
+            }
+            _ => self.get_type(),
+        }
+    }
+
+    /// Names of the types `type_hint` refers to, inside tuples and alternatives too
+    pub fn type_hint_names(type_hint: &str) -> Vec<&str> {
+        let mut names = vec![];
+
+        for hint in Self::split_type_hint(type_hint, '|') {
+            let hint = hint.trim().trim_start_matches('?').trim();
+
+            match hint.strip_prefix('(').and_then(|h| h.strip_suffix(')')) {
+                Some(inner) => match Self::tuple_elements(inner) {
+                    Some(elements) => {
+                        for element in elements {
+                            names.extend(Self::type_hint_names(element));
+                        }
+                    }
+                    None => names.extend(Self::type_hint_names(inner)),
+                },
+                None => names.push(hint),
+            }
         }
+        names
+    }
+
+    /// The element types of a tuple hint, given what's between its parentheses, or `None` if
+    /// they only group a single hint, like `(number | string)`. `(number,)` is a tuple of one.
+    fn tuple_elements(inner: &str) -> Option<Vec<&str>> {
+        if inner.trim().is_empty() {
+            return Some(vec![]);
+        }
+
+        let mut elements = Self::split_type_hint(inner, ',');
+        if elements.len() == 1 {
+            return None;
+        }
+        if elements.last().is_some_and(|last| last.trim().is_empty()) {
+            elements.pop();
+        }
+        Some(elements)
+    }
+
+    /// Splits `type_hint` at the `separator`s that aren't inside parentheses.
+    fn split_type_hint(type_hint: &str, separator: char) -> Vec<&str> {
+        let mut parts = vec![];
+        let mut depth = 0;
+        let mut start = 0;
+
+        for (i, c) in type_hint.char_indices() {
+            match c {
+                '(' => depth += 1,
+                ')' => depth -= 1,
+                c if c == separator && depth == 0 => {
+                    parts.push(&type_hint[start..i]);
+                    start = i + 1;
+                }
+                _ => (),
+            }
+        }
+        parts.push(&type_hint[start..]);
+        parts
     }
 }
 
@@ -263,7 +458,7 @@ impl Hash for Value {
         std::mem::discriminant(self).hash(state);
 
         match self {
-            Self::NIL => (),
+            Self::NIL | Self::Done => (),
             Self::Number(n) => {
                 // We convert to bits to provide a consistent hash.
                 n.to_bits().hash(state);
@@ -287,19 +482,20 @@ impl Hash for Value {
             Self::Namespace(space) => space.borrow().hash(state),
 
             Self::Enum(e) => std::ptr::hash(e.values.as_ref(), state),
-
-            Self::Range {
-                start,
-                end,
-                step,
-                inclusive,
-            } => {
-                start.hash(state);
-                end.hash(state);
-                step.hash(state);
-                inclusive.hash(state);
+            Self::Variant(variant) => {
+                variant.def.enum_name.hash(state);
+                variant.def.name.hash(state);
+                variant.values.hash(state);
+            }
+            Self::VariantDef(def) => {
+                def.enum_name.hash(state);
+                def.name.hash(state);
             }
 
+            Self::Range(range) => range.hash(state),
+
+            Self::Iterator(iterator) => std::ptr::hash(Rc::as_ptr(&iterator.state), state),
+
             Self::StructDef(def) => {
                 def.name.hash(state);
                 std::ptr::hash(Rc::as_ptr(&def.fields), state);
@@ -308,6 +504,10 @@ impl Hash for Value {
                 def.base.name.hash(state);
                 def.values.as_ptr().hash(state);
             }
+
+            Self::Class(class) => std::ptr::hash(Rc::as_ptr(class), state),
+            Self::Object(obj) => std::ptr::hash(obj.values.as_ptr(), state),
+            Self::Interface(interface) => std::ptr::hash(Rc::as_ptr(interface), state),
         }
     }
 }
@@ -1,47 +1,79 @@
 use crate::{
+    misc::source::Location,
     virtual_machine::{
-        chunk::Chunk,
-        inst::Inst,
+        chunk::{Chunk, LineInfo},
+        error::{RuntimeError, RuntimeResult},
+        gc::{self, Tracked},
+        inst::{Capture, Inst},
         libs::{
             lib::Library,
-            namespaces::{fs_lib::FSLib, io_lib::IOLib, math_lib::MathLib},
+            namespaces::{env_lib::EnvLib, fs_lib::FSLib, gc_lib::GCLib, io_lib::IOLib, math_lib::MathLib},
             type_lib::TypeLib,
             types::{
-                dict_lib::DictLib, list_lib::ListLib, string_lib::StringLib, tuple_lib::TupleLib,
+                dict_lib::DictLib, iter_lib::IterLib, list_lib::ListLib, string_lib::StringLib, tuple_lib::TupleLib,
             },
         },
-        namespaces::standard_namespace::load_standard_namespace,
+        namespaces::{namespace::TNamespace, standard_namespace::load_standard_namespace},
+        reg_inst::RegInst,
+        register_vm::RegisterFrame,
         traits::member_accessible::IMemberAccessible,
         types::{
-            dict::TDict, r#enum::TEnum, function::TFunction, list::TList, string::TString,
+            class::{TClass, TObject}, dict::TDict, iterator::{GeneratorFrame, TIterator}, r#enum::TEnum,
+            function::{TFunction, Upvalue, UpvalueCell}, list::TList, range::TRange, string::TString,
             r#struct::TStruct,
         },
         value::Value,
     },
 };
+use bincode::{Decode, Encode};
 use simply_colored::*;
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, collections::HashMap, error::Error, rc::Rc};
 
 const ORANGE: &str = "\x1b[38;2;255;150;60m";
 
 pub struct CallFrame {
-    scope_base: usize,
+    /// Index into `slots` of its first local
+    slot_base: usize,
     return_addr: usize,
-    upvalues: Vec<Rc<RefCell<HashMap<u64, (Value, bool)>>>>,
+    upvalues: Vec<UpvalueCell>,
+    handlers: Vec<Handler>,
+    /// Stack and iterator lengths where a resumed generator's frame starts
+    generator: Option<(usize, usize)>,
+}
+
+/// A `try` block that is currently running, and the VM state to go back to if it fails.
+#[derive(Encode, Decode, Clone)]
+pub struct Handler {
+    catch_addr: usize,
+    stack_len: usize,
+    slots_len: usize,
+    iterators_len: usize,
 }
 
 pub struct VM {
     pub pos: usize,
-    pub instructions: Vec<Inst>,
+    pub instructions: Rc<Vec<Inst>>,
     pub stack: Vec<Value>,
     pub call_stack: Vec<CallFrame>,
     pub constants: Vec<Value>,
     pub globals: HashMap<u64, (Value, bool)>,
-    pub locals: Vec<Rc<RefCell<HashMap<u64, (Value, bool)>>>>,
+    /// Locals of every frame, each one's starting at its `slot_base`
+    pub slots: Vec<Value>,
+    /// Upvalues of closures that still point into `slots`, sorted by slot
+    open_upvalues: Vec<UpvalueCell>,
     pub libraries: HashMap<u64, Box<dyn Library>>,
-    pub iterators: Vec<(Value, usize)>,
+    pub iterators: Vec<TIterator>,
     pub intern_table: HashMap<u64, Rc<str>>,
     pub expose_interns: bool,
+    pub script_args: Vec<String>,
+    pub files: Vec<Rc<str>>,
+    pub lines: Vec<LineInfo>,
+    /// The frame a `YIELD` just suspended, until `resume_generator` picks it up
+    suspended: Option<GeneratorFrame>,
+    /// Code of a chunk compiled for the register VM, which runs it instead of `instructions`
+    pub register_code: Rc<Vec<RegInst>>,
+    pub registers: Vec<Value>,
+    pub register_frames: Vec<RegisterFrame>,
 }
 
 #[allow(unused)]
@@ -49,20 +81,30 @@ impl VM {
     pub fn new() -> Self {
         Self {
             pos: 0,
-            instructions: vec![],
+            instructions: rc!(vec![]),
             stack: Vec::with_capacity(100),
             call_stack: vec![CallFrame {
-                scope_base: 0,
+                slot_base: 0,
                 return_addr: 0,
                 upvalues: vec![],
+                handlers: vec![],
+                generator: None,
             }],
             constants: Vec::with_capacity(100),
             globals: Self::initialize_globals(),
-            locals: vec![rc!(RefCell::new(HashMap::new()))],
+            slots: vec![],
+            open_upvalues: vec![],
             libraries: Self::initialize_libs(),
             iterators: vec![],
             intern_table: HashMap::new(),
             expose_interns: true,
+            script_args: vec![],
+            files: vec![],
+            lines: vec![],
+            suspended: None,
+            register_code: rc!(vec![]),
+            registers: vec![],
+            register_frames: vec![],
         }
     }
 
@@ -80,6 +122,10 @@ impl VM {
             hash_u64!("typeof"),
             (lib_function!("type", "typeof"), false),
         );
+        globals.insert(
+            hash_u64!("implements"),
+            (lib_function!("type", "implements"), false),
+        );
         globals.insert(
             hash_u64!("number"),
             (lib_function!("type", "number"), false),
@@ -90,6 +136,8 @@ impl VM {
             hash_u64!("string"),
             (lib_function!("type", "string"), false),
         );
+        globals.insert(hash_u64!("iter"), (lib_function!("iter", "iter"), false));
+        globals.insert(hash_u64!("Done"), (Value::Done, true));
 
         return globals;
     }
@@ -103,11 +151,14 @@ impl VM {
         libs.insert(hash_u64!("list"), Box::new(ListLib));
         libs.insert(hash_u64!("tuple"), Box::new(TupleLib));
         libs.insert(hash_u64!("dict"), Box::new(DictLib));
+        libs.insert(hash_u64!("iter"), Box::new(IterLib));
 
         // namespaces
         libs.insert(hash_u64!("Math"), Box::new(MathLib));
         libs.insert(hash_u64!("IO"), Box::new(IOLib));
         libs.insert(hash_u64!("FS"), Box::new(FSLib));
+        libs.insert(hash_u64!("Env"), Box::new(EnvLib));
+        libs.insert(hash_u64!("GC"), Box::new(GCLib));
 
         libs
     }
@@ -117,7 +168,28 @@ impl VM {
     }
 
     pub fn push_inst(&mut self, instruction: Inst) {
-        self.instructions.push(instruction);
+        Rc::make_mut(&mut self.instructions).push(instruction);
+    }
+
+    /// Appends freshly compiled instructions (used by the REPL to grow the program).
+    pub fn extend_instructions(&mut self, instructions: &[Inst]) {
+        Rc::make_mut(&mut self.instructions).extend_from_slice(instructions);
+    }
+
+    /// Source location of the instruction at `pos`, if the line table covers it.
+    pub fn location(&self, pos: usize) -> Option<Location> {
+        LineInfo::find(&self.lines, pos)?.location(&self.files)
+    }
+
+    /// Drops everything left behind by an interrupted run and returns to the top-level frame.
+    pub fn reset_to_top_level(&mut self) {
+        self.stack.clear();
+        self.call_stack.truncate(1);
+        self.call_stack[0].handlers.clear();
+        self.close_upvalues(0);
+        self.slots.clear();
+        self.iterators.clear();
+        self.pos = self.instructions.len();
     }
 
     #[inline]
@@ -149,36 +221,503 @@ impl VM {
         self.constants.extend(constants);
     }
 
-    pub fn call_function(&mut self, f: TFunction, mut args_count: usize) {
+    pub fn call_function(&mut self, f: Rc<TFunction>, mut args_count: usize) -> RuntimeResult<()> {
         if let Some((library, method)) = f.handler {
-            if let Some(this) = f.this {
-                self.stack.push(*this);
+            if let Some(this) = &f.this {
+                self.stack.push((**this).clone());
+                args_count += 1;
+            }
+            let args: Vec<_> = (0..args_count).map(|_| self.pop()).collect();
+
+            let value = self.call_library(library, method, args)?;
+            self.stack.push(value);
+        } else {
+            // Methods take the object they were read from as their first parameter
+            if let Some(this) = &f.this {
+                self.stack.insert(self.stack.len() - args_count, (**this).clone());
                 args_count += 1;
             }
-            let mut args: Vec<_> = (0..args_count).map(|_| self.pop()).collect();
 
-            if let Some(lib) = self.libraries.get(&library) {
-                let value = lib.get_function(method)(self, args);
-                self.stack.push(value);
+            // Missing arguments are nil and extra ones are dropped
+            if args_count < f.arity {
+                self.stack
+                    .extend((args_count..f.arity).map(|_| Value::NIL));
             } else {
-                println!("macro hash: {}", hash_u64!("Math"));
-                println!("handler hash: {}", library);
-                println!("handler name: {}", self.lookup_intern(library));
-
-                panic!(
-                    "Library not found for handler key: {} (method: {})",
-                    library,
-                    self.lookup_intern(method)
-                );
+                self.stack.truncate(self.stack.len() - (args_count - f.arity));
             }
-        } else {
+
+            // The body of a generator only starts running once it's asked for an item
+            if f.generator {
+                let args = self.stack.split_off(self.stack.len() - f.arity);
+                self.stack.push(Value::Iterator(TIterator::generator(f.entry, f.upvalues.clone(), args)));
+                return Ok(());
+            }
+
             self.call_stack.push(CallFrame {
-                scope_base: self.locals.len(),
+                slot_base: self.slots.len(),
                 return_addr: self.pos,
-                upvalues: f.upvalues,
+                upvalues: f.upvalues.clone(),
+                handlers: vec![],
+                generator: None,
             });
             self.pos = f.entry;
         }
+
+        Ok(())
+    }
+
+    /// Calls the value on top of the stack with the `args_count` values below it. Returns true
+    /// if that jumped into a function, which leaves its result once it returns.
+    fn call(&mut self, args_count: usize) -> RuntimeResult<bool> {
+        let func = self.pop();
+
+        if let Value::Function(f) = func {
+            let jumped = f.handler.is_none() && !f.generator;
+            self.call_function(f, args_count)?;
+            return Ok(jumped);
+        } else if let Value::VariantDef(def) = func {
+            let mut values = (0..args_count).map(|_| self.pop()).collect::<Vec<_>>();
+            values.reverse();
+
+            self.stack.push(Value::Variant(rc!(def.construct(values)?)));
+        } else if let Value::Class(class) = func {
+            // Calling a class runs its constructor on a fresh object
+            let mut init = class.methods["init"].clone();
+            init.this = Some(Box::new(Value::Object(rc!(class.instantiate()))));
+
+            self.call_function(rc!(init), args_count)?;
+            return Ok(true);
+        } else {
+            return Err(runtime_error!(
+                TypeError,
+                "Tried calling non-function: {func:?}"
+            ));
+        }
+
+        Ok(false)
+    }
+
+    /// Index into `slots` where the current frame's locals start
+    #[inline]
+    fn slot_base(&self) -> usize {
+        self.call_stack.last().map_or(0, |frame| frame.slot_base)
+    }
+
+    fn open_slot(cell: &UpvalueCell) -> usize {
+        match *cell.borrow() {
+            Upvalue::Open(slot) => slot,
+            Upvalue::Closed(_) => unreachable!("Closed upvalue in the open list"),
+        }
+    }
+
+    /// The upvalue of `slots[slot]`, shared with the closures that captured it already.
+    fn capture_upvalue(&mut self, slot: usize) -> UpvalueCell {
+        let index = self
+            .open_upvalues
+            .partition_point(|cell| Self::open_slot(cell) < slot);
+
+        if let Some(cell) = self.open_upvalues.get(index)
+            && Self::open_slot(cell) == slot
+        {
+            return Rc::clone(cell);
+        }
+
+        let cell = rc!(RefCell::new(Upvalue::Open(slot)));
+        gc::track(Tracked::Upvalue(Rc::downgrade(&cell)));
+        self.open_upvalues.insert(index, Rc::clone(&cell));
+        cell
+    }
+
+    /// Moves the values of the slots from `from` on into the upvalues open on them, before
+    /// the slots are dropped or reused.
+    fn close_upvalues(&mut self, from: usize) {
+        while let Some(cell) = self.open_upvalues.last() {
+            let slot = Self::open_slot(cell);
+            if slot < from {
+                break;
+            }
+
+            *cell.borrow_mut() = Upvalue::Closed(self.slots[slot].clone());
+            self.open_upvalues.pop();
+        }
+    }
+
+    /// Runs the library function `method` of `library`. `args` start with `this` (if it's a
+    /// method) followed by the arguments, last one first.
+    pub fn call_library(&mut self, library: u64, method: u64, args: Vec<Value>) -> RuntimeResult {
+        if let Some(lib) = self.libraries.get(&library) {
+            let Some(function) = lib.get_function(method) else {
+                return Err(runtime_error!(
+                    PropertyError,
+                    "Unknown function `{}` on lib {}",
+                    self.lookup_intern(method),
+                    lib.get_name()
+                ));
+            };
+
+            function(self, args)
+        } else {
+            Err(runtime_error!(
+                InternalError,
+                "Library not found for handler key: {} (method: {})",
+                library,
+                self.lookup_intern(method)
+            ))
+        }
+    }
+
+    /// Calls `f` from native code (library functions) and runs it to completion.
+    pub fn call_value(&mut self, f: Rc<TFunction>, args: Vec<Value>) -> RuntimeResult {
+        let is_native = f.handler.is_some() || f.generator;
+        let args_count = args.len();
+
+        self.stack.extend(args);
+        self.call_function(f, args_count)?;
+
+        if !is_native {
+            self.run(false, true)?;
+        }
+
+        Ok(self.pop())
+    }
+
+    /// Runs a generator's frame until its next `yield`, returning the yielded value and the
+    /// frame to resume it from later, or `None` once it returns.
+    pub fn resume_generator(
+        &mut self,
+        frame: GeneratorFrame,
+    ) -> RuntimeResult<Option<(Value, GeneratorFrame)>> {
+        let stack_base = self.stack.len();
+        let iterators_base = self.iterators.len();
+        let slot_base = self.slots.len();
+
+        self.stack.extend(frame.stack);
+        if frame.started {
+            // What the `yield` it stopped at evaluates to
+            self.stack.push(Value::NIL);
+        }
+        self.slots.extend(frame.slots);
+        self.iterators.extend(frame.iterators);
+
+        // Closures may have changed the variables they share with it in the meantime
+        for (slot, cell) in frame.open_upvalues {
+            let upvalue = std::mem::replace(&mut *cell.borrow_mut(), Upvalue::Open(slot_base + slot));
+            if let Upvalue::Closed(value) = upvalue {
+                self.slots[slot_base + slot] = value;
+            }
+            self.open_upvalues.push(cell);
+        }
+
+        let handlers = frame
+            .handlers
+            .into_iter()
+            .map(|handler| Handler {
+                catch_addr: handler.catch_addr,
+                stack_len: handler.stack_len + stack_base,
+                slots_len: handler.slots_len + slot_base,
+                iterators_len: handler.iterators_len + iterators_base,
+            })
+            .collect();
+
+        self.call_stack.push(CallFrame {
+            slot_base,
+            return_addr: self.pos,
+            upvalues: frame.upvalues,
+            handlers,
+            generator: Some((stack_base, iterators_base)),
+        });
+        self.pos = frame.pos;
+        self.run(false, true)?;
+
+        // Both `YIELD` and `RETURN` leave a value, but only the first one is an item
+        let value = self.pop();
+        Ok(self.suspended.take().map(|frame| (value, frame)))
+    }
+
+    /// Applies a binary operator (`ADD`, `LT`, `AND`, ...) to its operands, for both the stack
+    /// and the register instructions.
+    pub fn binary_op(inst: &Inst, a: &Value, b: &Value) -> RuntimeResult {
+        Ok(match inst {
+            Inst::ADD => {
+                if let (Value::Number(a), Value::Number(b)) = (a, b) {
+                    Value::Number(a + b)
+                } else if let (Value::String(a), Value::String(b)) = (a, b) {
+                    Value::String(TString::new(format!("{}{}", a.to_string(), b.to_string())))
+                } else if let (Value::String(a), Value::Char(b)) = (a, b) {
+                    Value::String(TString::new(format!("{}{}", a.to_string(), b)))
+                } else if let (Value::Char(a), Value::String(b)) = (a, b) {
+                    Value::String(TString::new(format!("{}{}", a, b.to_string())))
+                } else {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "Cannot add {} and {}",
+                        a.get_type(),
+                        b.get_type()
+                    ));
+                }
+            }
+            Inst::SUB => {
+                if let (Value::Number(a), Value::Number(b)) = (a, b) {
+                    Value::Number(a - b)
+                } else {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "Cannot subtract {} by {}",
+                        a.get_type(),
+                        b.get_type()
+                    ));
+                }
+            }
+            Inst::MUL => {
+                if let (Value::Number(a), Value::Number(b)) = (a, b) {
+                    Value::Number(a * b)
+                } else if let (Value::String(a), Value::Number(b)) = (a, b) {
+                    Value::String(TString::new(a.0.repeat(*b as usize)))
+                } else {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "Cannot multiply `{}` with `{}`",
+                        a.get_type(),
+                        b.get_type()
+                    ));
+                }
+            }
+            Inst::DIV => {
+                if let (Value::Number(a), Value::Number(b)) = (a, b) {
+                    if *b == 0.0 {
+                        return Err(runtime_error!(ZeroDivisionError, "Cannot divide by Zero"));
+                    }
+                    Value::Number(a / b)
+                } else {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "Cannot divide `{}` by `{}`",
+                        a.get_type(),
+                        b.get_type()
+                    ));
+                }
+            }
+            Inst::POW => {
+                if let (Value::Number(a), Value::Number(b)) = (a, b) {
+                    Value::Number(a.powf(*b))
+                } else {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "Cannot POW `{}` and `{}`",
+                        a.get_type(),
+                        b.get_type()
+                    ));
+                }
+            }
+            Inst::MOD => {
+                if let (Value::Number(a), Value::Number(b)) = (a, b) {
+                    Value::Number(a % b)
+                } else {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "Cannot MOD `{}` and `{}`",
+                        a.get_type(),
+                        b.get_type()
+                    ));
+                }
+            }
+
+            Inst::EQ => Value::Bool(Self::values_equal(a, b)),
+            Inst::NEQ => Value::Bool(!Self::values_equal(a, b)),
+            Inst::GT => match (a, b) {
+                (Value::Number(a), Value::Number(b)) => Value::Bool(a > b),
+                _ => return Err(runtime_error!(TypeError, "GT expects numbers")),
+            },
+            Inst::LT => match (a, b) {
+                (Value::Number(a), Value::Number(b)) => Value::Bool(a < b),
+                _ => {
+                    return Err(runtime_error!(
+                        TypeError,
+                        "LT expects numbers, got {a:?} and {b:?}"
+                    ));
+                }
+            },
+            Inst::GE => match (a, b) {
+                (Value::Number(a), Value::Number(b)) => Value::Bool(a >= b),
+                _ => return Err(runtime_error!(TypeError, "GE expects numbers")),
+            },
+            Inst::LE => match (a, b) {
+                (Value::Number(a), Value::Number(b)) => Value::Bool(a <= b),
+                _ => return Err(runtime_error!(TypeError, "LE expects numbers")),
+            },
+            Inst::AND => Value::Bool(a.is_truthy() && b.is_truthy()),
+            Inst::OR => Value::Bool(a.is_truthy() || b.is_truthy()),
+
+            _ => return Err(runtime_error!(InternalError, "`{inst:?}` isn't a binary operator")),
+        })
+    }
+
+    /// Reads `member` (a field, method or index) of `target`.
+    pub fn get_property(&mut self, target: Value, member: &Value) -> RuntimeResult {
+        match target {
+            Value::String(x) => x.get_member(self, member),
+            Value::List(x) => x.get_member(self, member),
+            Value::Tuple(x) => x.get_tuple_member(member),
+            Value::Dict(x) => x.get_member(self, member),
+            Value::Iterator(x) => x.get_member(self, member),
+            Value::Namespace(x) => x.borrow().get_member(self, member),
+            Value::Enum(x) => x.get_member(self, member),
+            Value::Struct(x) => x.get_member(self, member),
+            Value::Object(x) => x.get_member(self, member),
+            Value::Variant(x) => x.get_member(self, member),
+            Value::StructDef(x) => x.get_member(self, member),
+
+            _ => Err(runtime_error!(
+                PropertyError,
+                "Cannot get property on `{target:?}`"
+            )),
+        }
+    }
+
+    /// Sets `member` (a field or index) of `target` to `value`.
+    pub fn set_property(&mut self, target: Value, member: &Value, value: Value) -> RuntimeResult<()> {
+        match target {
+            Value::List(mut x) => x.set_member(member, value),
+            Value::Tuple(mut x) => x.set_member(member, value),
+            Value::Dict(mut x) => x.set_member(member, value),
+            Value::Namespace(x) => x.borrow_mut().set_member(member, value),
+            // Their fields live behind their own `Rc`, so a copy still writes to the shared values
+            Value::Struct(x) => TStruct::clone(&x).set_member(member, value),
+            Value::Object(x) => TObject::clone(&x).set_member(member, value),
+
+            _ => Err(runtime_error!(
+                PropertyError,
+                "Cannot set property `{member:?}` on `{}`",
+                target.to_string(false)
+            )),
+        }
+    }
+
+    /// Loose equality used by `==`, `!=` and `match`, where a char equals a one-character string.
+    fn values_equal(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (Value::String(x), Value::String(y)) => Rc::ptr_eq(&x.0, &y.0) || *x.0 == *y.0,
+
+            (Value::Char(x), Value::String(y)) | (Value::String(y), Value::Char(x)) => {
+                x.to_string() == *y.0
+            }
+
+            (a, b) => a == b,
+        }
+    }
+
+    /// Converts `value` for printing, using its `__str__` method if it has one. Elements of
+    /// lists, tuples and dicts are converted the same way.
+    pub fn stringify(&mut self, value: &Value) -> RuntimeResult<String> {
+        self.stringify_nested(value, false, &mut vec![])
+    }
+
+    /// `open` holds the containers being printed, so one that contains itself prints as `[...]`
+    fn stringify_nested(
+        &mut self,
+        value: &Value,
+        debug: bool,
+        open: &mut Vec<*const ()>,
+    ) -> RuntimeResult<String> {
+        if let Some(f) = value.special_method("__str__") {
+            return Ok(self.call_value(f, vec![])?.to_string(false));
+        }
+
+        let (id, (left, right)) = match value {
+            Value::List(list) => (list.values.as_ptr() as *const (), ("[", "]")),
+            Value::Tuple(list) => (list.values.as_ptr() as *const (), ("(", ")")),
+            Value::Dict(dict) => (dict.values.as_ptr() as *const (), ("{", "}")),
+            _ => return Ok(value.to_string(debug)),
+        };
+
+        if open.contains(&id) {
+            return Ok(format!("{left}...{right}"));
+        }
+        open.push(id);
+
+        // Copied out so `__str__` methods are free to change the container
+        let mut parts = vec![];
+        if let Value::Dict(dict) = value {
+            let entries: Vec<_> =
+                dict.values.borrow().iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            for (key, value) in entries {
+                let key = self.stringify_nested(&key, true, open)?;
+                let value = self.stringify_nested(&value, true, open)?;
+                parts.push(format!("{key}: {value}"));
+            }
+        } else if let Value::List(list) | Value::Tuple(list) = value {
+            let items = list.values.borrow().clone();
+            for item in items {
+                parts.push(self.stringify_nested(&item, true, open)?);
+            }
+        }
+
+        open.pop();
+        Ok(format!("{left}{}{right}", parts.join(", ")))
+    }
+
+    /// Runs the special method a struct or object operand defines for `inst`, like `__add__`
+    /// for `ADD`, and returns its result. Arithmetic falls back to the right operand's
+    /// reflected method (`3 * v` calls `v.__rmul__(3)`), and comparisons to `__lt__` with the
+    /// operands swapped or the result negated.
+    fn overloaded_operator(&mut self, inst: &Inst) -> RuntimeResult<Option<Value>> {
+        // (method is on the left operand, method, negate the result)
+        let candidates: &[(bool, &str, bool)] = match inst {
+            Inst::ADD => &[(true, "__add__", false), (false, "__radd__", false)],
+            Inst::SUB => &[(true, "__sub__", false), (false, "__rsub__", false)],
+            Inst::MUL => &[(true, "__mul__", false), (false, "__rmul__", false)],
+            Inst::DIV => &[(true, "__div__", false), (false, "__rdiv__", false)],
+            Inst::POW => &[(true, "__pow__", false), (false, "__rpow__", false)],
+            Inst::MOD => &[(true, "__mod__", false), (false, "__rmod__", false)],
+            Inst::EQ | Inst::MATCH => &[(true, "__eq__", false), (false, "__eq__", false)],
+            Inst::NEQ => &[(true, "__eq__", true), (false, "__eq__", true)],
+            Inst::LT => &[(true, "__lt__", false)],
+            Inst::GT => &[(true, "__gt__", false), (false, "__lt__", false)],
+            Inst::LE => &[(true, "__le__", false), (false, "__lt__", true)],
+            Inst::GE => &[(true, "__ge__", false), (true, "__lt__", true)],
+            Inst::GET_PROP if !matches!(self.stack.last(), Some(Value::String(_))) => {
+                &[(true, "__index__", false)]
+            }
+
+            Inst::NEG => {
+                let Some(method) = self.stack.last().and_then(|v| v.special_method("__neg__"))
+                else {
+                    return Ok(None);
+                };
+
+                self.pop();
+                return Ok(Some(self.call_value(method, vec![])?));
+            }
+
+            _ => return Ok(None),
+        };
+
+        let [.., left, right] = self.stack.as_slice() else {
+            return Ok(None);
+        };
+        if !matches!(left, Value::Struct(_) | Value::Object(_))
+            && !matches!(right, Value::Struct(_) | Value::Object(_))
+        {
+            return Ok(None);
+        }
+
+        for (on_left, name, negate) in candidates {
+            let (receiver, other) = if *on_left { (left, right) } else { (right, left) };
+
+            if let Some(method) = receiver.special_method(name) {
+                let other = other.clone();
+                self.pop_two();
+
+                let result = self.call_value(method, vec![other])?;
+                return Ok(Some(if *negate {
+                    Value::Bool(!result.is_truthy())
+                } else {
+                    result
+                }));
+            }
+        }
+
+        Ok(None)
     }
 
     pub fn lookup_intern(&self, id: u64) -> Rc<str> {
@@ -192,62 +731,34 @@ impl VM {
     }
 
     pub fn print_instructions(&self) {
-        let mut depth: i32 = 0;
+        if !self.register_code.is_empty() {
+            return self.print_register_code();
+        }
 
         for (i, v) in self.instructions.iter().enumerate() {
-            if let Inst::POP_SCOPE = v {
-                depth -= 1;
-            }
-
-            let indent = format!(
-                "{}{}",
-                if depth < 0 { RED } else { DIM_BLACK },
-                "|  ".repeat(depth.abs() as usize)
-            );
-
             if let Inst::COMMENT(x) = v {
-                println!(
-                    "{BLACK}\t {}--- {x} ---{RESET}",
-                    format!(
-                        "{}{}",
-                        if depth < 0 { RED } else { DIM_BLACK },
-                        "|  ".repeat(depth.abs() as usize)
-                    ),
-                );
+                println!("{BLACK}\t --- {x} ---{RESET}");
                 continue;
             }
 
             // Resolve u64 instructions to human-readable display strings
             let display = match v {
-                Inst::LOAD(id) => Some(format!("LOAD({})", self.lookup_intern(*id))),
-                Inst::LOAD_LOCAL { id, depth } => Some(format!(
-                    "LOAD_LOCAL({}, depth: {})",
-                    self.lookup_intern(*id),
-                    depth
-                )),
                 Inst::LOAD_GLOBAL(id) => Some(format!("LOAD_GLOBAL({})", self.lookup_intern(*id))),
-                Inst::STORE_LOCAL { id, depth } => Some(format!(
-                    "STORE_LOCAL({}, depth: {})",
-                    self.lookup_intern(*id),
-                    depth
-                )),
-                Inst::STORE_LOCAL_CONST { id, depth } => Some(format!(
-                    "STORE_LOCAL_CONST({}, depth: {})",
-                    self.lookup_intern(*id),
-                    depth
-                )),
                 Inst::STORE_GLOBAL(id) => {
                     Some(format!("STORE_GLOBAL({})", self.lookup_intern(*id)))
                 }
                 Inst::SET_VAR(id) => Some(format!("SET_VAR({})", self.lookup_intern(*id))),
-                Inst::LOAD_UPVALUE { id, scope_idx } => Some(format!(
-                    "LOAD_UPVALUE({}, depth: {})",
-                    self.lookup_intern(*id),
-                    depth
-                )),
-                Inst::MAKE_CLOSURE { entry, captures } => Some(format!(
-                    "MAKE_CLOSURE(entry: {}, captures: {:?})",
-                    entry, captures
+                Inst::MAKE_CLOSURE {
+                    entry,
+                    arity,
+                    captures,
+                    generator,
+                } => Some(format!(
+                    "MAKE_CLOSURE(entry: {}, arity: {}, captures: {:?}{})",
+                    entry,
+                    arity,
+                    captures,
+                    if *generator { ", generator" } else { "" }
                 )),
                 _ => None,
             };
@@ -261,7 +772,7 @@ impl VM {
             let opcode = parts.next().unwrap();
             let rest = parts.next().map_or("", |r| r);
 
-            let color = if matches!(v, Inst::EXIT | Inst::RETURN) {
+            let color = if matches!(v, Inst::EXIT | Inst::RETURN | Inst::YIELD) {
                 RED
             } else if matches!(
                 v,
@@ -270,6 +781,9 @@ impl VM {
                     | Inst::DICT(_)
                     | Inst::ENUM(..)
                     | Inst::STRUCT(..)
+                    | Inst::CLASS { .. }
+                    | Inst::IMPL { .. }
+                    | Inst::MODULE { .. }
                     | Inst::RANGE
             ) {
                 GREEN
@@ -279,7 +793,7 @@ impl VM {
                 ORANGE
             };
 
-            print!("{MAGENTA}{i:>2}{RESET}   {BLACK}{indent}");
+            print!("{MAGENTA}{i:>2}{RESET}   ");
 
             if rest.is_empty() {
                 print!("{color}{opcode}{RESET}");
@@ -293,37 +807,49 @@ impl VM {
             if let Inst::LOAD_CONST(x) = v {
                 print!("{BLACK}   {:>3?}{RESET}", self.constants[*x]);
             }
-            if let Inst::PUSH_SCOPE = v {
-                print!(" {{");
-                depth += 1;
-            }
-            if let Inst::POP_SCOPE = v {
-                println!(" }}");
-            } else {
-                println!();
-            }
+            println!();
         }
     }
 }
 
 // BYTECODE
 impl VM {
-    pub fn read_bytecode_file(&mut self, path: &str) {
-        let bytecode_file = std::fs::read(path).unwrap();
+    pub fn read_bytecode_file(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let bytecode_file = std::fs::read(path)
+            .map_err(|e| format!("Couldn't read bytecode file `{path}`: {e}"))?;
+
+        let encoded = Chunk::strip_header(&bytecode_file)
+            .map_err(|e| format!("Invalid bytecode file `{path}`: {e}. Recompile it from source"))?;
 
         let config = bincode::config::standard().with_variable_int_encoding();
-        let decoded: (Chunk, _) = bincode::decode_from_slice(&bytecode_file, config).unwrap();
+        let decoded: (Chunk, _) = bincode::decode_from_slice(encoded, config)
+            .map_err(|e| format!("Invalid bytecode file `{path}`: {e}"))?;
 
         self.constants = decoded.0.constants;
-        self.instructions = decoded.0.instructions;
+        self.instructions = rc!(decoded.0.instructions);
+        self.register_code = rc!(decoded.0.registers);
+        self.files = decoded.0.files;
+        self.lines = decoded.0.lines;
+
+        Ok(())
     }
 
-    pub fn write_bytecode_file(&mut self, path: &str) {
-        let chunk = Chunk::new(self.constants.clone(), self.instructions.clone());
+    pub fn write_bytecode_file(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        let chunk = Chunk::new(
+            self.constants.clone(),
+            (*self.instructions).clone(),
+            (*self.register_code).clone(),
+            self.files.clone(),
+            self.lines.clone(),
+        );
         let config = bincode::config::standard().with_variable_int_encoding();
-        let encoded = bincode::encode_to_vec(chunk, config).unwrap();
+        let mut encoded = Chunk::header();
+        encoded.extend(bincode::encode_to_vec(chunk, config)?);
 
-        std::fs::write(path, encoded).unwrap();
+        std::fs::write(path, encoded)
+            .map_err(|e| format!("Couldn't write bytecode file `{path}`: {e}"))?;
+
+        Ok(())
     }
 }
 
@@ -334,15 +860,66 @@ impl VM {
     }
 
     pub fn fold_constants(&mut self) {
-        for inst in &mut self.instructions {
+        for inst in Rc::make_mut(&mut self.instructions).iter_mut() {
             if let Inst::LOAD_CONST(idx) = inst {
                 *inst = Inst::PUSH(self.constants[*idx].clone());
             }
         }
     }
 
-    pub fn run(&mut self, debug: bool, stop_at_return: bool) {
-        let instructions = std::mem::take(&mut self.instructions);
+    /// Runs from `self.pos`, attaching the failing instruction and the call stack to any error.
+    /// Errors are handed to the innermost `try` started by this run before giving up.
+    pub fn run(&mut self, debug: bool, stop_at_return: bool) -> RuntimeResult<()> {
+        let depth = self.call_stack.len();
+
+        loop {
+            let Err(mut e) = self.execute(debug, stop_at_return, depth) else {
+                return Ok(());
+            };
+
+            if e.pos.is_none() {
+                self.locate(&mut e, self.pos, self.call_stack.len());
+            }
+
+            if !self.unwind(&e, depth) {
+                return Err(e);
+            }
+        }
+    }
+
+    /// Points `e` at the instruction at `pos`, called through the first `frames` call frames.
+    fn locate(&self, e: &mut RuntimeError, pos: usize, frames: usize) {
+        e.pos = Some(pos);
+        e.location = self.location(pos);
+        e.trace = self.call_stack[1..frames]
+            .iter()
+            .map(|frame| (frame.return_addr, self.location(frame.return_addr)))
+            .collect();
+    }
+
+    /// Jumps to the nearest catch handler, dropping the frames, stack values, locals and
+    /// iterators created since its `try`. Frames below `depth - 1` belong to an outer `run`
+    /// (a native function is still waiting on them), so they are left alone.
+    fn unwind(&mut self, e: &RuntimeError, depth: usize) -> bool {
+        for i in (depth.saturating_sub(1)..self.call_stack.len()).rev() {
+            if let Some(handler) = self.call_stack[i].handlers.pop() {
+                self.call_stack.truncate(i + 1);
+                self.stack.truncate(handler.stack_len);
+                self.close_upvalues(handler.slots_len);
+                self.slots.truncate(handler.slots_len);
+                self.iterators.truncate(handler.iterators_len);
+
+                self.stack.push(e.to_value());
+                self.pos = handler.catch_addr;
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn execute(&mut self, debug: bool, stop_at_return: bool, depth: usize) -> RuntimeResult<()> {
+        let instructions = Rc::clone(&self.instructions);
 
         while self.pos < instructions.len() {
             if debug {
@@ -350,13 +927,24 @@ impl VM {
             }
             let current = &instructions[self.pos];
 
+            // Structs and objects can define operators with special methods like `__add__`
+            if let Some(result) = self.overloaded_operator(current)? {
+                self.stack.push(result);
+                self.advance();
+                continue;
+            }
+
             match current {
-                Inst::EXIT => return,
+                Inst::EXIT => return Ok(()),
                 Inst::NOP => {}
                 Inst::COMMENT(_) => {}
-                Inst::PRINT => println!("{}", self.pop().to_string(false)),
+                Inst::PRINT => {
+                    let value = self.pop();
+                    println!("{}", self.stringify(&value)?);
+                }
                 Inst::TO_STRING => {
-                    let s = self.pop();
+                    let value = self.pop();
+                    let s = self.stringify(&value)?;
                     self.stack.push(Value::string(s))
                 }
                 Inst::POP => {
@@ -421,14 +1009,89 @@ impl VM {
                         .collect::<HashMap<_, _>>();
 
                     self.stack
-                        .push(Value::Enum(TEnum::new(name.clone(), rc!(map))));
+                        .push(Value::Enum(rc!(TEnum::new(name.clone(), rc!(map)))));
+                }
+                Inst::CLASS {
+                    name,
+                    fields,
+                    methods,
+                    interfaces,
+                } => {
+                    let mut table = HashMap::new();
+                    for method in methods.iter().rev() {
+                        if let Value::Function(f) = self.pop() {
+                            table.insert(method.clone(), Rc::unwrap_or_clone(f));
+                        }
+                    }
+
+                    let mut implemented = vec![];
+                    for _ in 0..*interfaces {
+                        match self.pop() {
+                            Value::Interface(interface) => implemented.insert(0, interface),
+                            other => {
+                                return Err(runtime_error!(
+                                    TypeError,
+                                    "Class `{name}` can only implement interfaces, got `{}`",
+                                    other.get_type()
+                                ));
+                            }
+                        }
+                    }
+
+                    let class = TClass::new(name.clone(), fields.clone(), table, implemented);
+                    for interface in class.interfaces.iter() {
+                        interface.check(&class)?;
+                    }
+
+                    self.stack.push(Value::Class(rc!(class)));
+                }
+
+                // Struct variants of enums are built like structs: `Shape.Rect { w: 1, h: 2 }`
+                Inst::STRUCT(field_names) if matches!(self.stack.last(), Some(Value::VariantDef(_))) => {
+                    let Value::VariantDef(def) = self.pop() else {
+                        unreachable!()
+                    };
+
+                    let mut values = field_names
+                        .iter()
+                        .map(|x| (x.clone(), self.pop()))
+                        .collect::<HashMap<_, _>>();
+
+                    if let Some(name) = field_names.iter().find(|x| !def.fields.contains(x)) {
+                        return Err(runtime_error!(
+                            PropertyError,
+                            "Variant `{}.{}` has no field `{name}`",
+                            def.enum_name,
+                            def.name
+                        ));
+                    }
+
+                    let values = def
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            values.remove(field).ok_or_else(|| {
+                                runtime_error!(
+                                    PropertyError,
+                                    "Missing field `{field}` for variant `{}.{}`",
+                                    def.enum_name,
+                                    def.name
+                                )
+                            })
+                        })
+                        .collect::<RuntimeResult<Vec<_>>>()?;
+
+                    self.stack.push(Value::Variant(rc!(def.construct(values)?)));
                 }
                 Inst::STRUCT(field_names) => {
                     let base_value = self.pop();
                     let base = if let Value::StructDef(base) = base_value {
                         base
                     } else {
-                        panic!("Can only intialize struct definitions")
+                        return Err(runtime_error!(
+                            TypeError,
+                            "Can only intialize struct definitions"
+                        ))
                     };
 
                     let values = rc!(RefCell::new(
@@ -441,208 +1104,120 @@ impl VM {
                     for (name, value) in values.borrow().iter() {
                         if let Some(v_type) = base.fields.get(&*name) {
                             if !value.type_matches(v_type) {
-                                panic!(
+                                return Err(runtime_error!(
+                                    TypeError,
                                     "Field '{name}' expects type `{v_type}`, got `{}`.",
-                                    value.get_type()
-                                )
+                                    value.hint_type()
+                                ))
                             }
                         } else {
-                            panic!(
+                            return Err(runtime_error!(
+                                PropertyError,
                                 "Tried setting unknown field on struct of base {}",
                                 base.name
-                            );
+                            ));
                         }
                     }
 
-                    self.stack.push(Value::Struct(TStruct::new(base, values)));
+                    self.stack.push(Value::Struct(rc!(TStruct::new(base, values))));
                 }
 
-                Inst::RANGE => {
-                    let inclusive_val = self.pop();
-                    let step = self.pop();
-                    let end = self.pop();
-                    let start = self.pop();
-
-                    let inclusive = if let Value::Bool(x) = inclusive_val {
-                        x
-                    } else {
-                        false
+                Inst::IMPL { methods, statics } => {
+                    let target = self.pop();
+                    let Value::StructDef(def) = target else {
+                        return Err(runtime_error!(
+                            TypeError,
+                            "Can only `impl` structs, got `{}`",
+                            target.get_type()
+                        ));
                     };
 
-                    self.stack.push(Value::Range {
-                        start: Box::new(start),
-                        end: Box::new(end),
-                        step: Box::new(step),
-                        inclusive,
-                    });
-                }
-
-                Inst::ADD => {
-                    let (a, b) = &self.pop_two();
-
-                    if let (Value::Number(a), Value::Number(b)) = (a, b) {
-                        self.stack.push(Value::Number(a + b));
-                    } else if let (Value::String(a), Value::String(b)) = (a, b) {
-                        self.stack.push(Value::String(TString::new(format!(
-                            "{}{}",
-                            a.to_string(),
-                            b.to_string()
-                        ))));
-                    } else if let (Value::String(a), Value::Char(b)) = (a, b) {
-                        self.stack.push(Value::String(TString::new(format!(
-                            "{}{}",
-                            a.to_string(),
-                            b
-                        ))));
-                    } else if let (Value::Char(a), Value::String(b)) = (a, b) {
-                        self.stack.push(Value::String(TString::new(format!(
-                            "{}{}",
-                            a,
-                            b.to_string()
-                        ))));
-                    } else {
-                        panic!("Cannot add {} and {}", a.get_type(), b.get_type());
-                    }
-                }
-                Inst::SUB => {
-                    let (a, b) = &self.pop_two();
-
-                    if let (Value::Number(a), Value::Number(b)) = (a, b) {
-                        self.stack.push(Value::Number(a - b));
-                    } else {
-                        panic!("Cannot subtract {} by {}", a.get_type(), b.get_type());
+                    for (names, functions) in [(statics, &def.statics), (methods, &def.methods)] {
+                        for name in names.iter().rev() {
+                            let Value::Function(function) = self.pop() else {
+                                unreachable!("IMPL expects the compiled functions on the stack")
+                            };
+                            functions.borrow_mut().insert(name.clone(), Rc::unwrap_or_clone(function));
+                        }
                     }
                 }
-                Inst::MUL => {
-                    let (a, b) = &self.pop_two();
 
-                    if let (Value::Number(a), Value::Number(b)) = (a, b) {
-                        self.stack.push(Value::Number(a * b));
-                    } else if let (Value::String(a), Value::Number(b)) = (a, b) {
-                        self.stack
-                            .push(Value::String(TString::new(a.0.repeat(*b as usize))));
-                    } else {
-                        panic!("Cannot multiply `{}` with `{}`", a.get_type(), b.get_type());
-                    }
-                }
-                Inst::DIV => {
-                    let (a, b) = &self.pop_two();
+                Inst::IMPORT_ALL => {
+                    let target = self.pop();
+                    let Value::Namespace(namespace) = target else {
+                        return Err(runtime_error!(
+                            TypeError,
+                            "Can only import every member of a namespace, got `{}`",
+                            target.get_type()
+                        ));
+                    };
 
-                    if let (Value::Number(a), Value::Number(b)) = (a, b) {
-                        if *b == 0.0 {
-                            panic!("Cannot divide by Zero");
-                        } else {
-                            self.stack.push(Value::Number(a / b));
-                        }
-                    } else {
-                        panic!("Cannot divide `{}` by `{}`", a.get_type(), b.get_type());
+                    let members = namespace.borrow().env.clone();
+                    for (name, (value, _)) in members {
+                        let id = hash_u64!(&*name);
+                        self.intern_table.entry(id).or_insert(name);
+                        self.globals.insert(id, (value, false));
                     }
                 }
-                Inst::POW => {
-                    let (a, b) = &self.pop_two();
 
-                    if let (Value::Number(a), Value::Number(b)) = (a, b) {
-                        self.stack.push(Value::Number(a.powf(*b)));
-                    } else {
-                        panic!("Cannot POW `{}` and `{}`", a.get_type(), b.get_type());
-                    }
-                }
-                Inst::MOD => {
-                    let (a, b) = &self.pop_two();
+                Inst::MODULE { name, members } => {
+                    let mut module = TNamespace::new(name, true);
 
-                    if let (Value::Number(a), Value::Number(b)) = (a, b) {
-                        self.stack.push(Value::Number(a % b));
-                    } else {
-                        panic!("Cannot MOD `{}` and `{}`", a.get_type(), b.get_type());
+                    for member in members.iter().rev() {
+                        let value = self.pop();
+                        module.set_const(member, value);
                     }
-                }
 
-                Inst::NEG => {
-                    let num = self.pop().as_number();
-                    self.stack.push(Value::Number(-num));
+                    self.stack.push(Value::Namespace(Rc::new(RefCell::new(module))));
                 }
-                Inst::POS => {
-                    let num = self.pop().as_number();
-                    self.stack.push(Value::Number(num));
-                }
-
-                Inst::EQ => {
-                    let result = match self.pop_two() {
-                        (Value::NIL, Value::NIL) => true,
-                        (Value::Number(x), Value::Number(y)) => x == y,
-                        (Value::Bool(x), Value::Bool(y)) => x == y,
-                        (Value::Char(x), Value::Char(y)) => x == y,
 
-                        (Value::String(x), Value::String(y)) => {
-                            Rc::ptr_eq(&x.0, &y.0) || *x.0 == *y.0
-                        }
-
-                        (Value::Char(x), Value::String(y)) => x.to_string() == *y.0,
-                        (Value::String(y), Value::Char(x)) => x.to_string() == *y.0,
+                Inst::RANGE => {
+                    let inclusive_val = self.pop();
+                    let step = self.pop();
+                    let end = self.pop();
+                    let start = self.pop();
 
-                        (a, b) => a == b,
+                    let inclusive = if let Value::Bool(x) = inclusive_val {
+                        x
+                    } else {
+                        false
                     };
 
-                    self.stack.push(Value::Bool(result));
+                    self.stack.push(Value::Range(rc!(TRange {
+                        start,
+                        end,
+                        step,
+                        inclusive,
+                    })));
                 }
-                Inst::NEQ => {
-                    let result = match self.pop_two() {
-                        (Value::NIL, Value::NIL) => true,
-                        (Value::Number(x), Value::Number(y)) => x != y,
-                        (Value::Bool(x), Value::Bool(y)) => x != y,
-                        (Value::Char(x), Value::Char(y)) => x != y,
-
-                        (Value::String(x), Value::String(y)) => {
-                            !Rc::ptr_eq(&x.0, &y.0) || *x.0 != *y.0
-                        }
-
-                        (Value::Char(x), Value::String(y)) => x.to_string() == *y.0,
-                        (Value::String(y), Value::Char(x)) => x.to_string() == *y.0,
-
-                        (a, b) => a != b,
-                    };
 
-                    self.stack.push(Value::Bool(result));
-                }
-                Inst::GT => {
-                    if let (Value::Number(a), Value::Number(b)) = self.pop_two() {
-                        self.stack.push(Value::Bool(a > b));
-                    } else {
-                        panic!("GT expects numbers");
-                    }
-                }
-                Inst::LT => {
+                Inst::ADD
+                | Inst::SUB
+                | Inst::MUL
+                | Inst::DIV
+                | Inst::POW
+                | Inst::MOD
+                | Inst::EQ
+                | Inst::NEQ
+                | Inst::GT
+                | Inst::LT
+                | Inst::GE
+                | Inst::LE
+                | Inst::AND
+                | Inst::OR => {
                     let (a, b) = self.pop_two();
-                    if let (Value::Number(a), Value::Number(b)) = (&a, &b) {
-                        self.stack.push(Value::Bool(a < b));
-                    } else {
-                        panic!("LT expects numbers, got {a:?} and {b:?}");
-                    }
-                }
-                Inst::GE => {
-                    if let (Value::Number(a), Value::Number(b)) = self.pop_two() {
-                        self.stack.push(Value::Bool(a >= b));
-                    } else {
-                        panic!("GE expects numbers");
-                    }
+                    self.stack.push(Self::binary_op(current, &a, &b)?);
                 }
-                Inst::LE => {
-                    if let (Value::Number(a), Value::Number(b)) = self.pop_two() {
-                        self.stack.push(Value::Bool(a <= b));
-                    } else {
-                        panic!("LE expects numbers");
-                    }
-                }
-                Inst::AND => {
-                    let (a, b) = self.pop_two();
 
-                    self.stack.push(Value::Bool(a.is_truthy() && b.is_truthy()));
+                Inst::NEG => {
+                    let num = self.pop().as_number()?;
+                    self.stack.push(Value::Number(-num));
                 }
-                Inst::OR => {
-                    let (a, b) = self.pop_two();
-
-                    self.stack.push(Value::Bool(a.is_truthy() || b.is_truthy()));
+                Inst::POS => {
+                    let num = self.pop().as_number()?;
+                    self.stack.push(Value::Number(num));
                 }
+
                 Inst::NOT => {
                     let res = self.pop().is_truthy();
                     self.stack.push(Value::Bool(!res));
@@ -660,103 +1235,67 @@ impl VM {
                     self.globals.insert(id, (value, true));
                 }
                 Inst::LOAD_GLOBAL(id) => {
-                    self.stack.push(
-                        self.globals
-                            .get(id)
-                            .expect(&format!(
-                                "Global `{}` doesn't exist.",
-                                self.lookup_intern(*id)
-                            ))
-                            .0
-                            .clone(),
-                    );
-                }
-
-                Inst::PUSH_SCOPE => self.locals.push(rc!(RefCell::new(HashMap::new()))),
-                Inst::POP_SCOPE => {
-                    self.locals.pop();
-                }
-                Inst::STORE_LOCAL { id, depth } => {
-                    if let Some(current_frame) = self.call_stack.last() {
-                        let id = *id;
-                        let depth = current_frame.scope_base + *depth;
-                        let value = self.pop();
-                        self.locals[depth].borrow_mut().insert(id, (value, false));
+                    if let Some((value, _)) = self.globals.get(id) {
+                        self.stack.push(value.clone());
                     } else {
-                        panic!("Too little CallFrames in call_stack (LOAD_LOCAL)")
+                        return Err(runtime_error!(
+                            NameError,
+                            "Global `{}` doesn't exist.",
+                            self.lookup_intern(*id)
+                        ));
                     }
                 }
-                Inst::LOAD_LOCAL { id, depth } => {
-                    if let Some(current_frame) = self.call_stack.last() {
-                        if let Some((val, _)) = self.locals[current_frame.scope_base + *depth]
-                            .borrow()
-                            .get(id)
-                        {
-                            self.stack.push(val.clone());
-                        } else {
-                            panic!(
-                                "Unknown local variable at depth {}: {}",
-                                current_frame.scope_base + *depth,
-                                self.lookup_intern(*id)
-                            );
-                        }
-                    } else {
-                        panic!("Too little CallFrames in call_stack (LOAD_LOCAL)")
+
+                Inst::STORE_LOCAL(slot) => {
+                    let slot = self.slot_base() + slot;
+                    let value = self.pop();
+
+                    if slot >= self.slots.len() {
+                        self.slots.resize(slot + 1, Value::NIL);
                     }
+                    self.slots[slot] = value;
                 }
-                Inst::STORE_LOCAL_CONST { id, depth } => {
-                    if let Some(current_frame) = self.call_stack.last() {
-                        let id = *id;
-                        let depth = current_frame.scope_base + *depth;
-                        let value = self.pop();
-                        self.locals[depth].borrow_mut().insert(id, (value, true));
-                    } else {
-                        panic!("Too little CallFrames in call_stack (LOAD_LOCAL)")
-                    }
+                Inst::LOAD_LOCAL(slot) => {
+                    let value = self.slots[self.slot_base() + slot].clone();
+                    self.stack.push(value);
                 }
 
-                Inst::LOAD(name) => {
+                Inst::LOAD { name, namespaces } => {
+                    let base = self.slot_base();
                     let mut found = None;
 
-                    for scope in self.locals.iter().rev() {
-                        if let Some(val) = scope.borrow().get(name) {
-                            found = Some(val.clone());
+                    for slot in namespaces {
+                        let Value::Namespace(namespace) = &self.slots[base + slot] else {
+                            return Err(runtime_error!(
+                                TypeError,
+                                "Can only import every member of a namespace, got `{}`",
+                                self.slots[base + slot].get_type()
+                            ));
+                        };
+                        if let Some((value, _)) = namespace.borrow().env.get(name.as_str()) {
+                            found = Some(value.clone());
                             break;
                         }
                     }
 
-                    if let Some((val, _)) = found {
-                        self.stack.push(val);
-                    } else if let Some((val, _)) = self.globals.get(name) {
-                        self.stack.push(val.clone());
+                    if let Some(value) = found {
+                        self.stack.push(value);
+                    } else if let Some((value, _)) = self.globals.get(&hash_u64!(name.as_str())) {
+                        self.stack.push(value.clone());
                     } else {
-                        panic!(
-                            "Unknown local/global variable: {}",
-                            self.lookup_intern(*name)
-                        );
+                        return Err(runtime_error!(
+                            NameError,
+                            "Unknown local/global variable: {name}"
+                        ));
                     }
                 }
                 Inst::SET_VAR(name) => {
-                    let mut found_idx = None;
-
-                    for i in (0..self.locals.len()).rev() {
-                        if let Some((_, is_const)) = self.locals[i].borrow().get(name) {
-                            if *is_const {
-                                panic!("Cannot set a constant `{name}`");
-                            } else {
-                                found_idx = Some(i);
-                            }
-                            break;
-                        }
-                    }
-                    if let Some(scope) = found_idx {
-                        let value = self.pop();
-                        if let Some(slot) = self.locals[scope].borrow_mut().get_mut(&name) {
-                            slot.0 = value;
-                        }
-                    } else if let Some((_, is_const)) = self.globals.get(name) {
+                    if let Some((_, is_const)) = self.globals.get(name) {
                         if *is_const {
-                            panic!("Cannot set a global constant `{name}`");
+                            return Err(runtime_error!(
+                                ConstError,
+                                "Cannot set a global constant `{name}`"
+                            ));
                         } else {
                             let value = self.pop();
                             if let Some(slot) = self.globals.get_mut(&name) {
@@ -766,29 +1305,48 @@ impl VM {
                     }
                 }
 
-                Inst::MAKE_CLOSURE { entry, captures } => {
-                    let upvalues = captures
-                        .iter()
-                        .map(|&i| Rc::clone(&self.locals[i]))
-                        .collect();
+                Inst::MAKE_CLOSURE {
+                    entry,
+                    arity,
+                    captures,
+                    generator,
+                } => {
+                    let base = self.slot_base();
+                    let mut upvalues = Vec::with_capacity(captures.len());
+
+                    for capture in captures {
+                        upvalues.push(match capture {
+                            Capture::Local(slot) => self.capture_upvalue(base + slot),
+                            Capture::Upvalue(i) => Rc::clone(&self.call_stack[self.call_stack.len() - 1].upvalues[*i]),
+                        });
+                    }
 
-                    self.stack.push(Value::Function(TFunction {
+                    self.stack.push(Value::Function(rc!(TFunction {
                         entry: *entry,
+                        arity: *arity,
                         upvalues,
                         handler: None,
                         this: None,
-                    }));
+                        generator: *generator,
+                    })));
                 }
-                Inst::LOAD_UPVALUE { scope_idx, id } => {
-                    if let Some(frame) = self.call_stack.last() {
-                        let scope = &frame.upvalues[*scope_idx];
-                        if let Some((val, _)) = scope.borrow().get(id) {
-                            self.stack.push(val.clone());
-                        } else {
-                            panic!("Unknown upvalue: {}", self.lookup_intern(*id));
-                        }
+                Inst::LOAD_UPVALUE(i) => {
+                    let frame = &self.call_stack[self.call_stack.len() - 1];
+                    let value = match &*frame.upvalues[*i].borrow() {
+                        Upvalue::Open(slot) => self.slots[*slot].clone(),
+                        Upvalue::Closed(value) => value.clone(),
+                    };
+                    self.stack.push(value);
+                }
+                Inst::STORE_UPVALUE(i) => {
+                    let value = self.pop();
+                    let frame = &self.call_stack[self.call_stack.len() - 1];
+                    match &mut *frame.upvalues[*i].borrow_mut() {
+                        Upvalue::Open(slot) => self.slots[*slot] = value,
+                        Upvalue::Closed(closed) => *closed = value,
                     }
                 }
+                Inst::CLOSE_UPVALUES(slot) => self.close_upvalues(self.slot_base() + slot),
 
                 Inst::JUMP(idx) => {
                     self.pos = *idx;
@@ -817,215 +1375,378 @@ impl VM {
                 }
 
                 Inst::CALL(args) => {
-                    let arg_count = *args;
-                    let func = self.pop();
-
-                    if let Value::Function(f) = func {
-                        let should_skip = f.handler.is_none();
-                        self.call_function(f, arg_count);
-                        if should_skip {
+                    if self.call(*args)? {
+                        continue;
+                    }
+                }
+                Inst::TAIL_CALL { args, iterators } => {
+                    let frame = self.call_stack.last().expect("No call frame");
+                    let reusable = self.call_stack.len() > 1
+                        && frame.handlers.is_empty()
+                        && frame.generator.is_none();
+
+                    match self.stack.last() {
+                        Some(Value::Function(f)) if reusable && f.handler.is_none() && !f.generator => {
+                            let Value::Function(f) = self.pop() else {
+                                unreachable!()
+                            };
+                            let frame = self.call_stack.pop().unwrap();
+
+                            self.iterators.truncate(self.iterators.len() - iterators);
+                            self.close_upvalues(frame.slot_base);
+                            self.slots.truncate(frame.slot_base);
+
+                            // The callee returns to wherever this frame would have
+                            self.pos = frame.return_addr;
+                            self.call_function(f, *args)?;
                             continue;
                         }
-                    } else {
-                        panic!("({}) Tried calling non-function: {func:?}", self.pos)
+                        _ => {
+                            if self.call(*args)? {
+                                continue;
+                            }
+                        }
                     }
                 }
                 Inst::RETURN => {
-                    if let Some(frame) = self.call_stack.last() {
-                        self.pos = *&frame.return_addr;
-                        self.locals.truncate(frame.scope_base);
-                        self.call_stack.pop();
+                    if let Some(frame) = self.call_stack.pop() {
+                        self.pos = frame.return_addr;
+                        self.close_upvalues(frame.slot_base);
+                        self.slots.truncate(frame.slot_base);
                     } else {
-                        self.locals.pop();
                         break;
                     }
-                    if stop_at_return {
-                        self.locals.pop();
+                    // Only stop once the function this run was started for has returned
+                    if stop_at_return && self.call_stack.len() < depth {
                         break;
                     }
                 }
+                Inst::CHECK_TYPE {
+                    hint,
+                    function,
+                    param,
+                } => {
+                    let value = self.stack.last().unwrap_or(&Value::NIL);
+
+                    if !value.type_matches(hint) {
+                        let actual = value.hint_type();
+
+                        let Some(param) = param else {
+                            return Err(runtime_error!(
+                                TypeError,
+                                "`{function}` should return `{hint}`, got `{actual}`"
+                            ));
+                        };
 
-                Inst::GET_PROP => {
-                    let member = self.pop();
-                    let target = self.pop();
-
-                    match target {
-                        Value::String(x) => {
-                            let value = x.get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                        // A bad argument is the caller's fault, so the error points at the call
+                        let mut e = runtime_error!(
+                            TypeError,
+                            "`{function}` expects `{param}` to be `{hint}`, got `{actual}`"
+                        );
+                        let frames = self.call_stack.len() - 1;
+                        self.locate(&mut e, self.call_stack[frames].return_addr, frames);
+                        return Err(e);
+                    }
+                }
+                Inst::YIELD => {
+                    let value = self.pop();
+                    let frame = self.call_stack.pop().unwrap();
 
-                        Value::List(x) => {
-                            let value = x.get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                    let Some((stack_base, iterators_base)) = frame.generator else {
+                        return Err(runtime_error!(
+                            InternalError,
+                            "Yielded outside of a generator"
+                        ));
+                    };
 
-                        Value::Tuple(x) => {
-                            let value = x.get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                    let handlers = frame
+                        .handlers
+                        .into_iter()
+                        .map(|handler| Handler {
+                            catch_addr: handler.catch_addr,
+                            stack_len: handler.stack_len - stack_base,
+                            slots_len: handler.slots_len - frame.slot_base,
+                            iterators_len: handler.iterators_len - iterators_base,
+                        })
+                        .collect();
 
-                        Value::Dict(x) => {
-                            let value = x.get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                    // Closures it made keep their variables while it's suspended
+                    let start = self
+                        .open_upvalues
+                        .partition_point(|cell| Self::open_slot(cell) < frame.slot_base);
+                    let mut open_upvalues = vec![];
+                    for cell in self.open_upvalues.split_off(start) {
+                        let slot = Self::open_slot(&cell);
+                        *cell.borrow_mut() = Upvalue::Closed(self.slots[slot].clone());
+                        open_upvalues.push((slot - frame.slot_base, cell));
+                    }
 
-                        Value::Namespace(x) => {
-                            let value = x.borrow().get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                    self.suspended = Some(GeneratorFrame {
+                        pos: self.pos + 1,
+                        started: true,
+                        stack: self.stack.split_off(stack_base),
+                        slots: self.slots.split_off(frame.slot_base),
+                        open_upvalues,
+                        iterators: self.iterators.split_off(iterators_base),
+                        upvalues: frame.upvalues,
+                        handlers,
+                    });
 
-                        Value::Enum(x) => {
-                            let value = x.get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                    // Generators are only resumed by `resume_generator`, which waits for this
+                    self.stack.push(value);
+                    self.pos = frame.return_addr;
+                    break;
+                }
 
-                        Value::Struct(x) => {
-                            let value = x.get_member(self, &member);
-                            self.stack.push(value);
-                        }
+                Inst::GET_PROP => {
+                    let member = self.pop();
+                    let target = self.pop();
 
-                        _ => panic!("Cannot get property on `{target:?}`"),
-                    }
+                    let value = self.get_property(target, &member)?;
+                    self.stack.push(value);
                 }
                 Inst::SET_PROP => {
                     let member = self.pop();
                     let target = self.pop();
                     let value = self.pop();
 
-                    match target {
-                        Value::List(mut x) => {
-                            x.set_member(&member, value);
-                        }
+                    self.set_property(target, &member, value)?;
+                }
 
-                        Value::Tuple(mut x) => {
-                            x.set_member(&member, value);
-                        }
+                Inst::END_ITER => {
+                    self.iterators.pop();
+                }
+                Inst::GET_ITER => {
+                    let value = self.pop();
+                    let iterator = TIterator::from_value(self, value)?;
+                    self.iterators.push(iterator);
+                }
+                Inst::FOR_ITER(jump_end) => {
+                    let iterator = self.iterators[self.iterators.len() - 1].clone();
 
-                        Value::Dict(mut x) => {
-                            x.set_member(&member, value);
-                        }
+                    if let Some(value) = iterator.next(self)? {
+                        self.stack.push(value);
+                    } else {
+                        self.iterators.pop();
+                        self.pos = *jump_end;
+                        continue;
+                    }
+                }
 
-                        Value::Namespace(x) => {
-                            x.borrow_mut().set_member(&member, value);
-                        }
+                Inst::TRY(catch_addr) => {
+                    let handler = Handler {
+                        catch_addr: *catch_addr,
+                        stack_len: self.stack.len(),
+                        slots_len: self.slots.len(),
+                        iterators_len: self.iterators.len(),
+                    };
+                    if let Some(frame) = self.call_stack.last_mut() {
+                        frame.handlers.push(handler);
+                    }
+                }
+                Inst::END_TRY => {
+                    if let Some(frame) = self.call_stack.last_mut() {
+                        frame.handlers.pop();
+                    }
+                }
+                Inst::THROW => {
+                    let value = self.pop();
+                    return Err(RuntimeError::from_value(value));
+                }
 
-                        Value::Struct(mut x) => {
-                            x.set_member(&member, value);
-                        }
+                Inst::MATCH => {
+                    let (a, b) = self.pop_two();
+                    let result = Self::values_equal(&a, &b);
 
-                        _ => panic!(
-                            "Cannot set property `{member:?}` on `{}`",
-                            target.to_string(false)
-                        ),
-                    }
+                    self.stack.push(Value::Bool(result));
                 }
 
-                Inst::GET_ITER => {
+                Inst::MATCH_SEQ { tuple, len, rest } => {
+                    let result = match self.pop() {
+                        Value::List(x) if !*tuple => Some(x.values.borrow().len()),
+                        Value::Tuple(x) if *tuple => Some(x.values.borrow().len()),
+                        _ => None,
+                    }
+                    .is_some_and(|items| if *rest { items >= *len } else { items == *len });
+
+                    self.stack.push(Value::Bool(result));
+                }
+                Inst::UNPACK { len, rest } => {
                     let value = self.pop();
+                    let (Value::List(x) | Value::Tuple(x)) = &value else {
+                        return Err(runtime_error!(
+                            TypeError,
+                            "Expected a list or tuple to destructure, got `{}`",
+                            value.get_type()
+                        ));
+                    };
 
-                    if let Value::String(s) = &value {
-                        let chars: Vec<Value> = s.0.chars().map(|c| Value::Char(c)).collect();
-                        self.iterators
-                            .push((Value::List(TList::new_tuple(rc!(RefCell::new(chars)))), 0)); // or a dedicated variant
-                    } else {
-                        self.iterators.push((value, 0));
+                    let items = x.values.borrow().len();
+                    if items < *len || (!*rest && items > *len) {
+                        return Err(runtime_error!(
+                            ValueError,
+                            "Expected {}{len} value(s) to destructure, got {items}",
+                            if *rest { "at least " } else { "" }
+                        ));
                     }
                 }
-                Inst::FOR_ITER(jump_end) => {
-                    let target_idx = self.iterators.len() - 1;
-                    let (value, idx) = &mut self.iterators[target_idx];
+                Inst::MATCH_TYPE(name) => {
+                    let result = match self.pop() {
+                        value @ (Value::Struct(_) | Value::Object(_)) => value.get_type() == *name,
+                        _ => false,
+                    };
 
-                    if let Value::List(list) = value {
-                        if *idx < list.values.borrow().len() {
-                            self.stack.push(list.values.borrow()[*idx].clone());
-                            *idx += 1;
-                        } else {
-                            self.iterators.pop();
-                            self.pos = *jump_end;
-                            continue;
-                        }
-                    } else if let Value::Tuple(list) = value {
-                        if *idx < list.values.borrow().len() {
-                            self.stack.push(list.values.borrow()[*idx].clone());
-                            *idx += 1;
-                        } else {
-                            self.iterators.pop();
-                            self.pos = *jump_end;
-                            continue;
+                    self.stack.push(Value::Bool(result));
+                }
+                Inst::MATCH_VARIANT(n) => {
+                    let def = self.pop();
+                    let value = self.pop();
+
+                    let def = match def {
+                        Value::VariantDef(def) => def,
+                        Value::Variant(variant) => variant.def.clone(),
+                        _ => {
+                            return Err(runtime_error!(
+                                TypeError,
+                                "Expected an enum variant in pattern, got `{}`",
+                                def.get_type()
+                            ));
                         }
-                    } else if let Value::Range {
-                        start,
-                        end,
-                        step,
-                        inclusive,
-                    } = value
-                    {
-                        let s = start.as_number();
-                        let e = end.as_number();
-                        let step_val = step.as_number();
-
-                        let current_value = s + (*idx as f64 * step_val);
-
-                        let is_within_bounds = if *inclusive {
-                            if step_val >= 0.0 {
-                                current_value <= e
-                            } else {
-                                current_value >= e
-                            }
-                        } else {
-                            if step_val >= 0.0 {
-                                current_value < e
-                            } else {
-                                current_value > e
-                            }
-                        };
+                    };
 
-                        if is_within_bounds {
-                            self.stack.push(Value::Number(current_value));
-                            *idx += 1;
-                        } else {
-                            // Range exhausted
-                            self.iterators.pop();
-                            self.pos = *jump_end;
-                            continue;
+                    let result = match value {
+                        Value::Variant(variant) if variant.def == def => {
+                            if *n > 0 && *n != def.fields.len() {
+                                return Err(runtime_error!(
+                                    TypeError,
+                                    "Pattern for `{}.{}` has {n} field(s), the variant has {}",
+                                    def.enum_name,
+                                    def.name,
+                                    def.fields.len()
+                                ));
+                            }
+                            true
                         }
-                    } else {
-                        panic!("Cannot iterate over {value:?}");
-                    }
+                        _ => false,
+                    };
+
+                    self.stack.push(Value::Bool(result));
                 }
+                Inst::MATCH_RANGE(inclusive) => {
+                    let end = self.pop();
+                    let start = self.pop();
 
-                Inst::MATCH => {
-                    let result = match self.pop_two() {
-                        (Value::NIL, Value::NIL) => true,
-                        (Value::Number(x), Value::Number(y)) => x == y,
-                        (Value::Bool(x), Value::Bool(y)) => x == y,
-                        (Value::Char(x), Value::Char(y)) => x == y,
-
-                        (Value::String(x), Value::String(y)) => {
-                            Rc::ptr_eq(&x.0, &y.0) || *x.0 == *y.0
+                    let result = match (self.pop(), start, end) {
+                        (Value::Number(x), Value::Number(start), Value::Number(end)) => {
+                            x >= start && if *inclusive { x <= end } else { x < end }
                         }
+                        _ => false,
+                    };
 
-                        (Value::Char(x), Value::String(y)) => x.to_string() == *y.0,
-                        (Value::String(y), Value::Char(x)) => x.to_string() == *y.0,
+                    self.stack.push(Value::Bool(result));
+                }
+                Inst::SLICE(start, end) => {
+                    let value = self.pop();
 
-                        (a, b) => a == b,
+                    let (Value::List(x) | Value::Tuple(x)) = &value else {
+                        return Err(runtime_error!(
+                            TypeError,
+                            "Cannot slice `{}`",
+                            value.get_type()
+                        ));
                     };
 
-                    self.stack.push(Value::Bool(result));
+                    let values = x.values.borrow();
+                    let items = values[*start..values.len() - end].to_vec();
+
+                    self.stack.push(if let Value::Tuple(_) = value {
+                        Value::Tuple(TList::new_tuple(rc!(RefCell::new(items))))
+                    } else {
+                        Value::List(TList::new(rc!(RefCell::new(items))))
+                    });
                 }
 
                 Inst::CONCAT_STR(n) => {
                     let values = (0..*n)
-                        .map(|_| self.pop().to_string(false))
-                        .collect::<String>();
+                        .map(|_| {
+                            let value = self.pop();
+                            self.stringify(&value)
+                        })
+                        .collect::<RuntimeResult<String>>()?;
                     self.stack.push(Value::String(TString::new(values)))
                 }
 
-                _ => panic!("Unimplemented instruction: {current:?}"),
+                _ => {
+                    return Err(runtime_error!(
+                        InternalError,
+                        "Unimplemented instruction: {current:?}"
+                    ));
+                }
             }
 
             self.advance();
         }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::{
+        compiler::compiler::Compiler,
+        language::{lexer::Lexer, nodes::Node, parser::Parser},
+        virtual_machine::{error::RuntimeResult, value::Value, vm::VM},
+    };
+
+    /// Compiles and runs `text`, returning the VM it ran on and the value of its last
+    /// expression (nil if it ends in a statement)
+    pub fn eval(text: &str) -> (VM, RuntimeResult) {
+        let mut parser = Parser::new(text.to_string(), Lexer::new(text).get_tokens());
+        let nodes = parser.parse_program();
+        assert!(
+            !parser.diagnostics.iter().any(|d| d.is_error()),
+            "syntax errors in test source"
+        );
+
+        let mut compiler = Compiler::new();
+        compiler.set_source("<test>", text);
+        for (i, node) in nodes.iter().enumerate() {
+            match node {
+                // Leave the value of the last expression on the stack, like the REPL
+                Node::ExprStmt(expr, span) if i == nodes.len() - 1 => {
+                    compiler.with_span(span, |compiler| compiler.compile_node(expr))
+                }
+                _ => compiler.compile_node(node),
+            }
+        }
+        compiler.resolve_type_hints();
+        assert!(compiler.errors.is_empty(), "compile errors in test source");
+
+        let mut vm = VM::new();
+        vm.constants = compiler.constants;
+        vm.instructions = rc!(compiler.instructions);
+        vm.intern_table = compiler.intern_table;
+        vm.files = compiler.files;
+        vm.lines = compiler.lines;
+
+        let result = vm.run(false, false).map(|_| vm.pop_or_nil());
+        (vm, result)
+    }
+
+    #[test]
+    fn tail_calls_reuse_the_frame() {
+        let (vm, result) = eval(
+            "fn count(n, total) {
+                if n == 0 { total } else { count(n - 1, total + n) }
+            }
+            count(1000000, 0)",
+        );
+
+        assert_eq!(result.unwrap(), Value::Number(500000500000.0));
+
+        // Capacity never shrinks, so it shows the deepest the recursion got
+        assert!(vm.call_stack.capacity() < 16, "{}", vm.call_stack.capacity());
+        assert!(vm.slots.capacity() < 16, "{}", vm.slots.capacity());
     }
 }